@@ -1,12 +1,15 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use antigravity_core::config::{load_config, expand_path, SchedulingMode as CoreSchedulingMode};
-use antigravity_core::proxy::{ProxyServer, TokenManager, StickySessionConfig, SchedulingMode};
+use antigravity_core::config::{load_config_with_path, expand_path};
+use antigravity_core::proxy::{ProxyServer, TokenManager, StickySessionConfig};
+use antigravity_core::proxy::server::SecurityConfig;
 
 pub async fn run(config_path: Option<PathBuf>, port_override: Option<u16>) -> anyhow::Result<()> {
     // Load configuration
-    let mut config = load_config(config_path)?;
+    let (mut config, loaded_from) = load_config_with_path(config_path)?;
+    // Kept pristine for the reloader so it diffs against the file, not the CLI overrides
+    let file_config = config.clone();
     
     // Apply port override if provided
     if let Some(port) = port_override {
@@ -14,11 +17,13 @@ pub async fn run(config_path: Option<PathBuf>, port_override: Option<u16>) -> an
     }
     
     let accounts_dir = expand_path(&config.accounts.directory);
+    let security_config = SecurityConfig::from_auth_config(&config.auth)?;
     
     tracing::info!("Starting Antigravity Proxy...");
     tracing::info!("  Port: {}", config.server.port);
     tracing::info!("  Host: {}", config.server.host);
     tracing::info!("  Accounts directory: {:?}", accounts_dir);
+    tracing::info!("  Auth: {:?} ({} API key(s))", security_config.auth_mode, security_config.keys.len());
     
     // Initialize token manager
     let token_manager = Arc::new(TokenManager::new(
@@ -35,15 +40,7 @@ pub async fn run(config_path: Option<PathBuf>, port_override: Option<u16>) -> an
     }
     
     // Update scheduling config
-    let scheduling = StickySessionConfig {
-        mode: match config.scheduling.mode {
-            CoreSchedulingMode::PerformanceFirst => SchedulingMode::PerformanceFirst,
-            CoreSchedulingMode::Balance => SchedulingMode::Balance,
-            CoreSchedulingMode::CacheFirst => SchedulingMode::CacheFirst,
-        },
-        max_wait_seconds: config.scheduling.max_wait_seconds,
-    };
-    token_manager.update_sticky_config(scheduling).await;
+    token_manager.update_sticky_config(StickySessionConfig::from(&config.scheduling)).await;
     
     // Create and start server
     let server = ProxyServer::new(
@@ -53,11 +50,19 @@ pub async fn run(config_path: Option<PathBuf>, port_override: Option<u16>) -> an
         config.model_mapping.anthropic.clone(),
         config.model_mapping.openai.clone(),
         config.model_mapping.custom.clone(),
-        config.timeouts.request_timeout,
-        config.auth.mode.clone(),
-        config.auth.api_key.clone(),
+        config.timeouts.clone(),
+        security_config,
+        config.metrics.clone(),
+        config.media.clone(),
+        config.generation.clone(),
     );
     
+    // Hot reload mappings, auth and scheduling from the config file
+    match loaded_from {
+        Some(path) => server.watch_config(path, file_config),
+        None => tracing::info!("No config file in use; hot reload disabled"),
+    }
+    
     tracing::info!("Proxy server starting on http://{}:{}", config.server.host, config.server.port);
     tracing::info!("Press Ctrl+C to stop");
     
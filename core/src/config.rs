@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Proxy server configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Config {
     #[serde(default)]
     pub server: ServerConfig,
@@ -25,9 +25,18 @@ pub struct Config {
     
     #[serde(default)]
     pub scheduling: SchedulingConfig,
+    
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    
+    #[serde(default)]
+    pub media: MediaConfig,
+    
+    #[serde(default)]
+    pub generation: GenerationConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ServerConfig {
     #[serde(default = "default_port")]
     pub port: u16,
@@ -63,16 +72,46 @@ impl Default for AuthMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct AuthConfig {
     #[serde(default)]
     pub mode: AuthMode,
     
+    /// Legacy single key, treated as an unrestricted (non-admin) key named "default"
     #[serde(default)]
     pub api_key: String,
+    
+    /// Named keys declared as `[[auth.keys]]`
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+/// A named client API key with optional restrictions
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    
+    pub key: String,
+    
+    /// RFC 3339 timestamp after which the key is rejected
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    
+    /// Upstream model globs this key may use (e.g. "gemini-*"), checked after mapping;
+    /// empty means all models
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    
+    /// Account ids or emails this key may draw from; unset means the whole pool
+    #[serde(default)]
+    pub accounts: Option<Vec<String>>,
+    
+    /// Grants access to the `/admin/*` API
+    #[serde(default)]
+    pub admin: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AccountsConfig {
     #[serde(default = "default_accounts_dir")]
     pub directory: PathBuf,
@@ -86,21 +125,56 @@ impl Default for AccountsConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TimeoutsConfig {
+    /// Total time for a non-streaming upstream call, including reading the body (seconds)
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
+    
+    /// TCP/TLS connect timeout for upstream calls (seconds, requires restart)
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    
+    /// Time until a stream delivers its first chunk (seconds)
+    #[serde(default = "default_stream_first_byte_timeout")]
+    pub stream_first_byte_timeout: u64,
+    
+    /// Longest allowed gap between two stream chunks (seconds)
+    #[serde(default = "default_stream_idle_timeout")]
+    pub stream_idle_timeout: u64,
+    
+    /// Per-model overrides keyed by model glob, matched against the mapped upstream model
+    /// (e.g. `"*-thinking"`, `"gemini-3-pro-image"`)
+    #[serde(default)]
+    pub models: HashMap<String, ModelTimeoutsConfig>,
 }
 
 impl Default for TimeoutsConfig {
     fn default() -> Self {
         Self {
             request_timeout: default_request_timeout(),
+            connect_timeout: default_connect_timeout(),
+            stream_first_byte_timeout: default_stream_first_byte_timeout(),
+            stream_idle_timeout: default_stream_idle_timeout(),
+            models: HashMap::new(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Timeout overrides for models matching a glob; unset fields use the global value
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ModelTimeoutsConfig {
+    #[serde(default)]
+    pub request_timeout: Option<u64>,
+    
+    #[serde(default)]
+    pub stream_first_byte_timeout: Option<u64>,
+    
+    #[serde(default)]
+    pub stream_idle_timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct ModelMappingConfig {
     #[serde(default)]
     pub anthropic: HashMap<String, String>,
@@ -112,7 +186,7 @@ pub struct ModelMappingConfig {
     pub custom: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
     pub level: String,
@@ -144,7 +218,7 @@ impl Default for SchedulingMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SchedulingConfig {
     #[serde(default)]
     pub mode: SchedulingMode,
@@ -162,6 +236,100 @@ impl Default for SchedulingConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetricsConfig {
+    /// Expose Prometheus metrics at `/metrics`
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    
+    /// Label series with a short hash of the account email instead of the email itself
+    #[serde(default = "default_true")]
+    pub hash_account_emails: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hash_account_emails: true,
+        }
+    }
+}
+
+/// Server-side fetching of remote `image_url` / document URLs (requires restart)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MediaConfig {
+    /// Download `http(s)://` media; when off such URLs are rejected
+    #[serde(default = "default_true")]
+    pub fetch_remote: bool,
+    
+    /// Largest accepted download (bytes)
+    #[serde(default = "default_media_max_bytes")]
+    pub max_bytes: u64,
+    
+    /// Time for the whole download, redirects included (seconds)
+    #[serde(default = "default_media_timeout")]
+    pub timeout: u64,
+    
+    /// Accepted MIME types; `image/*` style wildcards are allowed
+    #[serde(default = "default_media_mime_types")]
+    pub allowed_mime_types: Vec<String>,
+    
+    /// Hosts that may be fetched (`example.com` also matches subdomains); empty allows any public host
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+    
+    /// Hosts that are never fetched, checked before `allow_hosts`
+    #[serde(default)]
+    pub deny_hosts: Vec<String>,
+    
+    /// Allow loopback, private and link-local addresses (off to prevent SSRF)
+    #[serde(default)]
+    pub allow_private_networks: bool,
+    
+    /// Downloads kept in the in-memory cache, keyed by URL hash
+    #[serde(default = "default_media_cache_entries")]
+    pub cache_entries: usize,
+    
+    /// Lifetime of a cached download (seconds)
+    #[serde(default = "default_media_cache_ttl")]
+    pub cache_ttl: u64,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            fetch_remote: true,
+            max_bytes: default_media_max_bytes(),
+            timeout: default_media_timeout(),
+            allowed_mime_types: default_media_mime_types(),
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            allow_private_networks: false,
+            cache_entries: default_media_cache_entries(),
+            cache_ttl: default_media_cache_ttl(),
+        }
+    }
+}
+
+/// Defaults applied to upstream generation requests
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GenerationConfig {
+    /// Stop sequences sent upstream on every Claude request; they cut off runaway output
+    /// such as the model writing the next user turn. Gemini accepts at most 5, so the
+    /// client's own `stop_sequences` come first and these fill the remaining slots
+    #[serde(default = "default_stop_sequences")]
+    pub default_stop_sequences: Vec<String>,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            default_stop_sequences: default_stop_sequences(),
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -172,6 +340,9 @@ impl Default for Config {
             model_mapping: ModelMappingConfig::default(),
             logging: LoggingConfig::default(),
             scheduling: SchedulingConfig::default(),
+            metrics: MetricsConfig::default(),
+            media: MediaConfig::default(),
+            generation: GenerationConfig::default(),
         }
     }
 }
@@ -180,8 +351,30 @@ impl Default for Config {
 fn default_port() -> u16 { 8045 }
 fn default_host() -> String { "127.0.0.1".to_string() }
 fn default_request_timeout() -> u64 { 120 }
+fn default_connect_timeout() -> u64 { 20 }
+fn default_stream_first_byte_timeout() -> u64 { 60 }
+fn default_stream_idle_timeout() -> u64 { 120 }
 fn default_log_level() -> String { "info".to_string() }
 fn default_max_wait_seconds() -> u64 { 30 }
+fn default_true() -> bool { true }
+fn default_media_max_bytes() -> u64 { 20 * 1024 * 1024 }
+fn default_media_timeout() -> u64 { 15 }
+fn default_media_cache_entries() -> usize { 64 }
+fn default_media_cache_ttl() -> u64 { 600 }
+
+fn default_media_mime_types() -> Vec<String> {
+    ["image/*", "application/pdf", "text/plain", "audio/*", "video/*"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+pub fn default_stop_sequences() -> Vec<String> {
+    ["<|user|>", "<|endoftext|>", "<|end_of_turn|>", "[DONE]", "\n\nHuman:"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
 
 fn default_accounts_dir() -> PathBuf {
     dirs::home_dir()
@@ -207,12 +400,17 @@ pub fn default_config_path() -> PathBuf {
 /// 2. ./config.toml (if exists)
 /// 3. default_config_path() (usually ~/.config/antigravity-proxy/config.toml)
 pub fn load_config(path: Option<PathBuf>) -> anyhow::Result<Config> {
+    load_config_with_path(path).map(|(config, _)| config)
+}
+
+/// Same as [`load_config`], but also returns the file the config was read from
+/// (`None` when falling back to defaults). Used to watch the file for hot reload.
+pub fn load_config_with_path(path: Option<PathBuf>) -> anyhow::Result<(Config, Option<PathBuf>)> {
     if let Some(config_path) = path {
         if config_path.exists() {
-            let content = std::fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&content)?;
+            let config = read_config_file(&config_path)?;
             tracing::info!("Loaded config from specified path {:?}", config_path);
-            return Ok(config);
+            return Ok((config, Some(config_path)));
         } else {
             anyhow::bail!("Specified config file not found: {:?}", config_path);
         }
@@ -221,36 +419,35 @@ pub fn load_config(path: Option<PathBuf>) -> anyhow::Result<Config> {
     // Try current directory config.toml
     let local_config = PathBuf::from("config.toml");
     if local_config.exists() {
-        match std::fs::read_to_string(&local_config) {
-            Ok(content) => {
-                match toml::from_str::<Config>(&content) {
-                    Ok(config) => {
-                        tracing::info!("Loaded config from current directory {:?}", local_config);
-                        return Ok(config);
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to parse ./config.toml: {}. Falling back to default path.", e);
-                    }
-                }
+        match read_config_file(&local_config) {
+            Ok(config) => {
+                tracing::info!("Loaded config from current directory {:?}", local_config);
+                return Ok((config, Some(local_config)));
             }
             Err(e) => {
-                tracing::error!("Failed to read ./config.toml: {}. Falling back to default path.", e);
+                tracing::error!("Failed to load ./config.toml: {}. Falling back to default path.", e);
             }
         }
     }
 
     let default_path = default_config_path();
     if default_path.exists() {
-        let content = std::fs::read_to_string(&default_path)?;
-        let config: Config = toml::from_str(&content)?;
+        let config = read_config_file(&default_path)?;
         tracing::info!("Loaded config from default path {:?}", default_path);
-        Ok(config)
+        Ok((config, Some(default_path)))
     } else {
         tracing::info!("No config file found, using defaults");
-        Ok(Config::default())
+        Ok((Config::default(), None))
     }
 }
 
+/// Read and parse a single config file
+pub fn read_config_file(path: &Path) -> anyhow::Result<Config> {
+    let content = std::fs::read_to_string(path)?;
+    let config: Config = toml::from_str(&content)?;
+    Ok(config)
+}
+
 /// Expand ~ in path to home directory
 pub fn expand_path(path: &PathBuf) -> PathBuf {
     if let Some(path_str) = path.to_str() {
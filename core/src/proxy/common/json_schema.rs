@@ -71,7 +71,17 @@ fn clean_json_schema_recursive(value: &mut Value) {
         Value::Object(map) => {
             // 1. [CRITICAL] 深度递归处理：必须遍历当前对象的所有字段名对应的 Value
             // 解决 properties/items 之外的 definitions、anyOf、allOf 等结构的清理
-            for v in map.values_mut() {
+            for (key, v) in map.iter_mut() {
+                // properties 是 "属性名 -> schema" 的映射, 其本身不是 schema:
+                // 只清理各属性的 schema, 避免误删名为 pattern/format/default 等的属性
+                if key == "properties" {
+                    if let Value::Object(props) = v {
+                        for prop in props.values_mut() {
+                            clean_json_schema_recursive(prop);
+                        }
+                        continue;
+                    }
+                }
                 clean_json_schema_recursive(v);
             }
 
@@ -96,8 +106,10 @@ fn clean_json_schema_recursive(value: &mut Value) {
             for (field, label) in validation_fields {
                 if let Some(val) = map.remove(field) {
                     // 仅当值是简单类型时才迁移
-                    if val.is_string() || val.is_number() || val.is_boolean() {
-                        constraints.push(format!("{}: {}", label, val));
+                    match &val {
+                        Value::String(s) => constraints.push(format!("{}: {}", label, s)),
+                        Value::Number(_) | Value::Bool(_) => constraints.push(format!("{}: {}", label, val)),
+                        _ => {}
                     }
                 }
             }
@@ -53,6 +53,11 @@ static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|
     m
 });
 
+/// 内置映射表的精确匹配 (不做前缀穿透和默认回退)
+pub fn builtin_model_mapping(input: &str) -> Option<&'static str> {
+    CLAUDE_TO_GEMINI.get(input).copied()
+}
+
 pub fn map_claude_model_to_gemini(input: &str) -> String {
     // 1. Check exact match in map
     if let Some(mapped) = CLAUDE_TO_GEMINI.get(input) {
@@ -68,6 +73,25 @@ pub fn map_claude_model_to_gemini(input: &str) -> String {
     "claude-sonnet-4-5".to_string()
 }
 
+/// 未知模型的默认输出上限
+pub const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 64000;
+
+/// 上游模型的最大输出 token 数 (按映射后的模型名)
+pub fn max_output_tokens(model: &str) -> u32 {
+    let model = model.to_lowercase();
+    if model.contains("-image") {
+        32768
+    } else if model.starts_with("gemini-1.5") || model.starts_with("gemini-2.0") {
+        8192
+    } else if model.starts_with("gemini-") {
+        65536
+    } else if model.starts_with("claude-opus-4") && !model.starts_with("claude-opus-4-5") {
+        32000
+    } else {
+        DEFAULT_MAX_OUTPUT_TOKENS
+    }
+}
+
 /// 获取所有内置支持的模型列表关键字
 pub fn get_supported_models() -> Vec<String> {
     CLAUDE_TO_GEMINI.keys().map(|s| s.to_string()).collect()
@@ -267,4 +291,14 @@ mod tests {
             "claude-sonnet-4-5"
         );
     }
+
+    #[test]
+    fn test_max_output_tokens() {
+        assert_eq!(max_output_tokens("gemini-2.5-flash"), 65536);
+        assert_eq!(max_output_tokens("gemini-3-pro-image"), 32768);
+        assert_eq!(max_output_tokens("gemini-2.0-flash-exp"), 8192);
+        assert_eq!(max_output_tokens("claude-opus-4-5-thinking"), 64000);
+        assert_eq!(max_output_tokens("claude-opus-4-1"), 32000);
+        assert_eq!(max_output_tokens("unknown"), DEFAULT_MAX_OUTPUT_TOKENS);
+    }
 }
@@ -18,3 +18,51 @@ pub fn _deprecated_infer_quota_group(model: &str) -> String {
         "gemini".to_string()
     }
 }
+
+/// 简单的 glob 匹配 (支持 `*` 与 `?`)，用于模型白名单与按模型覆盖的配置
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<usize> = None;
+    let mut star_ti = 0;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some(pi);
+            star_ti = ti;
+            pi += 1;
+        } else if let Some(s) = star {
+            pi = s + 1;
+            star_ti += 1;
+            ti = star_ti;
+        } else {
+            return false;
+        }
+    }
+
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "gemini-2.5-flash"));
+        assert!(glob_match("gemini-*", "gemini-2.5-flash"));
+        assert!(glob_match("*-thinking", "claude-sonnet-4-5-thinking"));
+        assert!(glob_match("claude-?-5*", "claude-3-5-sonnet"));
+        assert!(glob_match("gpt-4o", "gpt-4o"));
+        assert!(!glob_match("gpt-4o", "gpt-4o-mini"));
+        assert!(!glob_match("gemini-*", "claude-sonnet-4-5"));
+    }
+}
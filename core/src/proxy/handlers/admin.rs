@@ -0,0 +1,408 @@
+//! Runtime admin API
+//! Handles /admin/* — account pool, model mappings and scheduling, without a restart
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
+    Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use crate::proxy::middleware::auth::ClientKey;
+use crate::proxy::rate_limit::model_family;
+use crate::proxy::server::AppState;
+use crate::proxy::sticky_config::{SchedulingMode, StickySessionConfig};
+use crate::proxy::token_manager::AccountError;
+
+/// Build the `/admin` router (nested under `/admin` by the server)
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/accounts", get(handle_list_accounts))
+        .route("/accounts/reload", post(handle_reload_accounts))
+        .route("/accounts/:account_id/enable", post(handle_enable_account))
+        .route("/accounts/:account_id/disable", post(handle_disable_account))
+        .route("/accounts/:account_id/cooldown", delete(handle_clear_cooldown))
+        .route("/mappings", get(handle_get_mappings))
+        .route(
+            "/mappings/:table",
+            get(handle_get_mapping_table)
+                .put(handle_replace_mapping_table)
+                .patch(handle_patch_mapping_table),
+        )
+        .route("/scheduling", get(handle_get_scheduling).put(handle_update_scheduling))
+        .route("/usage", get(handle_usage))
+        .route_layer(middleware::from_fn(require_admin))
+}
+
+/// Require a key with the admin flag, even when `[auth].mode` is `off`.
+/// The auth middleware has already resolved the key into request extensions.
+async fn require_admin(request: Request, next: Next) -> Response {
+    match request.extensions().get::<ClientKey>() {
+        Some(key) if key.admin => next.run(request).await,
+        Some(key) => {
+            tracing::warn!("API key '{}' attempted to access the admin API", key.name);
+            admin_error(
+                StatusCode::FORBIDDEN,
+                format!("API key '{}' is not an admin key", key.name),
+            )
+        }
+        None => admin_error(StatusCode::UNAUTHORIZED, "Admin API requires an admin API key"),
+    }
+}
+
+fn admin_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": { "message": message.into() } }))).into_response()
+}
+
+/// GET /admin/accounts
+async fn handle_list_accounts(State(state): State<AppState>) -> Response {
+    let accounts = state.token_manager.list_accounts();
+    Json(json!({ "total": accounts.len(), "accounts": accounts })).into_response()
+}
+
+/// POST /admin/accounts/reload
+async fn handle_reload_accounts(State(state): State<AppState>) -> Response {
+    match state.token_manager.load_accounts().await {
+        Ok(count) => {
+            tracing::info!("[Admin] Reloaded {} account(s)", count);
+            state.models.invalidate().await;
+            Json(json!({ "loaded": count })).into_response()
+        }
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// POST /admin/accounts/:account_id/enable
+async fn handle_enable_account(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Response {
+    set_account_enabled(&state, &account_id, true).await
+}
+
+/// POST /admin/accounts/:account_id/disable
+async fn handle_disable_account(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Response {
+    set_account_enabled(&state, &account_id, false).await
+}
+
+async fn set_account_enabled(state: &AppState, account_id: &str, enabled: bool) -> Response {
+    match state.token_manager.set_account_enabled(account_id, enabled).await {
+        Ok(()) => {
+            tracing::info!(
+                "[Admin] Account {} {}",
+                account_id,
+                if enabled { "enabled" } else { "disabled" }
+            );
+            Json(json!({ "account_id": account_id, "enabled": enabled })).into_response()
+        }
+        Err(e) => {
+            let status = match e {
+                AccountError::InvalidId(_) => StatusCode::BAD_REQUEST,
+                AccountError::NotFound(_) => StatusCode::NOT_FOUND,
+                AccountError::DisabledInFile(_) => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            admin_error(status, e.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CooldownQuery {
+    /// Clear only the family of this model (all families when omitted)
+    model: Option<String>,
+}
+
+/// DELETE /admin/accounts/:account_id/cooldown[?model=<model>]
+async fn handle_clear_cooldown(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+    Query(query): Query<CooldownQuery>,
+) -> Response {
+    let family = query.model.as_deref().map(model_family);
+    let cleared = state.token_manager.clear_rate_limit(&account_id, family.as_deref());
+    if cleared {
+        tracing::info!(
+            "[Admin] Cleared cooldown for account {} ({})",
+            account_id,
+            family.as_deref().unwrap_or("all models")
+        );
+    }
+    Json(json!({ "account_id": account_id, "family": family, "cleared": cleared })).into_response()
+}
+
+/// Resolve a mapping table name to its lock in `AppState`
+fn mapping_table<'a>(state: &'a AppState, table: &str) -> Option<&'a RwLock<HashMap<String, String>>> {
+    match table {
+        "custom" | "custom_mapping" => Some(&state.custom_mapping),
+        "openai" | "openai_mapping" => Some(&state.openai_mapping),
+        "anthropic" | "anthropic_mapping" => Some(&state.anthropic_mapping),
+        _ => None,
+    }
+}
+
+fn unknown_table(table: &str) -> Response {
+    admin_error(
+        StatusCode::NOT_FOUND,
+        format!("Unknown mapping table '{}' (expected custom, openai or anthropic)", table),
+    )
+}
+
+/// GET /admin/mappings
+async fn handle_get_mappings(State(state): State<AppState>) -> Response {
+    Json(json!({
+        "custom": *state.custom_mapping.read().await,
+        "openai": *state.openai_mapping.read().await,
+        "anthropic": *state.anthropic_mapping.read().await,
+    }))
+    .into_response()
+}
+
+/// GET /admin/mappings/:table
+async fn handle_get_mapping_table(
+    State(state): State<AppState>,
+    Path(table): Path<String>,
+) -> Response {
+    match mapping_table(&state, &table) {
+        Some(lock) => Json(json!(*lock.read().await)).into_response(),
+        None => unknown_table(&table),
+    }
+}
+
+/// PUT /admin/mappings/:table — replace the whole table
+async fn handle_replace_mapping_table(
+    State(state): State<AppState>,
+    Path(table): Path<String>,
+    Json(body): Json<HashMap<String, String>>,
+) -> Response {
+    let Some(lock) = mapping_table(&state, &table) else {
+        return unknown_table(&table);
+    };
+
+    let mut mapping = lock.write().await;
+    *mapping = body;
+    tracing::info!("[Admin] Replaced {} mapping ({} entries)", table, mapping.len());
+    Json(json!(*mapping)).into_response()
+}
+
+/// PATCH /admin/mappings/:table — upsert entries; a `null` value removes the entry
+async fn handle_patch_mapping_table(
+    State(state): State<AppState>,
+    Path(table): Path<String>,
+    Json(body): Json<HashMap<String, Option<String>>>,
+) -> Response {
+    let Some(lock) = mapping_table(&state, &table) else {
+        return unknown_table(&table);
+    };
+
+    let mut mapping = lock.write().await;
+    for (from, to) in body {
+        match to {
+            Some(to) => {
+                tracing::info!("[Admin] {} mapping: {} -> {}", table, from, to);
+                mapping.insert(from, to);
+            }
+            None => {
+                tracing::info!("[Admin] {} mapping: removed {}", table, from);
+                mapping.remove(&from);
+            }
+        }
+    }
+    Json(json!(*mapping)).into_response()
+}
+
+/// GET /admin/scheduling
+async fn handle_get_scheduling(State(state): State<AppState>) -> Response {
+    Json(state.token_manager.get_sticky_config().await).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct SchedulingUpdate {
+    mode: Option<SchedulingMode>,
+    max_wait_seconds: Option<u64>,
+}
+
+/// PUT /admin/scheduling — fields left out keep their current value
+async fn handle_update_scheduling(
+    State(state): State<AppState>,
+    Json(update): Json<SchedulingUpdate>,
+) -> Response {
+    let current = state.token_manager.get_sticky_config().await;
+    let new_config = StickySessionConfig {
+        mode: update.mode.unwrap_or_else(|| current.mode.clone()),
+        max_wait_seconds: update.max_wait_seconds.unwrap_or(current.max_wait_seconds),
+    };
+
+    // Session bindings made under the old mode no longer apply
+    if new_config.mode != current.mode {
+        state.token_manager.clear_all_sessions();
+    }
+
+    tracing::info!(
+        "[Admin] Scheduling: {:?} -> {:?} (max wait {}s)",
+        current.mode,
+        new_config.mode,
+        new_config.max_wait_seconds
+    );
+    state.token_manager.update_sticky_config(new_config.clone()).await;
+    Json(new_config).into_response()
+}
+
+/// GET /admin/usage — per API key usage since startup
+async fn handle_usage(State(state): State<AppState>) -> Response {
+    let keys: Vec<Value> = state
+        .usage
+        .snapshot()
+        .into_iter()
+        .map(|(name, usage)| json!({ "name": name, "usage": usage }))
+        .collect();
+    Json(json!({ "keys": keys })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use reqwest::Method;
+
+    const CONFIG: &str = r#"
+        [auth]
+        mode = "strict"
+        api_key = "sk-shared"
+
+        [[auth.keys]]
+        name = "ops"
+        key = "sk-admin"
+        admin = true
+
+        [model_mapping.openai]
+        gpt-4o = "gemini-2.5-flash"
+    "#;
+
+    fn test_state() -> AppState {
+        AppState::for_tests(&toml::from_str::<Config>(CONFIG).unwrap())
+    }
+
+    /// Same layering as the server: auth middleware resolves the key, then `require_admin`
+    fn app(state: &AppState) -> Router {
+        Router::new()
+            .nest("/admin", router())
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                crate::proxy::middleware::auth::auth_middleware,
+            ))
+            .with_state(state.clone())
+    }
+
+    async fn send(state: &AppState, method: Method, uri: &str, key: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app(state);
+        let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut request = reqwest::Client::new().request(method, format!("http://{}{}", addr, uri));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        server.abort();
+
+        let status = StatusCode::from_u16(response.status().as_u16()).unwrap();
+        (status, response.json().await.unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_require_admin() {
+        let state = test_state();
+        let (status, _) = send(&state, Method::GET, "/admin/mappings", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // The legacy shared key is an ordinary client key
+        let (status, body) = send(&state, Method::GET, "/admin/mappings", Some("sk-shared"), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body["error"]["message"].as_str().unwrap().contains("not an admin key"));
+
+        let (status, body) = send(&state, Method::GET, "/admin/mappings", Some("sk-admin"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["openai"]["gpt-4o"], "gemini-2.5-flash");
+    }
+
+    #[tokio::test]
+    async fn test_account_errors() {
+        let state = test_state();
+        let (status, _) = send(&state, Method::POST, "/admin/accounts/missing/enable", Some("sk-admin"), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&state, Method::POST, "/admin/accounts/..%2Fconfig/disable", Some("sk-admin"), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_mapping_put_and_patch() {
+        let state = test_state();
+        let (status, body) = send(
+            &state,
+            Method::PUT,
+            "/admin/mappings/openai",
+            Some("sk-admin"),
+            Some(json!({"gpt-4": "gemini-2.5-pro", "o1": "claude-opus-4-5-thinking"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"gpt-4": "gemini-2.5-pro", "o1": "claude-opus-4-5-thinking"}));
+
+        // PATCH upserts; null removes
+        let (_, body) = send(
+            &state,
+            Method::PATCH,
+            "/admin/mappings/openai",
+            Some("sk-admin"),
+            Some(json!({"gpt-4": "gemini-3-pro-high", "o1": null, "gpt-4o": "gemini-2.5-flash"})),
+        )
+        .await;
+        assert_eq!(body, json!({"gpt-4": "gemini-3-pro-high", "gpt-4o": "gemini-2.5-flash"}));
+        assert_eq!(state.openai_mapping.read().await.get("gpt-4").map(String::as_str), Some("gemini-3-pro-high"));
+
+        let (status, _) = send(&state, Method::PUT, "/admin/mappings/nope", Some("sk-admin"), Some(json!({}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_clear_cooldown_by_model() {
+        let state = test_state();
+        let tokens = &state.token_manager;
+        tokens.mark_rate_limited("acc-1", "gemini-2.5-flash", 429, Some("60"), "");
+        tokens.mark_rate_limited("acc-1", "claude-sonnet-4-5", 429, Some("60"), "");
+
+        let (status, body) = send(
+            &state,
+            Method::DELETE,
+            "/admin/accounts/acc-1/cooldown?model=gemini-2.5-flash",
+            Some("sk-admin"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["family"], "gemini-flash");
+        assert_eq!(body["cleared"], true);
+        assert!(!tokens.is_rate_limited("acc-1", "gemini-2.5-flash"));
+        assert!(tokens.is_rate_limited("acc-1", "claude-sonnet-4-5"));
+
+        let (_, body) = send(&state, Method::DELETE, "/admin/accounts/acc-1/cooldown", Some("sk-admin"), None).await;
+        assert_eq!(body["cleared"], true);
+        assert!(!tokens.is_rate_limited("acc-1", "claude-sonnet-4-5"));
+    }
+}
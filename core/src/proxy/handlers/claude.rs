@@ -9,21 +9,95 @@ use axum::{
 use bytes::Bytes;
 use futures::StreamExt;
 use serde_json::{json, Value};
-use tokio::time::{sleep, Duration};
 use tracing::{debug, info};
 
 use crate::proxy::mappers::claude::{
-    transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
+    resolve_claude_config, transform_claude_request_with_defaults, transform_response, create_claude_sse_stream,
+    validate_tool_choice, ClaudeRequest,
 };
+use crate::proxy::mappers::claude::count_tokens::{
+    build_count_tokens_request, estimate_input_tokens, parse_total_tokens,
+};
+use crate::proxy::metrics::{metrics, RequestLabels};
+use crate::proxy::middleware::auth::{model_not_allowed, ClientKey, ClientProtocol};
 use crate::proxy::server::AppState;
+use crate::proxy::session_manager::SessionManager;
+use crate::proxy::upstream::dispatcher::{dispatch, dispatch_once, DispatchError, DispatchRequest, Dispatched};
+use crate::proxy::upstream::timeouts::{timeout_response, with_stream_timeouts, ModelTimeouts};
 use axum::http::HeaderMap;
+use axum::Extension;
 
-const MAX_RETRY_ATTEMPTS: usize = 3;
 const MIN_SIGNATURE_LENGTH: usize = 10;
-const JITTER_FACTOR: f64 = 0.2;
+
+/// countTokens 上游调用上限，超时即改用本地估算
+const COUNT_TOKENS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
 use crate::proxy::mappers::claude::models::{ContentBlock, Message, MessageContent};
 
+/// 下载 `type: "url"` 的图片/文档来源并就地替换为 base64 来源
+async fn resolve_remote_sources(state: &AppState, messages: &mut [Message]) -> Result<(), String> {
+    for msg in messages.iter_mut() {
+        let MessageContent::Array(blocks) = &mut msg.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            if let ContentBlock::ToolResult { content, .. } = block {
+                resolve_tool_result_sources(state, content).await?;
+                continue;
+            }
+            let (source_type, media_type, data, url) = match block {
+                ContentBlock::Image { source, .. } => {
+                    (&mut source.source_type, &mut source.media_type, &mut source.data, &mut source.url)
+                }
+                ContentBlock::Document { source, .. } => {
+                    (&mut source.source_type, &mut source.media_type, &mut source.data, &mut source.url)
+                }
+                _ => continue,
+            };
+            if source_type != "url" {
+                continue;
+            }
+            let Some(remote) = url.take() else {
+                return Err("source.url is required for url sources".to_string());
+            };
+            let media = state
+                .media
+                .fetch(&remote)
+                .await
+                .map_err(|e| format!("Failed to fetch {}: {}", remote, e))?;
+            *source_type = "base64".to_string();
+            *media_type = media.mime_type;
+            *data = media.data;
+        }
+    }
+    Ok(())
+}
+
+/// tool_result 内的 url 图片/文档同样下载为 base64
+async fn resolve_tool_result_sources(state: &AppState, content: &mut Value) -> Result<(), String> {
+    let Some(blocks) = content.as_array_mut() else {
+        return Ok(());
+    };
+    for block in blocks.iter_mut() {
+        if !matches!(block.get("type").and_then(|v| v.as_str()), Some("image" | "document")) {
+            continue;
+        }
+        let Some(source) = block.get_mut("source").filter(|s| s["type"] == "url") else {
+            continue;
+        };
+        let Some(remote) = source.get("url").and_then(|v| v.as_str()).map(str::to_string) else {
+            return Err("source.url is required for url sources".to_string());
+        };
+        let media = state
+            .media
+            .fetch(&remote)
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", remote, e))?;
+        *source = json!({ "type": "base64", "media_type": media.mime_type, "data": media.data });
+    }
+    Ok(())
+}
+
 /// 检查 thinking 块是否有有效签名
 fn has_valid_signature(block: &ContentBlock) -> bool {
     match block {
@@ -110,20 +184,15 @@ fn remove_trailing_unsigned_thinking(blocks: &mut Vec<ContentBlock>) {
     }
 }
 
-/// Apply jitter to delay
-fn apply_jitter(delay_ms: u64) -> u64 {
-    use rand::Rng;
-    let jitter_range = (delay_ms as f64 * JITTER_FACTOR) as i64;
-    let jitter: i64 = rand::rng().random_range(-jitter_range..=jitter_range);
-    ((delay_ms as i64) + jitter).max(1) as u64
-}
-
 /// 处理 Claude messages 请求
 pub async fn handle_messages(
     State(state): State<AppState>,
-    _headers: HeaderMap,
+    headers: HeaderMap,
+    client_key: Option<Extension<ClientKey>>,
     Json(body): Json<Value>,
 ) -> Response {
+    let client_key = client_key.map(|Extension(k)| k);
+
     // 生成随机 Trace ID
     let trace_id: String = {
         use rand::Rng;
@@ -155,217 +224,359 @@ pub async fn handle_messages(
     // 过滤无效 Thinking 块
     filter_invalid_thinking_blocks(&mut request.messages);
     
+    let key_name = client_key.as_ref().map(|k| k.name.as_str()).unwrap_or("-");
     info!(
-        "[{}] Claude Request | Model: {} | Stream: {} | Messages: {}",
+        "[{}] Claude Request | Key: {} | Model: {} | Stream: {} | Messages: {}",
         trace_id,
+        key_name,
         request.model,
         request.stream,
         request.messages.len()
     );
 
-    let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
-    
-    let pool_size = token_manager.len();
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
+    // 模型路由
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &request.model,
+        &*state.custom_mapping.read().await,
+        &*state.openai_mapping.read().await,
+        &*state.anthropic_mapping.read().await,
+        true,
+    );
 
-    let mut last_error = String::new();
-    let mut last_status: Option<u16> = None;
-    let mut request_for_body = request.clone();
-    
-    for attempt in 0..max_attempts {
-        // 模型路由
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &request_for_body.model,
-            &*state.custom_mapping.read().await,
-            &*state.openai_mapping.read().await,
-            &*state.anthropic_mapping.read().await,
-            true,
-        );
-        
-        // 将 Claude 工具转为 Value 数组
-        let tools_val: Option<Vec<Value>> = request_for_body.tools.as_ref().map(|list| {
-            list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
-        });
+    let config = resolve_claude_config(&mapped_model, &request.tools);
 
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
-            &request_for_body.model, 
-            &mapped_model, 
-            &tools_val
-        );
+    // 校验 API Key 的模型白名单: 以实际下发的模型为准 (不匹配客户端别名)
+    if let Some(key) = &client_key {
+        if !key.allows_model(&config.final_model) {
+            return model_not_allowed(ClientProtocol::Anthropic, &key.name, &config.final_model);
+        }
+    }
 
-        // 获取 token
-        let force_rotate = attempt > 0;
-        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, force_rotate, None).await {
-            Ok(t) => t,
-            Err(e) => {
-                return (
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    Json(json!({
-                        "type": "error",
-                        "error": {
-                            "type": "overloaded_error",
-                            "message": format!("No available accounts: {}", e)
-                        }
-                    }))
-                ).into_response();
-            }
-        };
+    // tool_choice 引用了不存在的工具时直接拒绝，避免静默退化为自由文本
+    // (纯校验须在下载远程资源之前完成，无效请求不应触发任何外部请求)
+    if let Err(e) = validate_tool_choice(&request) {
+        return invalid_request_response(e);
+    }
 
-        info!("[{}] Using account: {} (model: {})", trace_id, email, mapped_model);
-        
-        // 准备请求
-        let mut request_with_mapped = request_for_body.clone();
-        
-        // 清理尾部无签名 thinking 块
-        for msg in request_with_mapped.messages.iter_mut() {
-            if msg.role == "assistant" || msg.role == "model" {
-                if let MessageContent::Array(blocks) = &mut msg.content {
-                    remove_trailing_unsigned_thinking(blocks);
+    // 远程图片/文档需先下载为 inlineData
+    if let Err(e) = resolve_remote_sources(&state, &mut request.messages).await {
+        tracing::warn!("[{}] {}", trace_id, e);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": e
                 }
+            }))
+        ).into_response();
+    }
+
+    // 准备请求
+    let mut request_with_mapped = request.clone();
+
+    // 清理尾部无签名 thinking 块
+    for msg in request_with_mapped.messages.iter_mut() {
+        if msg.role == "assistant" || msg.role == "model" {
+            if let MessageContent::Array(blocks) = &mut msg.content {
+                remove_trailing_unsigned_thinking(blocks);
             }
         }
-        
-        request_with_mapped.model = mapped_model.clone();
-
-        // 转换请求
-        let gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id) {
-            Ok(b) => {
-                debug!("[{}] Transformed body: {}", trace_id, serde_json::to_string_pretty(&b).unwrap_or_default());
-                b
-            },
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "type": "error",
-                        "error": {
-                            "type": "api_error",
-                            "message": format!("Transform error: {}", e)
-                        }
-                    }))
-                ).into_response();
-            }
+    }
+
+    request_with_mapped.model = mapped_model.clone();
+
+    // 调用上游 (账号轮换/重试由 dispatcher 统一处理)
+    // 非流式: request_timeout 覆盖请求与读取响应体; 流式: 响应头也计入首字节超时
+    let is_stream = request.stream;
+    let generation = state.generation.read().await.clone();
+    let stop_sequences = request.stop_sequences.clone().unwrap_or_default();
+    let single_tool_use = request.tool_choice.as_ref().is_some_and(|c| c.disable_parallel_tool_use());
+    let timeouts = ModelTimeouts::resolve(&*state.timeouts.read().await, &mapped_model);
+    let call_limit = if is_stream { timeouts.first_byte } else { timeouts.request };
+    let mut labels = RequestLabels { model: mapped_model.clone(), account: String::new() };
+
+    // 会话粘性: 多轮对话固定在同一账号，保持上游隐式缓存
+    let session_id = SessionManager::claude_session_id(&headers, &request);
+    let dispatch_request = DispatchRequest {
+        quota_group: &config.request_type,
+        session_id: Some(&session_id),
+        allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+        method: if is_stream { "streamGenerateContent" } else { "generateContent" },
+        query: if is_stream { Some("alt=sse") } else { None },
+        model: &mapped_model,
+        timeout: call_limit,
+        trace_id: &trace_id,
+    };
+
+    let dispatched = dispatch(&state, dispatch_request, |project_id| {
+        transform_claude_request_with_defaults(&request_with_mapped, project_id, &generation)
+            .inspect(|b| debug!("[{}] Transformed body: {}", trace_id, serde_json::to_string_pretty(b).unwrap_or_default()))
+            .map_err(|e| format!("Transform error: {}", e))
+    })
+    .await;
+
+    let Dispatched { response, account, deadline } = match dispatched {
+        Ok(d) => d,
+        Err(e) => {
+            let mut resp = dispatch_error_response(e);
+            resp.extensions_mut().insert(labels);
+            return resp;
+        }
+    };
+    let email = account.email;
+    labels.account = email.clone();
+    info!("[{}] Using account: {} (model: {}, key: {})", trace_id, email, mapped_model, key_name);
+
+    if is_stream {
+        let gemini_stream = with_stream_timeouts(
+            response.bytes_stream(),
+            deadline.saturating_duration_since(tokio::time::Instant::now()),
+            timeouts.idle,
+        );
+        let on_usage: crate::proxy::mappers::claude::UsageCallback = {
+            let usage_tracker = state.usage.clone();
+            let key_name = client_key.as_ref().map(|k| k.name.clone());
+            let (model, account) = (mapped_model.clone(), email.clone());
+            Box::new(move |u: &crate::proxy::mappers::claude::UsageMetadata| {
+                let usage = crate::proxy::mappers::claude::utils::to_claude_usage(u);
+                let cached = usage.cache_read_input_tokens.unwrap_or(0);
+                metrics().record_tokens(&model, &account, usage.input_tokens as u64, usage.output_tokens as u64, cached as u64);
+                if let Some(key_name) = key_name {
+                    usage_tracker.record_tokens(&key_name, usage.input_tokens, usage.output_tokens);
+                }
+            })
         };
-        
-        // 调用上游
-        let is_stream = request.stream;
-        let method = if is_stream { "streamGenerateContent" } else { "generateContent" };
-        let query = if is_stream { Some("alt=sse") } else { None };
+        let claude_stream = create_claude_sse_stream(
+            gemini_stream,
+            trace_id,
+            email,
+            stop_sequences,
+            single_tool_use,
+            Some(on_usage),
+        );
 
-        let response = match upstream.call_v1_internal(method, &access_token, gemini_body, query).await {
-            Ok(r) => r,
-            Err(e) => {
-                last_error = e.clone();
-                debug!("[{}] Request failed: {}", trace_id, e);
-                continue;
+        // 流存活期间计入 active_streams
+        let stream_guard = metrics().stream_guard("/v1/messages");
+        let sse_stream = claude_stream.map(move |result| -> Result<Bytes, std::io::Error> {
+            let _ = &stream_guard;
+            match result {
+                Ok(bytes) => Ok(bytes),
+                Err(e) => Ok(Bytes::from(format!("data: {{\"error\":\"{}\"}}\n\n", e))),
             }
+        });
+
+        let mut resp = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::CONNECTION, "keep-alive")
+            .body(Body::from_stream(sse_stream))
+            .unwrap();
+        resp.extensions_mut().insert(labels);
+        return resp;
+    }
+
+    let bytes = match tokio::time::timeout_at(deadline, response.bytes()).await {
+        Ok(Ok(b)) => b,
+        Ok(Err(e)) => return (StatusCode::BAD_GATEWAY, format!("Failed to read body: {}", e)).into_response(),
+        Err(_) => {
+            let mut resp = timeout_response(
+                ClientProtocol::Anthropic,
+                &format!("Upstream response not completed within {}s ({})", call_limit.as_secs(), mapped_model),
+            );
+            resp.extensions_mut().insert(labels);
+            return resp;
+        }
+    };
+
+    let gemini_resp: Value = match serde_json::from_slice(&bytes) {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)).into_response(),
+    };
+
+    let raw = gemini_resp.get("response").unwrap_or(&gemini_resp);
+
+    let gemini_response: crate::proxy::mappers::claude::models::GeminiResponse = 
+        match serde_json::from_value(raw.clone()) {
+            Ok(r) => r,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Convert error: {}", e)).into_response(),
         };
-        
-        let status = response.status();
-        
-        // 成功
-        if status.is_success() {
-            if request.stream {
-                let stream = response.bytes_stream();
-                let gemini_stream = Box::pin(stream);
-                let claude_stream = create_claude_sse_stream(gemini_stream, trace_id, email);
-
-                let sse_stream = claude_stream.map(|result| -> Result<Bytes, std::io::Error> {
-                    match result {
-                        Ok(bytes) => Ok(bytes),
-                        Err(e) => Ok(Bytes::from(format!("data: {{\"error\":\"{}\"}}\n\n", e))),
-                    }
-                });
-
-                return Response::builder()
-                    .status(StatusCode::OK)
-                    .header(header::CONTENT_TYPE, "text/event-stream")
-                    .header(header::CACHE_CONTROL, "no-cache")
-                    .header(header::CONNECTION, "keep-alive")
-                    .body(Body::from_stream(sse_stream))
-                    .unwrap();
-            } else {
-                let bytes = match response.bytes().await {
-                    Ok(b) => b,
-                    Err(e) => return (StatusCode::BAD_GATEWAY, format!("Failed to read body: {}", e)).into_response(),
-                };
-
-                let gemini_resp: Value = match serde_json::from_slice(&bytes) {
-                    Ok(v) => v,
-                    Err(e) => return (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)).into_response(),
-                };
-
-                let raw = gemini_resp.get("response").unwrap_or(&gemini_resp);
-
-                let gemini_response: crate::proxy::mappers::claude::models::GeminiResponse = 
-                    match serde_json::from_value(raw.clone()) {
-                        Ok(r) => r,
-                        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Convert error: {}", e)).into_response(),
-                    };
-                
-                let claude_response = match transform_response(&gemini_response) {
-                    Ok(r) => r,
-                    Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Transform error: {}", e)).into_response(),
-                };
-
-                info!(
-                    "[{}] Completed | In: {} | Out: {}", 
-                    trace_id, 
-                    claude_response.usage.input_tokens, 
-                    claude_response.usage.output_tokens
-                );
-
-                return Json(claude_response).into_response();
-            }
+    
+    let claude_response = match transform_response(&gemini_response, &stop_sequences, single_tool_use) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Transform error: {}", e)).into_response(),
+    };
+
+    info!(
+        "[{}] Completed | Key: {} | In: {} | Out: {}", 
+        trace_id, 
+        key_name,
+        claude_response.usage.input_tokens, 
+        claude_response.usage.output_tokens
+    );
+
+    if let Some(key) = &client_key {
+        state.usage.record_tokens(&key.name, claude_response.usage.input_tokens, claude_response.usage.output_tokens);
+    }
+    metrics().record_tokens(
+        &mapped_model,
+        &email,
+        claude_response.usage.input_tokens as u64,
+        claude_response.usage.output_tokens as u64,
+        claude_response.usage.cache_read_input_tokens.unwrap_or(0) as u64,
+    );
+
+    let mut resp = Json(claude_response).into_response();
+    resp.extensions_mut().insert(labels);
+    resp
+}
+
+/// 处理 POST /v1/messages/count_tokens
+/// 优先使用上游 countTokens，上游不可用时退回本地估算
+pub async fn handle_count_tokens(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    client_key: Option<Extension<ClientKey>>,
+    Json(body): Json<Value>,
+) -> Response {
+    let client_key = client_key.map(|Extension(k)| k);
+    let trace_id = format!("cnt-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+    let mut request: ClaudeRequest = match serde_json::from_value(body) {
+        Ok(r) => r,
+        Err(e) => return invalid_request_response(format!("Invalid request body: {}", e)),
+    };
+    filter_invalid_thinking_blocks(&mut request.messages);
+
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &request.model,
+        &*state.custom_mapping.read().await,
+        &*state.openai_mapping.read().await,
+        &*state.anthropic_mapping.read().await,
+        true,
+    );
+    let config = resolve_claude_config(&mapped_model, &request.tools);
+    if let Some(key) = &client_key {
+        if !key.allows_model(&config.final_model) {
+            return model_not_allowed(ClientProtocol::Anthropic, &key.name, &config.final_model);
         }
-        
-        // 处理错误
-        let status_code = status.as_u16();
-        let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status));
-        last_error = format!("HTTP {}: {}", status_code, error_text);
-        last_status = Some(status_code);
-        
-        debug!("[{}] Upstream error: {}", trace_id, last_error);
-        
-        // 重试逻辑
-        if attempt + 1 < max_attempts {
-            let delay_ms = match status_code {
-                429 => apply_jitter(1000 * (attempt as u64 + 1)),
-                503 | 529 | 500 => apply_jitter(500 * (attempt as u64 + 1)),
-                _ => 0,
-            };
-            
-            if delay_ms > 0 {
-                sleep(Duration::from_millis(delay_ms)).await;
+    }
+
+    // 与 /v1/messages 相同的转换 (URL 来源的图片/文档不下载，不计入)
+    request.model = mapped_model.clone();
+    let generation = state.generation.read().await.clone();
+    let gemini_body = match transform_claude_request_with_defaults(&request, "", &generation) {
+        Ok(b) => b,
+        Err(e) => return invalid_request_response(format!("Transform error: {}", e)),
+    };
+    let upstream_model = gemini_body.get("model").and_then(|v| v.as_str()).unwrap_or(&mapped_model).to_string();
+    let count_request = build_count_tokens_request(&gemini_body);
+
+    let session_id = SessionManager::claude_session_id(&headers, &request);
+    let mut labels = RequestLabels { model: upstream_model.clone(), account: String::new() };
+    // 单账号、不记录限流: 计数失败不应影响生成流量，直接退回本地估算
+    let dispatched = dispatch_once(
+        &state,
+        DispatchRequest {
+            quota_group: &config.request_type,
+            session_id: Some(&session_id),
+            allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+            method: "countTokens",
+            query: None,
+            model: &upstream_model,
+            timeout: COUNT_TOKENS_TIMEOUT,
+            trace_id: &trace_id,
+        },
+        |_project_id| Ok(count_request.clone()),
+    )
+    .await;
+
+    let upstream_count = match dispatched {
+        Ok(Dispatched { response, account, deadline }) => {
+            labels.account = account.email;
+            match tokio::time::timeout_at(deadline, response.json::<Value>()).await {
+                Ok(Ok(v)) => parse_total_tokens(&v),
+                _ => None,
             }
         }
-    }
-    
-    // 所有重试失败 - 保留原始状态码
-    let response_status = match last_status {
-        Some(429) => StatusCode::TOO_MANY_REQUESTS,
-        Some(code) if code >= 400 && code < 600 => {
-            StatusCode::from_u16(code).unwrap_or(StatusCode::BAD_GATEWAY)
+        Err(e) => {
+            tracing::warn!("[{}] countTokens unavailable, using local estimate: {}", trace_id, e);
+            None
         }
-        _ => StatusCode::BAD_GATEWAY,
     };
+    let input_tokens = upstream_count.unwrap_or_else(|| estimate_input_tokens(&gemini_body));
+    debug!("[{}] count_tokens {} -> {} (upstream: {})", trace_id, request.model, input_tokens, upstream_count.is_some());
+
+    let mut resp = Json(json!({ "input_tokens": input_tokens })).into_response();
+    resp.extensions_mut().insert(labels);
+    resp
+}
 
-    // 对于 429，使用 rate_limit_error 类型增加语义
-    let error_type = if last_status == Some(429) {
-        "rate_limit_error"
-    } else {
-        "api_error"
+fn invalid_request_response(message: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "type": "error",
+            "error": {
+                "type": "invalid_request_error",
+                "message": message
+            }
+        }))
+    ).into_response()
+}
+
+/// 将 dispatcher 错误转换为 Claude 错误响应 (保留上游状态码)
+fn dispatch_error_response(error: DispatchError) -> Response {
+    let error_type = match &error {
+        DispatchError::Timeout(_) => {
+            return timeout_response(ClientProtocol::Anthropic, &error.to_string());
+        }
+        DispatchError::NoAccounts(_) => "overloaded_error",
+        DispatchError::InvalidRequest(_) => "invalid_request_error",
+        // 对于 429，使用 rate_limit_error 类型增加语义
+        DispatchError::Upstream { status: 429, .. } => "rate_limit_error",
+        _ => "api_error",
     };
 
     (
-        response_status,
+        error.status(),
         Json(json!({
             "type": "error",
             "error": {
                 "type": error_type,
-                "message": last_error
+                "message": error.to_string()
             }
         }))
     ).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_tool_choice_rejected_before_remote_fetch() {
+        let state = AppState::for_tests(&crate::config::Config::default());
+        let body = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": [
+                {"type": "image", "source": {"type": "url", "url": "http://127.0.0.1:9/cat.png"}},
+                {"type": "text", "text": "Describe"}
+            ]}],
+            "tools": [{"name": "lookup", "input_schema": {"type": "object", "properties": {}}}],
+            "tool_choice": {"type": "tool", "name": "missing"}
+        });
+
+        let resp = handle_messages(State(state), HeaderMap::new(), None, Json(body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let error: Value = serde_json::from_slice(&bytes).unwrap();
+        // 报告的是 tool_choice 错误，而不是下载失败
+        let message = error["error"]["message"].as_str().unwrap();
+        assert!(message.contains("missing"), "{}", message);
+        assert!(!message.contains("Failed to fetch"), "{}", message);
+    }
+}
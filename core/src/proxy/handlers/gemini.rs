@@ -1,30 +1,238 @@
 //! Gemini passthrough handler
-//! Handles /v1beta/models/:model_action
+//! Handles /v1beta/models/:model_action (generate, stream and embed actions)
 
 use axum::{
-    body::Body,
+    body::{to_bytes, Body, Bytes},
     extract::{Path, State},
-    http::{Request, StatusCode},
-    response::IntoResponse,
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
 };
+use futures::StreamExt;
+use serde_json::{json, Value};
 
+use crate::proxy::metrics::{metrics, RequestLabels};
+use crate::proxy::middleware::auth::{model_not_allowed, ClientKey, ClientProtocol};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::dispatcher::{dispatch, DispatchError, DispatchRequest, Dispatched};
+use crate::proxy::upstream::timeouts::{timeout_response, with_stream_timeouts, ModelTimeouts};
 
 /// Handle Gemini API requests (passthrough)
 pub async fn handle_gemini_request(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
-    _request: Request<Body>,
-) -> Result<axum::response::Response, (StatusCode, String)> {
-    // Get token
-    let (_access_token, _project_id, email) = state.token_manager
-        .get_token("gemini", false, None)
+    request: Request<Body>,
+) -> Result<Response, (StatusCode, String)> {
+    let client_key = request.extensions().get::<ClientKey>().cloned();
+    let (model, action) = model_action
+        .split_once(':')
+        .unwrap_or((model_action.as_str(), ""));
+    if let Some(key) = &client_key {
+        if !key.allows_model(model) {
+            return Ok(model_not_allowed(ClientProtocol::Gemini, &key.name, model));
+        }
+    }
+
+    let (stream, embed) = match action {
+        "generateContent" => (false, false),
+        "streamGenerateContent" => (true, false),
+        "embedContent" | "batchEmbedContents" => (false, true),
+        _ => {
+            return Err((
+                StatusCode::NOT_IMPLEMENTED,
+                format!("Gemini action '{}' is not supported", action),
+            ))
+        }
+    };
+
+    // Custom mapping applies; Gemini model names otherwise pass through unchanged
+    let mapped_model = state
+        .custom_mapping
+        .read()
         .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
-    
-    tracing::debug!("Gemini passthrough: {} using account {}", model_action, email);
-    
-    // For now, return not implemented
-    // Full implementation would parse model_action and forward to Gemini
-    Err((StatusCode::NOT_IMPLEMENTED, "Gemini passthrough not yet implemented".to_string()))
+        .get(model)
+        .cloned()
+        .unwrap_or_else(|| model.to_string());
+
+    let body = to_bytes(request.into_body(), usize::MAX)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read body: {}", e)))?;
+    let mut inner_request: Value = serde_json::from_slice(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid JSON body: {}", e)))?;
+    if embed {
+        point_embed_requests_at(&mut inner_request, &mapped_model);
+    }
+
+    let mut labels = RequestLabels { model: mapped_model.clone(), account: String::new() };
+    let timeouts = ModelTimeouts::resolve(&*state.timeouts.read().await, &mapped_model);
+    let call_limit = if stream { timeouts.first_byte } else { timeouts.request };
+    let trace_id = format!("gem-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+    let dispatched = dispatch(
+        &state,
+        DispatchRequest {
+            quota_group: if embed { "text" } else { "gemini" },
+            session_id: None,
+            allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+            method: action,
+            query: if stream { Some("alt=sse") } else { None },
+            model: &mapped_model,
+            timeout: call_limit,
+            trace_id: &trace_id,
+        },
+        |project_id| {
+            let mut wrapper = json!({
+                "project": project_id,
+                "requestId": format!("cli-{}", uuid::Uuid::new_v4().simple()),
+                "request": inner_request.clone(),
+                "model": mapped_model
+            });
+            if !embed {
+                wrapper["userAgent"] = json!("antigravity-cli");
+                wrapper["requestType"] = json!("agent");
+            }
+            Ok(wrapper)
+        },
+    )
+    .await;
+    let Dispatched { response, account, deadline } = match dispatched {
+        Ok(d) => d,
+        Err(e) => {
+            let mut resp = dispatch_error_response(e);
+            resp.extensions_mut().insert(labels);
+            return Ok(resp);
+        }
+    };
+    labels.account = account.email.clone();
+
+    tracing::debug!("Gemini passthrough: {} using account {}", model_action, account.email);
+
+    if stream {
+        let upstream = with_stream_timeouts(
+            response.bytes_stream(),
+            deadline.saturating_duration_since(tokio::time::Instant::now()),
+            timeouts.idle,
+        );
+        let stream_guard = metrics().stream_guard("/v1beta/models");
+        let sse = async_stream::stream! {
+            let _guard = stream_guard;
+            let mut upstream = upstream;
+            let mut buffer = String::new();
+            while let Some(chunk) = upstream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.push_str(&String::from_utf8_lossy(&bytes));
+                        while let Some(pos) = buffer.find('\n') {
+                            let line = buffer[..pos].trim_end_matches('\r').to_string();
+                            buffer.drain(..=pos);
+                            if let Some(out) = unwrap_sse_line(&line) {
+                                yield Ok::<Bytes, std::io::Error>(Bytes::from(out));
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let code = if e.is_timeout() { 504 } else { 502 };
+                        let status = if e.is_timeout() { "DEADLINE_EXCEEDED" } else { "UNAVAILABLE" };
+                        let error = json!({"error": {"code": code, "message": e.to_string(), "status": status}});
+                        yield Ok(Bytes::from(format!("data: {}\n\n", error)));
+                        break;
+                    }
+                }
+            }
+        };
+
+        let mut resp = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from_stream(sse))
+            .unwrap();
+        resp.extensions_mut().insert(labels);
+        return Ok(resp);
+    }
+
+    let raw_response: Value = match tokio::time::timeout_at(deadline, response.json()).await {
+        Ok(result) => result.map_err(|e| (StatusCode::BAD_GATEWAY, format!("Invalid JSON response: {}", e)))?,
+        Err(_) => {
+            let mut resp = timeout_response(
+                ClientProtocol::Gemini,
+                &format!("Upstream response not completed within {}s ({})", call_limit.as_secs(), mapped_model),
+            );
+            resp.extensions_mut().insert(labels);
+            return Ok(resp);
+        }
+    };
+    let gemini_response = raw_response.get("response").cloned().unwrap_or(raw_response);
+
+    if let Some(usage) = gemini_response.get("usageMetadata") {
+        let input = usage.get("promptTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+        let output = usage.get("candidatesTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+        let cached = usage.get("cachedContentTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+        metrics().record_tokens(&mapped_model, &account.email, input.saturating_sub(cached), output, cached);
+        if let Some(key) = &client_key {
+            state.usage.record_tokens(&key.name, input as u32, output as u32);
+        }
+    }
+
+    let mut resp = Json(gemini_response).into_response();
+    resp.extensions_mut().insert(labels);
+    Ok(resp)
+}
+
+/// Point the `model` of each embed request at the mapped model
+fn point_embed_requests_at(request: &mut Value, mapped_model: &str) {
+    let model = json!(format!("models/{}", mapped_model));
+    if let Some(requests) = request.get_mut("requests").and_then(|r| r.as_array_mut()) {
+        for item in requests.iter_mut().filter(|item| item.is_object()) {
+            item["model"] = model.clone();
+        }
+    } else if request.get("model").is_some() {
+        request["model"] = model;
+    }
+}
+
+/// Strip the v1internal `response` wrapper from one SSE line
+fn unwrap_sse_line(line: &str) -> Option<String> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data.is_empty() {
+        return None;
+    }
+    let chunk = match serde_json::from_str::<Value>(data) {
+        Ok(mut v) => v.get_mut("response").map(Value::take).unwrap_or(v),
+        Err(_) => return Some(format!("data: {}\n\n", data)),
+    };
+    Some(format!("data: {}\n\n", chunk))
+}
+
+/// Map a dispatcher failure to a Google-style error body
+fn dispatch_error_response(error: DispatchError) -> Response {
+    if let DispatchError::Timeout(_) = error {
+        return timeout_response(ClientProtocol::Gemini, &error.to_string());
+    }
+    let status = error.status();
+    // Upstream errors are already Google-shaped
+    if let DispatchError::Upstream { body, .. } = &error {
+        if let Ok(value) = serde_json::from_str::<Value>(body) {
+            return (status, Json(value)).into_response();
+        }
+    }
+    let google_status = match status.as_u16() {
+        400 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        429 => "RESOURCE_EXHAUSTED",
+        503 => "UNAVAILABLE",
+        _ => "INTERNAL",
+    };
+    (
+        status,
+        Json(json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": error.to_string(),
+                "status": google_status
+            }
+        })),
+    )
+        .into_response()
 }
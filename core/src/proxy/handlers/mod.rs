@@ -3,3 +3,6 @@
 pub mod openai;
 pub mod claude;
 pub mod gemini;
+pub mod responses;
+pub mod models;
+pub mod admin;
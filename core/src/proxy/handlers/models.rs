@@ -0,0 +1,106 @@
+//! Model listing handler
+//! Handles /v1/models and /v1/models/{id} for OpenAI and Anthropic clients;
+//! the response shape follows the request headers (`anthropic-version`)
+
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::openai::resolve_model;
+use crate::proxy::mappers::openai::OpenAIError;
+use crate::proxy::middleware::auth::{ClientKey, ClientProtocol};
+use crate::proxy::model_catalog::{anthropic_model, anthropic_page, openai_model};
+use crate::proxy::server::AppState;
+
+/// Anthropic pagination parameters (ignored for OpenAI clients)
+#[derive(Debug, Default, Deserialize)]
+pub struct ListModelsQuery {
+    limit: Option<usize>,
+    after_id: Option<String>,
+    before_id: Option<String>,
+}
+
+/// Default Anthropic page size
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Model ids visible to the calling key (aliases by the model they map to).
+/// The catalogue only lists ids that resolve, so the listing matches what dispatch accepts
+async fn visible_models(state: &AppState, client_key: Option<&ClientKey>) -> Vec<String> {
+    let ids = state.models.list(state).await;
+    let Some(key) = client_key else {
+        return ids;
+    };
+    let mut visible = Vec::with_capacity(ids.len());
+    for id in ids {
+        if matches!(resolve_model(state, &id).await, Ok(model) if key.allows_model(&model)) {
+            visible.push(id);
+        }
+    }
+    visible
+}
+
+/// Handle GET /v1/models
+pub async fn handle_list_models(
+    State(state): State<AppState>,
+    client_key: Option<Extension<ClientKey>>,
+    headers: HeaderMap,
+    Query(query): Query<ListModelsQuery>,
+) -> Response {
+    let ids = visible_models(&state, client_key.as_ref().map(|Extension(k)| k)).await;
+
+    match ClientProtocol::from_request("/v1/models", &headers) {
+        ClientProtocol::Anthropic => Json(anthropic_page(
+            &ids,
+            query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            query.after_id.as_deref(),
+            query.before_id.as_deref(),
+        ))
+        .into_response(),
+        _ => Json(json!({
+            "object": "list",
+            "data": ids.iter().map(|id| openai_model(id)).collect::<Vec<_>>()
+        }))
+        .into_response(),
+    }
+}
+
+/// Handle GET /v1/models/{id}
+pub async fn handle_get_model(
+    State(state): State<AppState>,
+    client_key: Option<Extension<ClientKey>>,
+    headers: HeaderMap,
+    Path(model_id): Path<String>,
+) -> Response {
+    let ids = visible_models(&state, client_key.as_ref().map(|Extension(k)| k)).await;
+    let protocol = ClientProtocol::from_request("/v1/models", &headers);
+
+    if !ids.contains(&model_id) {
+        let message = format!("The model '{}' does not exist", model_id);
+        return match protocol {
+            ClientProtocol::Anthropic => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "type": "error",
+                    "error": { "type": "not_found_error", "message": message }
+                })),
+            )
+                .into_response(),
+            _ => OpenAIError::NotFound {
+                message,
+                param: Some("model".to_string()),
+                code: Some("model_not_found".to_string()),
+            }
+            .into_response(),
+        };
+    }
+
+    match protocol {
+        ClientProtocol::Anthropic => Json(anthropic_model(&model_id)).into_response(),
+        _ => Json(openai_model(&model_id)).into_response(),
+    }
+}
@@ -1,238 +1,221 @@
 //! OpenAI-compatible handler
-//! Handles /v1/chat/completions, /v1/completions, /v1/models, /v1/images/generations
+//! Handles /v1/chat/completions, /v1/completions, /v1/images/generations, /v1/embeddings
+//! (/v1/responses lives in `responses.rs` and reuses the helpers here; /v1/models in `models.rs`)
 
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Body,
+    extract::{rejection::JsonRejection, Json, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
 };
+use bytes::Bytes;
+use futures::StreamExt;
 use serde_json::{json, Value};
 
+use crate::proxy::common::model_mapping::builtin_model_mapping;
+use crate::proxy::mappers::claude::{UsageCallback, UsageMetadata};
+use crate::proxy::mappers::openai::models::{OpenAIChatRequest, OpenAIContent, OpenAIContentPart};
+use crate::proxy::mappers::openai::request::is_remote_url;
+use crate::proxy::mappers::openai::{
+    convert_chat_response, create_openai_sse_stream, parse_chat_request, resolve_openai_config,
+    transform_openai_request, wrap_v1internal, OpenAIError,
+};
+use crate::proxy::mappers::openai::embeddings::{
+    batch_embeddings, build_batch_request, embedding_inputs, estimate_tokens, is_gemini_embedding_model,
+    to_openai_embeddings, DEFAULT_EMBEDDING_MODEL, MAX_BATCH_SIZE,
+};
+use crate::proxy::mappers::openai::request::strict_response_schema;
+use crate::proxy::mappers::openai::response::StrictSchema;
+use crate::proxy::metrics::{metrics, RequestLabels};
+use crate::proxy::middleware::auth::{model_not_allowed, ClientKey, ClientProtocol};
 use crate::proxy::server::AppState;
+use crate::proxy::session_manager::SessionManager;
+use crate::proxy::upstream::dispatcher::{dispatch, DispatchRequest, Dispatched};
+use crate::proxy::upstream::timeouts::{with_stream_timeouts, ModelTimeouts};
 
 /// Handle POST /v1/chat/completions
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
-    Json(body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Extract model and check if streaming
-    let model = body.get("model")
-        .and_then(|v| v.as_str())
-        .unwrap_or("gemini-1.5-flash");
-    
-    let stream = body.get("stream")
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false);
-    
-    // Resolve model mapping
-    let gemini_model = resolve_model(&state, model).await;
-    
-    // Get token
-    let session_id = None; // TODO: extract from headers
-    let (access_token, project_id, email) = state.token_manager
-        .get_token("text", false, session_id)
-        .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
-    
-    tracing::info!("OpenAI request: {} -> {} (account: {})", model, gemini_model, email);
-    
-    // Build v1internal request
-    let v1_request = build_v1internal_request(&body, &gemini_model, &project_id)?;
+    headers: HeaderMap,
+    client_key: Option<Extension<ClientKey>>,
+    payload: Result<Json<Value>, JsonRejection>,
+) -> Result<Response, OpenAIError> {
+    let client_key = client_key.map(|Extension(k)| k);
+    let Json(body) = payload?;
+    let mut request = parse_chat_request(&body)?;
+    let model = request.model.clone();
+    let stream = request.stream;
+    
+    // Resolve model mapping (`-online` only asks for grounding, the mapping is for the base name)
+    let mapped_model = resolve_model(&state, model.trim_end_matches("-online")).await?;
+    let config = resolve_openai_config(&request, &mapped_model);
+    let gemini_model = config.final_model.clone();
+
+    // The allowlist applies to the model actually sent upstream, after mappings and variants
+    if let Some(key) = &client_key {
+        if !key.allows_model(&gemini_model) {
+            return Ok(model_not_allowed(ClientProtocol::OpenAI, &key.name, &gemini_model));
+        }
+    }
+    let key_name = client_key.as_ref().map(|k| k.name.as_str()).unwrap_or("-");
+    
+    // Compile the strict response schema up front so a bad schema is a 400, not an upstream call
+    let strict_schema = match strict_response_schema(&request) {
+        Some((name, schema)) => Some(
+            StrictSchema::new(name, &schema)
+                .map_err(|e| OpenAIError::invalid_request(e, Some("response_format"), None))?,
+        ),
+        None => None,
+    };
     
-    // Call upstream
-    let client = crate::proxy::upstream::client::UpstreamClient::new(None);
+    // Only a validated, authorized request may make the proxy download anything
+    inline_remote_images(&state, &mut request).await?;
+    let inner_request = transform_openai_request(&request, &config);
     
+    // Call upstream (account rotation and retries are handled by the dispatcher)
     let method = if stream { "streamGenerateContent" } else { "generateContent" };
     let query = if stream { Some("alt=sse") } else { None };
     
-    let response = client
-        .call_v1_internal(method, &access_token, v1_request, query)
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
-    
-    let status = response.status();
-    
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        tracing::error!("Upstream error {}: {}", status, error_text);
-        return Err((StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY), error_text));
-    }
+    let mut labels = RequestLabels { model: gemini_model.clone(), account: String::new() };
+    let timeouts = ModelTimeouts::resolve(&*state.timeouts.read().await, &gemini_model);
+    let call_limit = if stream { timeouts.first_byte } else { timeouts.request };
+    let trace_id = format!("oai-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    // Keeps a conversation on one account so the upstream's implicit cache stays warm
+    let session_id = SessionManager::openai_session_id(&headers, &request);
+    
+    let dispatched = dispatch(
+        &state,
+        DispatchRequest {
+            quota_group: &config.request_type,
+            session_id: Some(&session_id),
+            allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+            method,
+            query,
+            model: &gemini_model,
+            timeout: call_limit,
+            trace_id: &trace_id,
+        },
+        |project_id| Ok(wrap_v1internal(inner_request.clone(), &config, project_id)),
+    )
+    .await;
+    let Dispatched { response, account, deadline } = match dispatched {
+        Ok(d) => d,
+        Err(e) => {
+            let mut resp = OpenAIError::from(e).into_response();
+            resp.extensions_mut().insert(labels);
+            return Ok(resp);
+        }
+    };
+    let email = account.email;
+    labels.account = email.clone();
+    
+    tracing::info!("OpenAI request: {} -> {} (account: {}, key: {})", model, gemini_model, email, key_name);
+    
+    let timed_out = |labels: RequestLabels| {
+        let mut resp = OpenAIError::Timeout(format!(
+            "Upstream did not respond within {}s ({})",
+            call_limit.as_secs(),
+            gemini_model
+        ))
+        .into_response();
+        resp.extensions_mut().insert(labels);
+        Ok(resp)
+    };
     
     if stream {
-        // TODO: Implement SSE streaming conversion
-        let body_text = response.text().await.unwrap_or_default();
-        Ok((StatusCode::OK, body_text).into_response())
+        let include_usage = request.stream_options.as_ref().is_some_and(|o| o.include_usage);
+        let gemini_stream = with_stream_timeouts(
+            response.bytes_stream(),
+            deadline.saturating_duration_since(tokio::time::Instant::now()),
+            timeouts.idle,
+        );
+        let on_usage: UsageCallback = {
+            let usage_tracker = state.usage.clone();
+            let key_name = client_key.as_ref().map(|k| k.name.clone());
+            let (model, account) = (gemini_model.clone(), email.clone());
+            Box::new(move |u: &UsageMetadata| {
+                let input = u.prompt_token_count.unwrap_or(0);
+                let output = u.candidates_token_count.unwrap_or(0);
+                let cached = u.cached_content_token_count.unwrap_or(0);
+                metrics().record_tokens(&model, &account, input.saturating_sub(cached) as u64, output as u64, cached as u64);
+                if let Some(key_name) = key_name {
+                    usage_tracker.record_tokens(&key_name, input, output);
+                }
+            })
+        };
+        let openai_stream = create_openai_sse_stream(
+            gemini_stream,
+            model.to_string(),
+            include_usage,
+            strict_schema,
+            Some(on_usage),
+        );
+        
+        // Count the stream in active_streams while it is alive
+        let stream_guard = metrics().stream_guard("/v1/chat/completions");
+        let sse_stream = openai_stream.map(move |result| -> Result<Bytes, std::io::Error> {
+            let _ = &stream_guard;
+            result.map_err(std::io::Error::other)
+        });
+        
+        let mut resp = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::CONNECTION, "keep-alive")
+            .body(Body::from_stream(sse_stream))
+            .unwrap();
+        resp.extensions_mut().insert(labels);
+        Ok(resp)
     } else {
-        let raw_response: Value = response.json().await
-            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Invalid JSON response: {}", e)))?;
+        let raw_response: Value = match tokio::time::timeout_at(deadline, response.json()).await {
+            Ok(result) => result.map_err(|e| OpenAIError::bad_gateway(format!("Invalid JSON response: {}", e)))?,
+            Err(_) => return timed_out(labels),
+        };
         
         // Extract response from v1internal wrapper
         let gemini_response = raw_response.get("response").unwrap_or(&raw_response);
+
+        if let Some(usage) = gemini_response.get("usageMetadata") {
+            let input = usage.get("promptTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+            let output = usage.get("candidatesTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+            let cached = usage.get("cachedContentTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+            metrics().record_tokens(&gemini_model, &email, input.saturating_sub(cached), output, cached);
+            if let Some(key) = &client_key {
+                state.usage.record_tokens(&key.name, input as u32, output as u32);
+            }
+        }
         
         // Convert Gemini response to OpenAI format
-        let openai_response = crate::proxy::mappers::gemini_to_openai::convert_chat_response(gemini_response, model);
+        let openai_response = convert_chat_response(gemini_response, &model);
         
-        Ok(Json(openai_response).into_response())
-    }
-}
-
-/// Build v1internal request wrapper
-fn build_v1internal_request(body: &Value, gemini_model: &str, project_id: &str) -> Result<Value, (StatusCode, String)> {
-    let mut contents = Vec::new();
-    let mut system_instruction: Option<Value> = None;
-    
-    // Process messages
-    if let Some(messages) = body.get("messages").and_then(|v| v.as_array()) {
-        for msg in messages {
-            let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
-            
-            // Handle system messages separately
-            if role == "system" {
-                if let Some(content) = msg.get("content").and_then(|v| v.as_str()) {
-                    system_instruction = Some(json!({
-                        "parts": [{"text": content}]
-                    }));
+        if let Some(strict) = &strict_schema {
+            let message = &openai_response["choices"][0]["message"];
+            if message.get("tool_calls").is_none() {
+                if let Err(e) = strict.validate(message["content"].as_str().unwrap_or_default()) {
+                    tracing::warn!("[OpenAI] {}", e);
+                    let mut resp = OpenAIError::schema_violation(e).into_response();
+                    resp.extensions_mut().insert(labels);
+                    return Ok(resp);
                 }
-                continue;
-            }
-            
-            let gemini_role = match role {
-                "assistant" => "model",
-                _ => "user",
-            };
-            
-            let mut parts = Vec::new();
-            
-            if let Some(content) = msg.get("content") {
-                match content {
-                    Value::String(s) => {
-                        parts.push(json!({"text": s}));
-                    }
-                    Value::Array(arr) => {
-                        for item in arr {
-                            if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
-                                parts.push(json!({"text": text}));
-                            }
-                            // Handle image_url type
-                            if let Some(image_url) = item.get("image_url") {
-                                if let Some(url) = image_url.get("url").and_then(|v| v.as_str()) {
-                                    if url.starts_with("data:") {
-                                        // Base64 encoded image
-                                        if let Some((mime, data)) = parse_data_url(url) {
-                                            parts.push(json!({
-                                                "inlineData": {
-                                                    "mimeType": mime,
-                                                    "data": data
-                                                }
-                                            }));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            
-            if !parts.is_empty() {
-                contents.push(json!({
-                    "role": gemini_role,
-                    "parts": parts
-                }));
             }
         }
+        
+        let mut resp = Json(openai_response).into_response();
+        resp.extensions_mut().insert(labels);
+        Ok(resp)
     }
-    
-    // Ensure we have at least one message
-    if contents.is_empty() {
-        contents.push(json!({
-            "role": "user",
-            "parts": [{"text": "Hello"}]
-        }));
-    }
-    
-    // Build generation config
-    let mut gen_config = json!({});
-    
-    if let Some(max_tokens) = body.get("max_tokens").or(body.get("max_completion_tokens")) {
-        gen_config["maxOutputTokens"] = max_tokens.clone();
-    }
-    if let Some(temp) = body.get("temperature") {
-        gen_config["temperature"] = temp.clone();
-    }
-    if let Some(top_p) = body.get("top_p") {
-        gen_config["topP"] = top_p.clone();
-    }
-    if let Some(stop) = body.get("stop") {
-        gen_config["stopSequences"] = stop.clone();
-    }
-    
-    // Build inner request
-    let mut inner_request = json!({
-        "contents": contents,
-        "safetySettings": [
-            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" }
-        ]
-    });
-    
-    if let Some(sys_inst) = system_instruction {
-        inner_request["systemInstruction"] = sys_inst;
-    }
-    
-    if !gen_config.as_object().map(|o| o.is_empty()).unwrap_or(true) {
-        inner_request["generationConfig"] = gen_config;
-    }
-    
-    // Generate request ID
-    let request_id = format!("cli-{}", uuid::Uuid::new_v4().simple());
-    
-    // Build v1internal wrapper
-    let v1_body = json!({
-        "project": project_id,
-        "requestId": request_id,
-        "request": inner_request,
-        "model": gemini_model,
-        "userAgent": "antigravity-cli",
-        "requestType": "text"
-    });
-    
-    Ok(v1_body)
-}
-
-/// Parse data URL to extract mime type and base64 data
-fn parse_data_url(url: &str) -> Option<(String, String)> {
-    if !url.starts_with("data:") {
-        return None;
-    }
-    
-    let rest = &url[5..];
-    let parts: Vec<&str> = rest.splitn(2, ',').collect();
-    if parts.len() != 2 {
-        return None;
-    }
-    
-    let meta = parts[0];
-    let data = parts[1];
-    
-    let mime = if meta.contains(';') {
-        meta.split(';').next().unwrap_or("application/octet-stream")
-    } else {
-        meta
-    };
-    
-    Some((mime.to_string(), data.to_string()))
 }
 
 /// Handle POST /v1/completions (legacy)
 pub async fn handle_completions(
     State(state): State<AppState>,
-    Json(mut body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    headers: HeaderMap,
+    client_key: Option<Extension<ClientKey>>,
+    payload: Result<Json<Value>, JsonRejection>,
+) -> Result<Response, OpenAIError> {
+    let Json(mut body) = payload?;
     // Convert legacy completions format to chat format
     if let Some(prompt) = body.get("prompt").cloned() {
         let prompt_str = match prompt {
@@ -247,59 +230,26 @@ pub async fn handle_completions(
         body["messages"] = json!([{"role": "user", "content": prompt_str}]);
     }
     
-    handle_chat_completions(State(state), Json(body)).await
-}
-
-/// Handle GET /v1/models
-pub async fn handle_list_models(
-    State(_state): State<AppState>,
-) -> impl IntoResponse {
-    let models = vec![
-        model_object("gemini-2.5-pro"),
-        model_object("gemini-2.5-flash"),
-        model_object("gemini-2.5-flash-lite"),
-        model_object("gemini-3-flash"),
-        model_object("gemini-3-pro-low"),
-        model_object("gemini-3-pro-high"),
-        model_object("claude-sonnet-4-5"),
-        model_object("claude-opus-4-5-thinking"),
-        model_object("gpt-4"),
-        model_object("gpt-4o"),
-        model_object("gpt-4o-mini"),
-        model_object("gpt-3.5-turbo"),
-    ];
-    
-    Json(json!({
-        "object": "list",
-        "data": models
-    }))
-}
-
-fn model_object(id: &str) -> Value {
-    json!({
-        "id": id,
-        "object": "model",
-        "created": 1700000000,
-        "owned_by": "antigravity-proxy"
-    })
+    handle_chat_completions(State(state), headers, client_key, Ok(Json(body))).await
 }
 
 /// Handle POST /v1/images/generations
 pub async fn handle_images_generations(
     State(state): State<AppState>,
-    Json(body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    client_key: Option<Extension<ClientKey>>,
+    payload: Result<Json<Value>, JsonRejection>,
+) -> Result<Response, OpenAIError> {
+    let client_key = client_key.map(|Extension(k)| k);
+    let Json(body) = payload?;
     let prompt = body.get("prompt")
         .and_then(|v| v.as_str())
-        .ok_or((StatusCode::BAD_REQUEST, "Missing prompt".to_string()))?;
-    
-    // Get token for image generation
-    let (access_token, project_id, email) = state.token_manager
-        .get_token("image_gen", false, None)
-        .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
-    
-    tracing::info!("Image generation request (account: {})", email);
+        .ok_or_else(|| OpenAIError::invalid_request("'prompt' is required", Some("prompt"), None))?;
+
+    if let Some(key) = &client_key {
+        if !key.allows_model("gemini-3-pro-image") {
+            return Ok(model_not_allowed(ClientProtocol::OpenAI, &key.name, "gemini-3-pro-image"));
+        }
+    }
     
     // Build v1internal request for image generation
     let inner_request = json!({
@@ -315,33 +265,52 @@ pub async fn handle_images_generations(
         }
     });
     
-    let request_id = format!("cli-img-{}", uuid::Uuid::new_v4().simple());
-    
-    let v1_body = json!({
-        "project": project_id,
-        "requestId": request_id,
-        "request": inner_request,
-        "model": "gemini-3-pro-image",
-        "userAgent": "antigravity-cli",
-        "requestType": "image_gen"
-    });
-    
-    let client = crate::proxy::upstream::client::UpstreamClient::new(None);
-    
-    let response = client
-        .call_v1_internal("generateContent", &access_token, v1_body, None)
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    let timeouts = ModelTimeouts::resolve(&*state.timeouts.read().await, "gemini-3-pro-image");
+    let timed_out = || {
+        OpenAIError::Timeout(format!("Image generation did not complete within {}s", timeouts.request.as_secs()))
+    };
+    let trace_id = format!("img-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    
+    let dispatched = dispatch(
+        &state,
+        DispatchRequest {
+            quota_group: "image_gen",
+            session_id: None,
+            allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+            method: "generateContent",
+            query: None,
+            model: "gemini-3-pro-image",
+            timeout: timeouts.request,
+            trace_id: &trace_id,
+        },
+        |project_id| {
+            Ok(json!({
+                "project": project_id,
+                "requestId": format!("cli-img-{}", uuid::Uuid::new_v4().simple()),
+                "request": inner_request.clone(),
+                "model": "gemini-3-pro-image",
+                "userAgent": "antigravity-cli",
+                "requestType": "image_gen"
+            }))
+        },
+    )
+    .await;
+    let Dispatched { response, account, deadline } = match dispatched {
+        Ok(d) => d,
+        Err(e) => return Err(e.into()),
+    };
+    let email = account.email;
     
-    if !response.status().is_success() {
-        let status_code = response.status().as_u16();
-        let error_text = response.text().await.unwrap_or_default();
-        let status = StatusCode::from_u16(status_code).unwrap_or(StatusCode::BAD_GATEWAY);
-        return Err((status, error_text));
-    }
+    tracing::info!(
+        "Image generation request (account: {}, key: {})",
+        email,
+        client_key.as_ref().map(|k| k.name.as_str()).unwrap_or("-")
+    );
     
-    let raw_response: Value = response.json().await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Invalid JSON: {}", e)))?;
+    let raw_response: Value = match tokio::time::timeout_at(deadline, response.json()).await {
+        Ok(result) => result.map_err(|e| OpenAIError::bad_gateway(format!("Invalid JSON: {}", e)))?,
+        Err(_) => return Err(timed_out()),
+    };
     
     let gemini_response = raw_response.get("response").unwrap_or(&raw_response);
     
@@ -364,19 +333,173 @@ pub async fn handle_images_generations(
         })
         .unwrap_or_default();
     
-    Ok(Json(json!({
+    let mut resp = Json(json!({
         "created": chrono::Utc::now().timestamp(),
         "data": images
-    })))
+    })).into_response();
+    resp.extensions_mut().insert(RequestLabels { model: "gemini-3-pro-image".to_string(), account: email });
+    Ok(resp)
 }
 
-/// Resolve model mapping
-async fn resolve_model(state: &AppState, model: &str) -> String {
+/// Handle POST /v1/embeddings
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    client_key: Option<Extension<ClientKey>>,
+    payload: Result<Json<Value>, JsonRejection>,
+) -> Result<Response, OpenAIError> {
+    let client_key = client_key.map(|Extension(k)| k);
+    let Json(body) = payload?;
+    let model = body.get("model")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| OpenAIError::invalid_request("'model' is required", Some("model"), None))?;
+    let texts = embedding_inputs(&body).map_err(|e| OpenAIError::invalid_request(e, Some("input"), None))?;
+    let base64 = match body.get("encoding_format").and_then(|v| v.as_str()) {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err(OpenAIError::invalid_request(
+                format!("Unsupported encoding_format: '{}'", other),
+                Some("encoding_format"),
+                None,
+            ))
+        }
+    };
+    let dimensions = body.get("dimensions").and_then(|v| v.as_u64());
+
+    let gemini_model = resolve_embedding_model(&state, model).await;
+    if let Some(key) = &client_key {
+        if !key.allows_model(&gemini_model) {
+            return Ok(model_not_allowed(ClientProtocol::OpenAI, &key.name, &gemini_model));
+        }
+    }
+
+    let mut labels = RequestLabels { model: gemini_model.clone(), account: String::new() };
+    let timeouts = ModelTimeouts::resolve(&*state.timeouts.read().await, &gemini_model);
+    let trace_id = format!("emb-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+    // Each batch is dispatched on its own, so large inputs can spread over the pool
+    let mut vectors = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(MAX_BATCH_SIZE) {
+        let inner_request = build_batch_request(&gemini_model, batch, dimensions);
+        let dispatched = dispatch(
+            &state,
+            DispatchRequest {
+                quota_group: "text",
+                session_id: None,
+                allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+                method: "batchEmbedContents",
+                query: None,
+                model: &gemini_model,
+                timeout: timeouts.request,
+                trace_id: &trace_id,
+            },
+            |project_id| {
+                Ok(json!({
+                    "project": project_id,
+                    "requestId": format!("cli-{}", uuid::Uuid::new_v4().simple()),
+                    "request": inner_request.clone(),
+                    "model": gemini_model
+                }))
+            },
+        )
+        .await;
+        let Dispatched { response, account, deadline } = match dispatched {
+            Ok(d) => d,
+            Err(e) => {
+                let mut resp = OpenAIError::from(e).into_response();
+                resp.extensions_mut().insert(labels);
+                return Ok(resp);
+            }
+        };
+        labels.account = account.email;
+
+        let raw_response: Value = match tokio::time::timeout_at(deadline, response.json()).await {
+            Ok(result) => result.map_err(|e| OpenAIError::bad_gateway(format!("Invalid JSON response: {}", e)))?,
+            Err(_) => {
+                let mut resp = OpenAIError::Timeout(format!(
+                    "Upstream did not respond within {}s ({})",
+                    timeouts.request.as_secs(),
+                    gemini_model
+                ))
+                .into_response();
+                resp.extensions_mut().insert(labels);
+                return Ok(resp);
+            }
+        };
+        let gemini_response = raw_response.get("response").unwrap_or(&raw_response);
+        let batch_vectors = batch_embeddings(gemini_response).map_err(OpenAIError::bad_gateway)?;
+        if batch_vectors.len() != batch.len() {
+            return Err(OpenAIError::bad_gateway(format!(
+                "Upstream returned {} embeddings for {} inputs",
+                batch_vectors.len(),
+                batch.len()
+            )));
+        }
+        vectors.extend(batch_vectors);
+    }
+
+    tracing::info!(
+        "Embeddings request: {} -> {} ({} inputs, account: {}, key: {})",
+        model,
+        gemini_model,
+        texts.len(),
+        labels.account,
+        client_key.as_ref().map(|k| k.name.as_str()).unwrap_or("-")
+    );
+
+    let prompt_tokens = estimate_tokens(&texts);
+    metrics().record_tokens(&gemini_model, &labels.account, prompt_tokens as u64, 0, 0);
+    if let Some(key) = &client_key {
+        state.usage.record_tokens(&key.name, prompt_tokens, 0);
+    }
+
+    let mut resp = Json(to_openai_embeddings(vectors, model, base64, prompt_tokens)).into_response();
+    resp.extensions_mut().insert(labels);
+    Ok(resp)
+}
+
+/// Download remote `image_url` parts and replace them with data URLs.
+///
+/// Runs after the request is validated and the key is authorized for the model, so a
+/// rejected request never triggers a download. The mapper only turns data URLs into
+/// `inlineData`; a failed download is a 400 rather than a silently dropped image.
+pub(crate) async fn inline_remote_images(state: &AppState, request: &mut OpenAIChatRequest) -> Result<(), OpenAIError> {
+    for (i, msg) in request.messages.iter_mut().enumerate() {
+        let Some(OpenAIContent::Parts(parts)) = &mut msg.content else {
+            continue;
+        };
+        for (j, part) in parts.iter_mut().enumerate() {
+            let OpenAIContentPart::ImageUrl { image_url } = part else {
+                continue;
+            };
+            if !is_remote_url(&image_url.url) {
+                continue;
+            }
+            match state.media.fetch(&image_url.url).await {
+                Ok(media) => image_url.url = media.data_url(),
+                Err(e) => {
+                    tracing::warn!("[OpenAI] Failed to fetch image_url {}: {}", image_url.url, e);
+                    return Err(OpenAIError::invalid_request(
+                        format!("Failed to fetch image_url '{}': {}", image_url.url, e),
+                        Some(&format!("messages[{}].content[{}].image_url.url", i, j)),
+                        Some("invalid_image_url"),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve model mapping: custom > openai > anthropic > built-in aliases (`gpt-4o`, ...),
+/// then Gemini/Claude names as-is.
+/// Anything else is a 404 `model_not_found` rather than a silent swap to another model.
+pub(crate) async fn resolve_model(state: &AppState, model: &str) -> Result<String, OpenAIError> {
     // Check custom mapping first
     {
         let custom = state.custom_mapping.read().await;
         if let Some(mapped) = custom.get(model) {
-            return mapped.clone();
+            return Ok(mapped.clone());
         }
     }
     
@@ -384,7 +507,7 @@ async fn resolve_model(state: &AppState, model: &str) -> String {
     {
         let openai = state.openai_mapping.read().await;
         if let Some(mapped) = openai.get(model) {
-            return mapped.clone();
+            return Ok(mapped.clone());
         }
     }
     
@@ -392,14 +515,95 @@ async fn resolve_model(state: &AppState, model: &str) -> String {
     {
         let anthropic = state.anthropic_mapping.read().await;
         if let Some(mapped) = anthropic.get(model) {
-            return mapped.clone();
+            return Ok(mapped.clone());
         }
     }
     
-    // Default: use as-is if it looks like a Gemini model, otherwise default to flash
+    // Built-in aliases, the same table `/v1/models` advertises
+    if let Some(mapped) = builtin_model_mapping(model) {
+        return Ok(mapped.to_string());
+    }
+    
+    // Unmapped names pass through only if upstream can serve them directly
     if model.starts_with("gemini-") || model.starts_with("models/") || model.starts_with("claude-") {
-        model.to_string()
+        Ok(model.to_string())
     } else {
-        "gemini-2.5-flash".to_string()
+        Err(OpenAIError::NotFound {
+            message: format!("The model '{}' does not exist", model),
+            param: Some("model".to_string()),
+            code: Some("model_not_found".to_string()),
+        })
+    }
+}
+
+/// Resolve an embedding model: mappings first, then Gemini embedding names as-is
+async fn resolve_embedding_model(state: &AppState, model: &str) -> String {
+    if let Some(mapped) = state.custom_mapping.read().await.get(model) {
+        return mapped.clone();
+    }
+    if let Some(mapped) = state.openai_mapping.read().await.get(model) {
+        return mapped.clone();
+    }
+    if is_gemini_embedding_model(model) {
+        model.trim_start_matches("models/").to_string()
+    } else {
+        DEFAULT_EMBEDDING_MODEL.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::proxy::handlers::responses::handle_responses;
+
+    fn test_state() -> AppState {
+        let config: Config = toml::from_str(
+            r#"
+            [model_mapping.openai]
+            gpt-4o = "gemini-2.5-pro"
+            "#,
+        )
+        .unwrap();
+        AppState::for_tests(&config)
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model_rejects_unknown_names() {
+        let state = test_state();
+        assert_eq!(resolve_model(&state, "gpt-4o").await.unwrap(), "gemini-2.5-pro");
+        assert_eq!(resolve_model(&state, "gemini-3-flash").await.unwrap(), "gemini-3-flash");
+        assert_eq!(resolve_model(&state, "claude-sonnet-4-5").await.unwrap(), "claude-sonnet-4-5");
+        // Standard aliases work without any configured mapping
+        assert_eq!(resolve_model(&state, "gpt-4o-mini").await.unwrap(), "gemini-2.5-flash");
+        assert_eq!(resolve_model(&state, "gpt-3.5-turbo").await.unwrap(), "gemini-2.5-flash");
+        assert_eq!(resolve_model(&state, "gpt-4").await.unwrap(), "gemini-2.5-pro");
+
+        match resolve_model(&state, "my-typo-model").await {
+            Err(OpenAIError::NotFound { param, code, .. }) => {
+                assert_eq!(param.as_deref(), Some("model"));
+                assert_eq!(code.as_deref(), Some("model_not_found"));
+            }
+            other => panic!("expected model_not_found, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_model_is_not_found_before_dispatch() {
+        let body = json!({"model": "my-typo-model", "messages": [{"role": "user", "content": "hi"}]});
+        let err = handle_chat_completions(State(test_state()), HeaderMap::new(), None, Ok(Json(body)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_responses_require_model() {
+        let body = json!({"input": "hi"});
+        let err = handle_responses(State(test_state()), HeaderMap::new(), None, Ok(Json(body))).await.unwrap_err();
+        match err {
+            OpenAIError::InvalidRequest { param, .. } => assert_eq!(param.as_deref(), Some("model")),
+            other => panic!("expected invalid_request, got {:?}", other),
+        }
     }
 }
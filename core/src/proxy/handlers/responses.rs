@@ -0,0 +1,241 @@
+//! OpenAI Responses API handler
+//! Handles /v1/responses by way of the chat completions → Gemini mappers
+
+use axum::{
+    body::Body,
+    extract::{rejection::JsonRejection, Json, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::Value;
+
+use super::openai::{inline_remote_images, resolve_model};
+use crate::proxy::mappers::openai::request::{strict_response_schema, OpenAIRequestError};
+use crate::proxy::mappers::openai::{
+    convert_chat_response, parse_chat_request, resolve_openai_config, transform_openai_request, wrap_v1internal,
+    OpenAIError,
+};
+use crate::proxy::mappers::openai::response::StrictSchema;
+use crate::proxy::mappers::openai::responses::{
+    chat_to_response, create_responses_sse_stream, responses_to_chat, CompletedResponse, CompletionCallback,
+    ResponseMeta,
+};
+use crate::proxy::metrics::{metrics, RequestLabels};
+use crate::proxy::middleware::auth::{model_not_allowed, ClientKey, ClientProtocol};
+use crate::proxy::server::AppState;
+use crate::proxy::session_manager::SessionManager;
+use crate::proxy::upstream::dispatcher::{dispatch, DispatchRequest, Dispatched};
+use crate::proxy::upstream::timeouts::{with_stream_timeouts, ModelTimeouts};
+
+/// Handle POST /v1/responses
+pub async fn handle_responses(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    client_key: Option<Extension<ClientKey>>,
+    payload: Result<Json<Value>, JsonRejection>,
+) -> Result<Response, OpenAIError> {
+    let client_key = client_key.map(|Extension(k)| k);
+    let Json(body) = payload?;
+    let owner = client_key.as_ref().map(|k| k.name.as_str());
+
+    // No default model: a request without one is rejected, not routed somewhere arbitrary
+    let model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .filter(|m| !m.trim().is_empty())
+        .ok_or_else(|| OpenAIRequestError::new("model", "is required"))?
+        .to_string();
+    let stream = body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false);
+    let store = body.get("store").and_then(|v| v.as_bool()).unwrap_or(true);
+
+    // Continue a stored conversation
+    let previous_response_id = body.get("previous_response_id").and_then(|v| v.as_str());
+    let history = match previous_response_id {
+        Some(id) => match state.responses.get(id, owner) {
+            Some(messages) => messages,
+            None => {
+                return Err(OpenAIError::NotFound {
+                    message: format!("Previous response with id '{}' not found.", id),
+                    param: Some("previous_response_id".to_string()),
+                    code: Some("previous_response_not_found".to_string()),
+                })
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let history_len = history.len();
+    let (chat_body, mut conversation) = responses_to_chat(&body, history)
+        .map_err(|e| OpenAIError::invalid_request(e, Some("input"), Some("invalid_value")))?;
+    // Only this turn is stored; earlier turns stay behind `previous_response_id`
+    let turn = conversation.split_off(history_len);
+
+    let mapped_model = resolve_model(&state, model.trim_end_matches("-online")).await?;
+
+    let mut request = parse_chat_request(&chat_body)?;
+    let config = resolve_openai_config(&request, &mapped_model);
+    let gemini_model = config.final_model.clone();
+
+    // The allowlist applies to the model actually sent upstream, after mappings and variants
+    if let Some(key) = &client_key {
+        if !key.allows_model(&gemini_model) {
+            return Ok(model_not_allowed(ClientProtocol::OpenAI, &key.name, &gemini_model));
+        }
+    }
+
+    let strict_schema = match strict_response_schema(&request) {
+        Some((name, schema)) => Some(
+            StrictSchema::new(name, &schema)
+                .map_err(|e| OpenAIError::invalid_request(e, Some("text.format"), None))?,
+        ),
+        None => None,
+    };
+
+    // Downloads happen only for an authorized request; the stored conversation keeps the original URLs
+    inline_remote_images(&state, &mut request).await?;
+
+    let mut labels = RequestLabels { model: gemini_model.clone(), account: String::new() };
+    let timeouts = ModelTimeouts::resolve(&*state.timeouts.read().await, &gemini_model);
+    let call_limit = if stream { timeouts.first_byte } else { timeouts.request };
+    let trace_id = format!("rsp-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let inner_request = transform_openai_request(&request, &config);
+    // History is replayed from the store, so the first turn fingerprints the whole conversation
+    let session_id = SessionManager::openai_session_id(&headers, &request);
+
+    let dispatched = dispatch(
+        &state,
+        DispatchRequest {
+            quota_group: &config.request_type,
+            session_id: Some(&session_id),
+            allowed_accounts: client_key.as_ref().and_then(|k| k.allowed_accounts.as_deref()),
+            method: if stream { "streamGenerateContent" } else { "generateContent" },
+            query: if stream { Some("alt=sse") } else { None },
+            model: &gemini_model,
+            timeout: call_limit,
+            trace_id: &trace_id,
+        },
+        |project_id| Ok(wrap_v1internal(inner_request.clone(), &config, project_id)),
+    )
+    .await;
+    let Dispatched { response, account, deadline } = match dispatched {
+        Ok(d) => d,
+        Err(e) => {
+            let mut resp = OpenAIError::from(e).into_response();
+            resp.extensions_mut().insert(labels);
+            return Ok(resp);
+        }
+    };
+    let email = account.email;
+    labels.account = email.clone();
+
+    tracing::info!(
+        "Responses request: {} -> {} (account: {}, key: {})",
+        model,
+        gemini_model,
+        email,
+        owner.unwrap_or("-")
+    );
+
+    let meta = ResponseMeta::new(&body, &model);
+
+    if stream {
+        let gemini_stream = with_stream_timeouts(
+            response.bytes_stream(),
+            deadline.saturating_duration_since(tokio::time::Instant::now()),
+            timeouts.idle,
+        );
+        let on_complete: CompletionCallback = {
+            let state = state.clone();
+            let owner = owner.map(str::to_string);
+            let previous = previous_response_id.map(str::to_string);
+            let (gemini_model, email) = (gemini_model.clone(), email.clone());
+            Box::new(move |completed: CompletedResponse| {
+                if let Some(u) = &completed.usage {
+                    let input = u.prompt_token_count.unwrap_or(0);
+                    let output = u.candidates_token_count.unwrap_or(0);
+                    let cached = u.cached_content_token_count.unwrap_or(0);
+                    metrics().record_tokens(&gemini_model, &email, input.saturating_sub(cached) as u64, output as u64, cached as u64);
+                    if let Some(owner) = &owner {
+                        state.usage.record_tokens(owner, input, output);
+                    }
+                }
+                if store {
+                    let mut messages = turn;
+                    messages.push(completed.assistant_message);
+                    let id = completed.response["id"].as_str().unwrap_or_default();
+                    state.responses.insert(id, owner.as_deref(), previous.as_deref(), messages);
+                }
+            })
+        };
+        let events = create_responses_sse_stream(gemini_stream, meta, strict_schema, Some(on_complete));
+
+        let stream_guard = metrics().stream_guard("/v1/responses");
+        let sse_stream = events.map(move |result| -> Result<Bytes, std::io::Error> {
+            let _ = &stream_guard;
+            result.map_err(std::io::Error::other)
+        });
+
+        let mut resp = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::CONNECTION, "keep-alive")
+            .body(Body::from_stream(sse_stream))
+            .unwrap();
+        resp.extensions_mut().insert(labels);
+        return Ok(resp);
+    }
+
+    let raw_response: Value = match tokio::time::timeout_at(deadline, response.json()).await {
+        Ok(result) => result.map_err(|e| OpenAIError::bad_gateway(format!("Invalid JSON response: {}", e)))?,
+        Err(_) => {
+            let mut resp = OpenAIError::Timeout(format!(
+                "Upstream did not respond within {}s ({})",
+                call_limit.as_secs(),
+                gemini_model
+            ))
+            .into_response();
+            resp.extensions_mut().insert(labels);
+            return Ok(resp);
+        }
+    };
+    let gemini_response = raw_response.get("response").unwrap_or(&raw_response);
+
+    if let Some(usage) = gemini_response.get("usageMetadata") {
+        let input = usage.get("promptTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+        let output = usage.get("candidatesTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+        let cached = usage.get("cachedContentTokenCount").and_then(|v| v.as_u64()).unwrap_or(0);
+        metrics().record_tokens(&gemini_model, &email, input.saturating_sub(cached), output, cached);
+        if let Some(owner) = owner {
+            state.usage.record_tokens(owner, input as u32, output as u32);
+        }
+    }
+
+    let chat_response = convert_chat_response(gemini_response, &model);
+    let message = chat_response["choices"][0]["message"].clone();
+
+    if let Some(strict) = &strict_schema {
+        if message.get("tool_calls").is_none() {
+            if let Err(e) = strict.validate(message["content"].as_str().unwrap_or_default()) {
+                tracing::warn!("[Responses] {}", e);
+                let mut resp = OpenAIError::schema_violation(e).into_response();
+                resp.extensions_mut().insert(labels);
+                return Ok(resp);
+            }
+        }
+    }
+
+    let response_body = chat_to_response(&chat_response, &meta);
+    if store {
+        let mut messages = turn;
+        messages.push(message);
+        state.responses.insert(&meta.id, owner, previous_response_id, messages);
+    }
+
+    let mut resp = Json(response_body).into_response();
+    resp.extensions_mut().insert(labels);
+    Ok(resp)
+}
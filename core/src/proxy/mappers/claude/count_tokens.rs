@@ -0,0 +1,118 @@
+// Claude count_tokens 支持
+// 上游 countTokens 请求构造 + 上游不可用时的本地估算
+
+use serde_json::{json, Value};
+
+/// Gemini 对单张图片/单个内联文件按固定 token 计费
+const INLINE_DATA_TOKENS: u32 = 258;
+
+/// 由 transform_claude_request_in 的产物构造 v1internal countTokens 请求体
+///
+/// countTokens 只接受 contents，因此 systemInstruction 与工具声明以文本形式
+/// 放在最前面的 user 轮次中，使结果与实际请求的输入规模一致。
+pub fn build_count_tokens_request(v1internal_body: &Value) -> Value {
+    let inner = v1internal_body.get("request").unwrap_or(v1internal_body);
+    let model = v1internal_body.get("model").and_then(|v| v.as_str()).unwrap_or("gemini-2.5-flash");
+
+    let mut preamble = Vec::new();
+    if let Some(parts) = inner.pointer("/systemInstruction/parts").and_then(|p| p.as_array()) {
+        preamble.extend(parts.iter().filter(|p| p.get("text").is_some()).cloned());
+    }
+    if let Some(tools) = inner.get("tools").filter(|t| t.as_array().is_some_and(|a| !a.is_empty())) {
+        preamble.push(json!({ "text": tools.to_string() }));
+    }
+
+    let mut contents = Vec::new();
+    if !preamble.is_empty() {
+        contents.push(json!({ "role": "user", "parts": preamble }));
+    }
+    contents.extend(inner.get("contents").and_then(|c| c.as_array()).into_iter().flatten().cloned());
+
+    json!({
+        "request": {
+            "model": format!("models/{}", model),
+            "contents": contents
+        }
+    })
+}
+
+/// 解析上游 countTokens 响应中的 totalTokens
+pub fn parse_total_tokens(response: &Value) -> Option<u32> {
+    let body = response.get("response").unwrap_or(response);
+    body.get("totalTokens").and_then(|v| v.as_u64()).map(|n| n as u32)
+}
+
+/// 本地估算 (约 4 字符 / token，内联数据按固定值)，仅在上游不可用时使用
+pub fn estimate_input_tokens(v1internal_body: &Value) -> u32 {
+    let request = build_count_tokens_request(v1internal_body);
+    let parts = request["request"]["contents"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.get("parts").and_then(|p| p.as_array()))
+        .flatten();
+
+    let mut tokens = 0u32;
+    for part in parts {
+        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+            tokens += text.chars().count().div_ceil(4) as u32;
+        } else if part.get("inlineData").is_some() {
+            tokens += INLINE_DATA_TOKENS;
+        } else if let Some(call) = part.get("functionCall") {
+            tokens += call.to_string().chars().count().div_ceil(4) as u32;
+        } else if let Some(response) = part.get("functionResponse") {
+            // 多模态 functionResponse 的附件按内联数据计
+            tokens += response["response"].to_string().chars().count().div_ceil(4) as u32;
+            tokens += response["parts"].as_array().map_or(0, |p| p.len() as u32) * INLINE_DATA_TOKENS;
+        }
+    }
+    // 与 Anthropic 一致：非空请求至少 1 token
+    tokens.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body() -> Value {
+        json!({
+            "project": "proj-1",
+            "model": "claude-sonnet-4-5",
+            "request": {
+                "systemInstruction": {"parts": [{"text": "You are helpful."}]},
+                "contents": [
+                    {"role": "user", "parts": [
+                        {"text": "What is in this picture?"},
+                        {"inlineData": {"mimeType": "image/png", "data": "iVBORw0KGgo="}}
+                    ]},
+                    {"role": "model", "parts": [{"functionCall": {"name": "look", "args": {}}}]}
+                ],
+                "tools": [{"functionDeclarations": [{"name": "look", "parameters": {"type": "object"}}]}],
+                "generationConfig": {"maxOutputTokens": 1024}
+            }
+        })
+    }
+
+    #[test]
+    fn test_build_count_tokens_request() {
+        let request = build_count_tokens_request(&body());
+        assert_eq!(request["request"]["model"], "models/claude-sonnet-4-5");
+        let contents = request["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        // system 与 tools 合并为首个 user 轮次
+        assert_eq!(contents[0]["parts"][0]["text"], "You are helpful.");
+        assert!(contents[0]["parts"][1]["text"].as_str().unwrap().contains("functionDeclarations"));
+        assert!(request["request"].get("generationConfig").is_none());
+    }
+
+    #[test]
+    fn test_parse_and_estimate() {
+        assert_eq!(parse_total_tokens(&json!({"totalTokens": 42})), Some(42));
+        assert_eq!(parse_total_tokens(&json!({"response": {"totalTokens": 7}})), Some(7));
+        assert_eq!(parse_total_tokens(&json!({})), None);
+
+        let estimate = estimate_input_tokens(&body());
+        assert!(estimate > INLINE_DATA_TOKENS);
+        assert_eq!(estimate_input_tokens(&json!({"request": {"contents": []}})), 1);
+    }
+}
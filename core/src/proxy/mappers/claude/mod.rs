@@ -1,6 +1,7 @@
 // Claude mapper 模块
 // 负责 Claude ↔ Gemini 协议转换
 
+pub mod count_tokens;
 pub mod models;
 pub mod request;
 pub mod response;
@@ -8,7 +9,9 @@ pub mod streaming;
 pub mod utils;
 
 pub use models::*;
-pub use request::transform_claude_request_in;
+pub use request::{
+    resolve_claude_config, transform_claude_request_in, transform_claude_request_with_defaults, validate_tool_choice,
+};
 pub use response::transform_response;
 pub use streaming::{PartProcessor, StreamingState};
 
@@ -16,18 +19,28 @@ use bytes::Bytes;
 use futures::Stream;
 use std::pin::Pin;
 
+use crate::proxy::upstream::timeouts::UpstreamByteStream;
+
+/// 流结束时回调最终的 usageMetadata (用于按 API Key 统计用量)
+pub type UsageCallback = Box<dyn FnOnce(&UsageMetadata) + Send>;
+
 /// 创建从 Gemini SSE 流到 Claude SSE 流的转换
 pub fn create_claude_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut gemini_stream: UpstreamByteStream,
     trace_id: String,
     email: String,
+    stop_sequences: Vec<String>,
+    single_tool_use: bool,
+    on_usage: Option<UsageCallback>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
     use futures::StreamExt;
 
     Box::pin(stream! {
-        let mut state = StreamingState::new();
+        let mut state = StreamingState::new()
+            .with_stop_sequences(stop_sequences)
+            .with_single_tool_use(single_tool_use);
         let mut buffer = BytesMut::new();
 
         while let Some(chunk_result) = gemini_stream.next().await {
@@ -50,6 +63,15 @@ pub fn create_claude_sse_stream(
                         }
                     }
                 }
+                Err(e) if e.is_timeout() => {
+                    // 超时: 发送 Claude error 事件并结束, 不再补发 message_stop
+                    yield Ok(state.emit("error", serde_json::json!({
+                        "type": "error",
+                        "error": { "type": "timeout_error", "message": e.to_string() }
+                    })));
+                    state.message_stop_sent = true;
+                    break;
+                }
                 Err(e) => {
                     yield Err(format!("Stream error: {}", e));
                     break;
@@ -61,6 +83,10 @@ pub fn create_claude_sse_stream(
         for chunk in emit_force_stop(&mut state) {
             yield Ok(chunk);
         }
+
+        if let (Some(callback), Some(usage)) = (on_usage, state.final_usage.as_ref()) {
+            callback(usage);
+        }
     })
 }
 
@@ -12,6 +12,8 @@ pub struct ClaudeRequest {
     pub system: Option<SystemPrompt>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
     #[serde(default)]
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -23,6 +25,8 @@ pub struct ClaudeRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_k: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub thinking: Option<ThinkingConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<Metadata>,
@@ -138,17 +142,26 @@ pub enum ContentBlock {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageSource {
     #[serde(rename = "type")]
-    pub source_type: String,
+    pub source_type: String, // "base64" | "url"
+    #[serde(default)]
     pub media_type: String,
+    #[serde(default)]
     pub data: String,
+    /// Remote location for `type: "url"`, downloaded by the handler before mapping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSource {
     #[serde(rename = "type")]
-    pub source_type: String, // "base64"
+    pub source_type: String, // "base64" | "url"
+    #[serde(default)]
     pub media_type: String,  // e.g. "application/pdf"
+    #[serde(default)]
     pub data: String,        // base64 data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 /// Tool - supports both client tools (with input_schema) and server tools (like web_search)
@@ -201,6 +214,38 @@ impl Tool {
     }
 }
 
+/// tool_choice: auto / any / tool (指定名称) / none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    Auto {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    Any {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    Tool {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    None,
+}
+
+impl ToolChoice {
+    /// 是否要求每轮最多一个 tool_use
+    pub fn disable_parallel_tool_use(&self) -> bool {
+        match self {
+            ToolChoice::Auto { disable_parallel_tool_use }
+            | ToolChoice::Any { disable_parallel_tool_use }
+            | ToolChoice::Tool { disable_parallel_tool_use, .. } => disable_parallel_tool_use.unwrap_or(false),
+            ToolChoice::None => false,
+        }
+    }
+}
+
 /// Metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
@@ -348,6 +393,9 @@ pub struct UsageMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "cachedContentTokenCount")]
     pub cached_content_token_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "thoughtsTokenCount")]
+    pub thoughts_token_count: Option<u32>,
 }
 
 // ========== Grounding Metadata (for googleSearch results) ==========
@@ -2,6 +2,9 @@
 // 对应 transformClaudeRequestIn
 
 use super::models::*;
+use crate::config::GenerationConfig;
+use crate::proxy::mappers::common_utils::RequestConfig;
+use crate::proxy::common::model_mapping::{max_output_tokens, DEFAULT_MAX_OUTPUT_TOKENS};
 use crate::proxy::mappers::signature_store::get_thought_signature;
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -106,10 +109,58 @@ fn clean_cache_control_from_messages(messages: &mut [Message]) {
     }
 }
 
-/// 转换 Claude 请求为 Gemini v1internal 格式
+/// Gemini 单次请求最多接受的 stopSequences 数量
+const MAX_STOP_SEQUENCES: usize = 5;
+
+/// 是否带联网工具 (server tool or built-in tool)
+fn has_web_search_tool(tools: &Option<Vec<Tool>>) -> bool {
+    tools.iter().flatten().any(|t| {
+        t.is_web_search()
+            || t.name.as_deref() == Some("google_search")
+            || t.type_.as_deref() == Some("web_search_20250305")
+    })
+}
+
+/// 映射后的上游模型 (联网请求固定使用 flash)
+fn map_upstream_model(model: &str, tools: &Option<Vec<Tool>>) -> String {
+    // [IMPROVED] 提取 web search 模型为常量，便于维护
+    const WEB_SEARCH_FALLBACK_MODEL: &str = "gemini-2.5-flash";
+
+    if has_web_search_tool(tools) {
+        tracing::debug!(
+            "[Claude-Request] Web search tool detected, using fallback model: {}",
+            WEB_SEARCH_FALLBACK_MODEL
+        );
+        WEB_SEARCH_FALLBACK_MODEL.to_string()
+    } else {
+        crate::proxy::common::model_mapping::map_claude_model_to_gemini(model)
+    }
+}
+
+/// 解析实际下发的模型与请求类型 (handler 据此校验 Key 白名单、选择配额组)
+pub fn resolve_claude_config(model: &str, tools: &Option<Vec<Tool>>) -> RequestConfig {
+    let mapped_model = map_upstream_model(model, tools);
+
+    // 将 Claude 工具转为 Value 数组以便探测联网
+    let tools_val: Option<Vec<Value>> = tools.as_ref().map(|list| {
+        list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
+    });
+    crate::proxy::mappers::common_utils::resolve_request_config(model, &mapped_model, &tools_val)
+}
+
+/// 转换 Claude 请求为 Gemini v1internal 格式 (使用默认生成配置)
 pub fn transform_claude_request_in(
     claude_req: &ClaudeRequest,
     project_id: &str,
+) -> Result<Value, String> {
+    transform_claude_request_with_defaults(claude_req, project_id, &GenerationConfig::default())
+}
+
+/// 转换 Claude 请求为 Gemini v1internal 格式 (`[generation]` 提供默认停止序列)
+pub fn transform_claude_request_with_defaults(
+    claude_req: &ClaudeRequest,
+    project_id: &str,
+    defaults: &GenerationConfig,
 ) -> Result<Value, String> {
     // [CRITICAL FIX] 预先清理所有消息中的 cache_control 字段
     // 这解决了 VS Code 插件等客户端在多轮对话中将历史消息的 cache_control 字段
@@ -119,17 +170,9 @@ pub fn transform_claude_request_in(
     let claude_req = &cleaned_req; // 后续使用清理后的请求
 
     // 检测是否有联网工具 (server tool or built-in tool)
-    let has_web_search_tool = claude_req
-        .tools
-        .as_ref()
-        .map(|tools| {
-            tools.iter().any(|t| {
-                t.is_web_search() 
-                    || t.name.as_deref() == Some("google_search")
-                    || t.type_.as_deref() == Some("web_search_20250305")
-            })
-        })
-        .unwrap_or(false);
+    let has_web_search_tool = has_web_search_tool(&claude_req.tools);
+
+    validate_tool_choice(claude_req)?;
 
     // 用于存储 tool_use id -> name 映射
     let mut tool_id_to_name: HashMap<String, String> = HashMap::new();
@@ -137,28 +180,9 @@ pub fn transform_claude_request_in(
     // 1. System Instruction (注入动态身份防护)
     let system_instruction = build_system_instruction(&claude_req.system, &claude_req.model);
 
-    //  Map model name (Use standard mapping)
-    // [IMPROVED] 提取 web search 模型为常量，便于维护
-    const WEB_SEARCH_FALLBACK_MODEL: &str = "gemini-2.5-flash";
-
-    let mapped_model = if has_web_search_tool {
-        tracing::debug!(
-            "[Claude-Request] Web search tool detected, using fallback model: {}",
-            WEB_SEARCH_FALLBACK_MODEL
-        );
-        WEB_SEARCH_FALLBACK_MODEL.to_string()
-    } else {
-        crate::proxy::common::model_mapping::map_claude_model_to_gemini(&claude_req.model)
-    };
-    
-    // 将 Claude 工具转为 Value 数组以便探测联网
-    let tools_val: Option<Vec<Value>> = claude_req.tools.as_ref().map(|list| {
-        list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
-    });
-
-
-    // Resolve grounding config
-    let config = crate::proxy::mappers::common_utils::resolve_request_config(&claude_req.model, &mapped_model, &tools_val);
+    //  Map model name (Use standard mapping) & resolve grounding config
+    let mapped_model = map_upstream_model(&claude_req.model, &claude_req.tools);
+    let config = resolve_claude_config(&claude_req.model, &claude_req.tools);
     
     // [CRITICAL FIX] Disable dummy thought injection for Vertex AI
     // [CRITICAL FIX] Disable dummy thought injection for Vertex AI
@@ -251,7 +275,13 @@ pub fn transform_claude_request_in(
     }
 
     // 4. Generation Config & Thinking (Pass final is_thinking_enabled)
-    let generation_config = build_generation_config(claude_req, has_web_search_tool, is_thinking_enabled);
+    let generation_config = build_generation_config(
+        claude_req,
+        &config.final_model,
+        has_web_search_tool,
+        is_thinking_enabled,
+        &defaults.default_stop_sequences,
+    );
 
     // 2. Contents (Messages)
     let contents = build_contents(
@@ -259,6 +289,7 @@ pub fn transform_claude_request_in(
         &mut tool_id_to_name,
         is_thinking_enabled,
         allow_dummy_thought,
+        config.final_model.starts_with("gemini-3"),
     )?;
 
     // 3. Tools
@@ -285,13 +316,8 @@ pub fn transform_claude_request_in(
     }
 
     if let Some(tools_val) = tools {
+        inner_request["toolConfig"] = build_tool_config(claude_req.tool_choice.as_ref(), &tools_val);
         inner_request["tools"] = tools_val;
-        // 显式设置工具配置模式为 VALIDATED
-        inner_request["toolConfig"] = json!({
-            "functionCallingConfig": {
-                "mode": "VALIDATED"
-            }
-        });
     }
 
     // Inject googleSearch tool if needed (and not already done by build_tools)
@@ -476,6 +502,7 @@ fn build_contents(
     tool_id_to_name: &mut HashMap<String, String>,
     is_thinking_enabled: bool,
     allow_dummy_thought: bool,
+    multimodal_tool_results: bool,
 ) -> Result<Value, String> {
     let mut contents = Vec::new();
     let mut last_thought_signature: Option<String> = None;
@@ -619,27 +646,13 @@ fn build_contents(
                                 .cloned()
                                 .unwrap_or_else(|| tool_use_id.clone());
 
-                            // 处理 content：可能是一个内容块数组或单字符串
-                            let mut merged_content = match content {
-                                serde_json::Value::String(s) => s.clone(),
-                                serde_json::Value::Array(arr) => arr
-                                    .iter()
-                                    .filter_map(|block| {
-                                        if let Some(text) =
-                                            block.get("text").and_then(|v| v.as_str())
-                                        {
-                                            Some(text)
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect::<Vec<_>>()
-                                    .join("\n"),
-                                _ => content.to_string(),
-                            };
+                            // 处理 content：可能是一个内容块数组或单字符串 (图片/文档单独取出)
+                            let (mut merged_content, attachments) = split_tool_result_content(tool_use_id, content);
 
                             // [优化] 如果结果为空，注入显式确认信号，防止模型幻觉
-                            if merged_content.trim().is_empty() {
+                            if merged_content.trim().is_empty() && !attachments.is_empty() {
+                                merged_content = format!("Returned {} attachment(s).", attachments.len());
+                            } else if merged_content.trim().is_empty() {
                                 if is_error.unwrap_or(false) {
                                     merged_content =
                                         "Tool execution failed with no output.".to_string();
@@ -648,6 +661,8 @@ fn build_contents(
                                 }
                             }
 
+                            // 非多模态 functionResponse 时的附件，紧跟在对应的 functionResponse 之后
+                            let mut attachment_parts = Vec::new();
                             let mut part = json!({
                                 "functionResponse": {
                                     "name": func_name,
@@ -656,12 +671,42 @@ fn build_contents(
                                 }
                             });
 
+                            // 图片/文档: 按出现顺序编号，result 中的 attachments 与之一一对应
+                            if !attachments.is_empty() {
+                                let names: Vec<&str> = attachments.iter().map(|a| a.name.as_str()).collect();
+                                if multimodal_tool_results {
+                                    // Gemini 3 多模态 functionResponse: 通过 $ref 引用 parts 中的 displayName
+                                    part["functionResponse"]["response"]["attachments"] =
+                                        json!(names.iter().map(|n| json!({ "$ref": n })).collect::<Vec<_>>());
+                                    part["functionResponse"]["parts"] = json!(attachments
+                                        .iter()
+                                        .map(|a| json!({
+                                            "inlineData": {
+                                                "mimeType": a.mime_type,
+                                                "data": a.data,
+                                                "displayName": a.name
+                                            }
+                                        }))
+                                        .collect::<Vec<_>>());
+                                } else {
+                                    // 其他模型: 以带标注的 inlineData 跟在本 functionResponse 之后 (早于后续用户文本)
+                                    part["functionResponse"]["response"]["attachments"] = json!(names);
+                                    for a in &attachments {
+                                        attachment_parts.push(json!({ "text": format!("[Attachment {}]", a.name) }));
+                                        attachment_parts.push(json!({
+                                            "inlineData": { "mimeType": a.mime_type, "data": a.data }
+                                        }));
+                                    }
+                                }
+                            }
+
                             // [修复] Tool Result 也需要回填签名（如果上下文中有）
                             if let Some(sig) = last_thought_signature.as_ref() {
                                 part["thoughtSignature"] = json!(sig);
                             }
 
                             parts.push(part);
+                            parts.extend(attachment_parts);
                         }
                         ContentBlock::ServerToolUse { .. } | ContentBlock::WebSearchToolResult { .. } => {
                             // 搜索结果 block 不应由客户端发回给上游 (已由 tool_result 替代)
@@ -749,6 +794,87 @@ fn build_contents(
     Ok(json!(contents))
 }
 
+/// tool_result 中的图片/文档
+struct ToolResultAttachment {
+    name: String,
+    mime_type: String,
+    data: String,
+}
+
+/// 拆分 tool_result content: 文本合并为 result，base64 图片/文档按出现顺序编号为附件
+fn split_tool_result_content(tool_use_id: &str, content: &Value) -> (String, Vec<ToolResultAttachment>) {
+    let blocks = match content {
+        Value::String(s) => return (s.clone(), Vec::new()),
+        Value::Array(arr) => arr,
+        _ => return (content.to_string(), Vec::new()),
+    };
+
+    let mut texts = Vec::new();
+    let mut attachments = Vec::new();
+    for block in blocks {
+        if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+            texts.push(text);
+            continue;
+        }
+        let is_media = matches!(block.get("type").and_then(|v| v.as_str()), Some("image" | "document"));
+        let Some(source) = block.get("source").filter(|s| is_media && s["type"] == "base64") else {
+            continue;
+        };
+        let mime_type = source["media_type"].as_str().unwrap_or("application/octet-stream");
+        let extension = mime_type.rsplit('/').next().unwrap_or("bin");
+        attachments.push(ToolResultAttachment {
+            name: format!("{}_{}.{}", tool_use_id, attachments.len() + 1, extension),
+            mime_type: mime_type.to_string(),
+            data: source["data"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    (texts.join("\n"), attachments)
+}
+
+/// 校验 tool_choice 引用的工具存在 (any/tool 至少需要一个工具)
+pub fn validate_tool_choice(claude_req: &ClaudeRequest) -> Result<(), String> {
+    let tool_names: Vec<&str> = claude_req
+        .tools
+        .iter()
+        .flatten()
+        .filter_map(|t| t.name.as_deref())
+        .collect();
+
+    match &claude_req.tool_choice {
+        Some(ToolChoice::Any { .. }) if tool_names.is_empty() => {
+            Err("tool_choice.type 'any' requires at least one tool".to_string())
+        }
+        Some(ToolChoice::Tool { name, .. }) if !tool_names.contains(&name.as_str()) => {
+            Err(format!("tool_choice.name '{}' does not match any tool in tools", name))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// tool_choice -> functionCallingConfig
+///
+/// 未指定时沿用 VALIDATED；指定联网工具时无法强制 (googleSearch 不是函数)，退回 AUTO
+fn build_tool_config(tool_choice: Option<&ToolChoice>, tools: &Value) -> Value {
+    let declared = |name: &str| {
+        tools
+            .pointer("/0/functionDeclarations")
+            .and_then(|d| d.as_array())
+            .is_some_and(|d| d.iter().any(|f| f["name"] == name))
+    };
+
+    let config = match tool_choice {
+        None => json!({ "mode": "VALIDATED" }),
+        Some(ToolChoice::Auto { .. }) => json!({ "mode": "AUTO" }),
+        Some(ToolChoice::None) => json!({ "mode": "NONE" }),
+        Some(ToolChoice::Any { .. }) if tools.pointer("/0/functionDeclarations").is_some() => json!({ "mode": "ANY" }),
+        Some(ToolChoice::Tool { name, .. }) if declared(name) => {
+            json!({ "mode": "ANY", "allowedFunctionNames": [name] })
+        }
+        Some(_) => json!({ "mode": "AUTO" }),
+    };
+    json!({ "functionCallingConfig": config })
+}
+
 /// 构建 Tools
 fn build_tools(tools: &Option<Vec<Tool>>, has_web_search: bool) -> Result<Option<Value>, String> {
     if let Some(tools_list) = tools {
@@ -824,11 +950,29 @@ fn build_tools(tools: &Option<Vec<Tool>>, has_web_search: bool) -> Result<Option
 /// 构建 Generation Config
 fn build_generation_config(
     claude_req: &ClaudeRequest,
+    upstream_model: &str,
     has_web_search: bool,
-    is_thinking_enabled: bool
+    is_thinking_enabled: bool,
+    default_stop_sequences: &[String],
 ) -> Value {
     let mut config = json!({});
 
+    // max_tokens 映射为 maxOutputTokens (按模型上限截断)
+    let model_limit = max_output_tokens(upstream_model);
+    let max_output = claude_req
+        .max_tokens
+        .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS)
+        .clamp(1, model_limit);
+    if claude_req.max_tokens.is_some_and(|m| m > model_limit) {
+        tracing::debug!(
+            "[Generation-Config] max_tokens {:?} exceeds {} limit, clamped to {}",
+            claude_req.max_tokens,
+            upstream_model,
+            model_limit
+        );
+    }
+    config["maxOutputTokens"] = json!(max_output);
+
     // Thinking 配置
     if let Some(thinking) = &claude_req.thinking {
         // [New Check] 必须 is_thinking_enabled 为真才生成 thinkingConfig
@@ -843,6 +987,8 @@ fn build_generation_config(
                 if is_flash_model {
                     budget = budget.min(24576);
                 }
+                // 思考预算计入输出上限，需给正文留出空间
+                budget = budget.min(max_output.saturating_sub(1));
                 thinking_config["thinkingBudget"] = json!(budget);
             }
 
@@ -884,17 +1030,21 @@ fn build_generation_config(
         config["candidateCount"] = json!(1);
     }*/
 
-    // max_tokens 映射为 maxOutputTokens
-    config["maxOutputTokens"] = json!(64000);
-
-    // [优化] 设置全局停止序列，防止流式输出冗余 (参考 done-hub)
-    config["stopSequences"] = json!([
-        "<|user|>",
-        "<|endoftext|>",
-        "<|end_of_turn|>",
-        "[DONE]",
-        "\n\nHuman:"
-    ]);
+    // 停止序列: 客户端序列优先下发，剩余名额由默认列表补齐 (防止流式输出冗余)
+    // 超出上限的客户端序列由响应侧本地匹配截断
+    let client_stops = claude_req.stop_sequences.as_deref().unwrap_or_default();
+    let mut stop_sequences: Vec<&String> = Vec::new();
+    for seq in client_stops.iter().chain(default_stop_sequences.iter()) {
+        if stop_sequences.len() == MAX_STOP_SEQUENCES {
+            break;
+        }
+        if !seq.is_empty() && !stop_sequences.contains(&seq) {
+            stop_sequences.push(seq);
+        }
+    }
+    if !stop_sequences.is_empty() {
+        config["stopSequences"] = json!(stop_sequences);
+    }
 
     config
 }
@@ -919,6 +1069,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: None,
             metadata: None,
             output_config: None,
@@ -1016,6 +1168,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: None,
             metadata: None,
             output_config: None,
@@ -1073,6 +1227,7 @@ mod tests {
                                 source_type: "base64".to_string(),
                                 media_type: "image/png".to_string(),
                                 data: "iVBORw0KGgo=".to_string(),
+                                url: None,
                             },
                             cache_control: Some(json!({"type": "ephemeral"})), // 这个也应该被清理
                         },
@@ -1086,6 +1241,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: None,
             metadata: None,
             output_config: None,
@@ -1158,6 +1315,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: Some(ThinkingConfig {
                 type_: "enabled".to_string(),
                 budget_tokens: Some(1024),
@@ -1210,6 +1369,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: None, // 未启用 thinking
             metadata: None,
             output_config: None,
@@ -1260,6 +1421,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: Some(ThinkingConfig {
                 type_: "enabled".to_string(),
                 budget_tokens: Some(1024),
@@ -1303,6 +1466,8 @@ mod tests {
             temperature: None,
             top_p: None,
             top_k: None,
+            stop_sequences: None,
+            tool_choice: None,
             thinking: None,
             metadata: None,
             output_config: None,
@@ -1318,4 +1483,189 @@ mod tests {
         assert!(text.contains("[Redacted Thinking: some data]"));
         assert!(parts[0].get("thought").is_none(), "Redacted thinking should NOT have thought: true");
     }
+
+    #[test]
+    fn test_max_tokens_and_stop_sequences() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "gemini-2.0-flash",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "max_tokens": 100000,
+            "stop_sequences": ["\nObservation:", "<|user|>"]
+        }))
+        .unwrap();
+        let defaults = GenerationConfig::default();
+
+        let body = transform_claude_request_with_defaults(&req, "test-project", &defaults).unwrap();
+        let gen_config = &body["request"]["generationConfig"];
+        assert_eq!(gen_config["maxOutputTokens"], 8192);
+        // 客户端序列在前，默认项去重后补齐至 5 个
+        assert_eq!(
+            gen_config["stopSequences"],
+            json!(["\nObservation:", "<|user|>", "<|endoftext|>", "<|end_of_turn|>", "[DONE]"])
+        );
+
+        // 客户端序列超过上限时只下发前 5 个，其余由本地匹配
+        let mut many = req.clone();
+        many.stop_sequences = Some((1..=7).map(|i| format!("STOP{}", i)).collect());
+        let body = transform_claude_request_with_defaults(&many, "test-project", &defaults).unwrap();
+        assert_eq!(
+            body["request"]["generationConfig"]["stopSequences"],
+            json!(["STOP1", "STOP2", "STOP3", "STOP4", "STOP5"])
+        );
+
+        let no_defaults = GenerationConfig { default_stop_sequences: Vec::new() };
+        let mut req = req;
+        req.max_tokens = Some(512);
+        req.stop_sequences = None;
+        let body = transform_claude_request_with_defaults(&req, "test-project", &no_defaults).unwrap();
+        assert_eq!(body["request"]["generationConfig"]["maxOutputTokens"], 512);
+        assert!(body["request"]["generationConfig"].get("stopSequences").is_none());
+    }
+
+    #[test]
+    fn test_tool_choice_mapping() {
+        let request = |tool_choice: Value| -> ClaudeRequest {
+            serde_json::from_value(json!({
+                "model": "claude-sonnet-4-5",
+                "messages": [{"role": "user", "content": "Extract the invoice"}],
+                "tools": [
+                    {"name": "extract_invoice", "input_schema": {"type": "object", "properties": {}}},
+                    {"name": "lookup", "input_schema": {"type": "object", "properties": {}}}
+                ],
+                "tool_choice": tool_choice
+            }))
+            .unwrap()
+        };
+        let tool_config = |tool_choice: Value| {
+            let body = transform_claude_request_in(&request(tool_choice), "test-project").unwrap();
+            body["request"]["toolConfig"]["functionCallingConfig"].clone()
+        };
+
+        assert_eq!(
+            tool_config(json!({"type": "tool", "name": "extract_invoice"})),
+            json!({"mode": "ANY", "allowedFunctionNames": ["extract_invoice"]})
+        );
+        assert_eq!(tool_config(json!({"type": "any"})), json!({"mode": "ANY"}));
+        assert_eq!(tool_config(json!({"type": "none"})), json!({"mode": "NONE"}));
+        assert_eq!(tool_config(json!({"type": "auto", "disable_parallel_tool_use": true})), json!({"mode": "AUTO"}));
+
+        let req = request(json!({"type": "tool", "name": "extract_invoice", "disable_parallel_tool_use": true}));
+        assert!(req.tool_choice.as_ref().unwrap().disable_parallel_tool_use());
+
+        // 未知工具名直接报错
+        let unknown = request(json!({"type": "tool", "name": "missing"}));
+        assert!(validate_tool_choice(&unknown).unwrap_err().contains("missing"));
+        assert!(transform_claude_request_in(&unknown, "test-project").is_err());
+    }
+
+    #[test]
+    fn test_tool_result_attachments() {
+        let request = |model: &str| -> ClaudeRequest {
+            serde_json::from_value(json!({
+                "model": model,
+                "messages": [
+                    {"role": "user", "content": "Open the page"},
+                    {"role": "assistant", "content": [
+                        {"type": "tool_use", "id": "toolu_1", "name": "screenshot", "input": {}}
+                    ]},
+                    {"role": "user", "content": [
+                        {"type": "tool_result", "tool_use_id": "toolu_1", "content": [
+                            {"type": "text", "text": "Captured"},
+                            {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}},
+                            {"type": "document", "source": {"type": "base64", "media_type": "application/pdf", "data": "JVBERi0="}}
+                        ]},
+                        {"type": "text", "text": "What do you see?"}
+                    ]}
+                ]
+            }))
+            .unwrap()
+        };
+
+        // Gemini 3: 附件嵌入 functionResponse.parts，并在 response 中按顺序 $ref 引用
+        let body = transform_claude_request_in(&request("gemini-3-pro-high"), "test-project").unwrap();
+        let parts = &body["request"]["contents"][2]["parts"];
+        let response = &parts[0]["functionResponse"];
+        assert_eq!(response["response"]["result"], "Captured");
+        assert_eq!(
+            response["response"]["attachments"],
+            json!([{"$ref": "toolu_1_1.png"}, {"$ref": "toolu_1_2.pdf"}])
+        );
+        assert_eq!(response["parts"][0]["inlineData"]["displayName"], "toolu_1_1.png");
+        assert_eq!(response["parts"][1]["inlineData"]["mimeType"], "application/pdf");
+        assert_eq!(parts[1]["text"], "What do you see?");
+        assert_eq!(parts.as_array().unwrap().len(), 2);
+
+        // 其他模型: 附件以带标注的 inlineData 紧跟在 functionResponse 之后，用户文本排在最后
+        let body = transform_claude_request_in(&request("gemini-2.5-flash"), "test-project").unwrap();
+        let parts = body["request"]["contents"][2]["parts"].as_array().unwrap().clone();
+        assert!(parts[0]["functionResponse"].get("parts").is_none());
+        assert_eq!(parts[0]["functionResponse"]["response"]["attachments"], json!(["toolu_1_1.png", "toolu_1_2.pdf"]));
+        assert_eq!(parts[1]["text"], "[Attachment toolu_1_1.png]");
+        assert_eq!(parts[2]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(parts[3]["text"], "[Attachment toolu_1_2.pdf]");
+        assert_eq!(parts[4]["inlineData"]["data"], "JVBERi0=");
+        assert_eq!(parts[5]["text"], "What do you see?");
+        assert_eq!(parts.len(), 6);
+    }
+
+    #[test]
+    fn test_tool_result_attachments_follow_their_function_response() {
+        let request: ClaudeRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [
+                {"role": "user", "content": "Capture both pages"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_a", "name": "screenshot", "input": {}},
+                    {"type": "tool_use", "id": "toolu_b", "name": "screenshot", "input": {}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_a", "content": [
+                        {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "QQ=="}}
+                    ]},
+                    {"type": "text", "text": "Compare them"},
+                    {"type": "tool_result", "tool_use_id": "toolu_b", "content": [
+                        {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "Qg=="}}
+                    ]}
+                ]}
+            ]
+        }))
+        .unwrap();
+
+        let body = transform_claude_request_in(&request, "test-project").unwrap();
+        let parts = body["request"]["contents"][2]["parts"].as_array().unwrap().clone();
+        assert_eq!(parts[0]["functionResponse"]["id"], "toolu_a");
+        assert_eq!(parts[1]["text"], "[Attachment toolu_a_1.png]");
+        assert_eq!(parts[2]["inlineData"]["data"], "QQ==");
+        assert_eq!(parts[3]["text"], "Compare them");
+        assert_eq!(parts[4]["functionResponse"]["id"], "toolu_b");
+        assert_eq!(parts[5]["text"], "[Attachment toolu_b_1.png]");
+        assert_eq!(parts[6]["inlineData"]["data"], "Qg==");
+    }
+
+    #[test]
+    fn test_tool_result_image_only() {
+        let content = json!([
+            {"type": "image", "source": {"type": "base64", "media_type": "image/jpeg", "data": "/9j/"}}
+        ]);
+        let (text, attachments) = split_tool_result_content("toolu_9", &content);
+        assert!(text.is_empty());
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].name, "toolu_9_1.jpeg");
+
+        let (text, attachments) = split_tool_result_content("toolu_9", &json!("plain output"));
+        assert_eq!(text, "plain output");
+        assert!(attachments.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_claude_config_final_model() {
+        let config = resolve_claude_config("claude-sonnet-4-5", &None);
+        assert_eq!(config.final_model, "claude-sonnet-4-5");
+
+        // 联网工具会改用 flash，白名单须按此模型校验
+        let tools: Vec<Tool> = serde_json::from_value(json!([{"type": "web_search_20250305", "name": "web_search"}])).unwrap();
+        let config = resolve_claude_config("claude-sonnet-4-5", &Some(tools));
+        assert_eq!(config.final_model, "gemini-2.5-flash");
+        assert_eq!(config.request_type, "web_search");
+    }
 }
@@ -2,7 +2,7 @@
 // 对应 NonStreamingProcessor
 
 use super::models::*;
-use super::utils::to_claude_usage;
+use super::utils::{find_stop_sequence, to_claude_usage};
 
 /// Known parameter remappings for Gemini → Claude compatibility
 /// [FIX] Gemini sometimes uses different parameter names than specified in tool schema
@@ -49,6 +49,10 @@ pub struct NonStreamingProcessor {
     thinking_signature: Option<String>,
     trailing_signature: Option<String>,
     has_tool_call: bool,
+    /// 客户端 stop_sequences，用于本地截断
+    stop_sequences: Vec<String>,
+    /// disable_parallel_tool_use: 只保留首个 tool_use
+    single_tool_use: bool,
 }
 
 impl NonStreamingProcessor {
@@ -60,9 +64,21 @@ impl NonStreamingProcessor {
             thinking_signature: None,
             trailing_signature: None,
             has_tool_call: false,
+            stop_sequences: Vec::new(),
+            single_tool_use: false,
         }
     }
 
+    pub fn with_stop_sequences(mut self, stop_sequences: &[String]) -> Self {
+        self.stop_sequences = stop_sequences.to_vec();
+        self
+    }
+
+    pub fn with_single_tool_use(mut self, single_tool_use: bool) -> Self {
+        self.single_tool_use = single_tool_use;
+        self
+    }
+
     /// 处理 Gemini 响应并转换为 Claude 响应
     pub fn process(&mut self, gemini_response: &GeminiResponse) -> ClaudeResponse {
         // 获取 parts
@@ -101,7 +117,28 @@ impl NonStreamingProcessor {
         }
 
         // 构建响应
-        self.build_response(gemini_response)
+        let stop_sequence = self.apply_stop_sequences();
+        self.build_response(gemini_response, stop_sequence)
+    }
+
+    /// 在首个命中的停止序列处截断正文，丢弃其后的所有块
+    fn apply_stop_sequences(&mut self) -> Option<String> {
+        let (index, pos, seq) = self.content_blocks.iter().enumerate().find_map(|(i, block)| match block {
+            ContentBlock::Text { text } => {
+                find_stop_sequence(text, &self.stop_sequences).map(|(pos, seq)| (i, pos, seq.to_string()))
+            }
+            _ => None,
+        })?;
+
+        self.content_blocks.truncate(index + 1);
+        if let Some(ContentBlock::Text { text }) = self.content_blocks.last_mut() {
+            text.truncate(pos);
+            if text.is_empty() {
+                self.content_blocks.pop();
+            }
+        }
+        self.has_tool_call = self.content_blocks.iter().any(|b| matches!(b, ContentBlock::ToolUse { .. }));
+        Some(seq)
     }
 
     /// 处理单个 part
@@ -110,6 +147,11 @@ impl NonStreamingProcessor {
 
         // 1. FunctionCall 处理
         if let Some(fc) = &part.function_call {
+            // Gemini 没有关闭并行调用的开关，多余的调用在此丢弃
+            if self.single_tool_use && self.has_tool_call {
+                tracing::debug!("[Claude-Response] Dropping parallel tool call '{}'", fc.name);
+                return;
+            }
             self.flush_thinking();
             self.flush_text();
 
@@ -293,7 +335,7 @@ impl NonStreamingProcessor {
     }
 
     /// 构建最终响应
-    fn build_response(&self, gemini_response: &GeminiResponse) -> ClaudeResponse {
+    fn build_response(&self, gemini_response: &GeminiResponse, stop_sequence: Option<String>) -> ClaudeResponse {
         let finish_reason = gemini_response
             .candidates
             .as_ref()
@@ -302,6 +344,8 @@ impl NonStreamingProcessor {
 
         let stop_reason = if self.has_tool_call {
             "tool_use"
+        } else if stop_sequence.is_some() {
+            "stop_sequence"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
         } else {
@@ -329,15 +373,21 @@ impl NonStreamingProcessor {
             model: gemini_response.model_version.clone().unwrap_or_default(),
             content: self.content_blocks.clone(),
             stop_reason: stop_reason.to_string(),
-            stop_sequence: None,
+            stop_sequence,
             usage,
         }
     }
 }
 
 /// 转换 Gemini 响应为 Claude 响应 (公共接口)
-pub fn transform_response(gemini_response: &GeminiResponse) -> Result<ClaudeResponse, String> {
-    let mut processor = NonStreamingProcessor::new();
+pub fn transform_response(
+    gemini_response: &GeminiResponse,
+    stop_sequences: &[String],
+    single_tool_use: bool,
+) -> Result<ClaudeResponse, String> {
+    let mut processor = NonStreamingProcessor::new()
+        .with_stop_sequences(stop_sequences)
+        .with_single_tool_use(single_tool_use);
     Ok(processor.process(gemini_response))
 }
 
@@ -369,12 +419,13 @@ mod tests {
                 candidates_token_count: Some(5),
                 total_token_count: Some(15),
                 cached_content_token_count: None,
+                thoughts_token_count: None,
             }),
             model_version: Some("gemini-2.5-pro".to_string()),
             response_id: Some("resp_123".to_string()),
         };
 
-        let result = transform_response(&gemini_resp);
+        let result = transform_response(&gemini_resp, &[], false);
         assert!(result.is_ok());
 
         let claude_resp = result.unwrap();
@@ -424,7 +475,7 @@ mod tests {
             response_id: Some("resp_456".to_string()),
         };
 
-        let result = transform_response(&gemini_resp);
+        let result = transform_response(&gemini_resp, &[], false);
         assert!(result.is_ok());
 
         let claude_resp = result.unwrap();
@@ -449,4 +500,66 @@ mod tests {
             _ => panic!("Expected Text block"),
         }
     }
+
+    #[test]
+    fn test_stop_sequence_truncates_response() {
+        let gemini_resp: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "Answer: 42\nQ: next question"},
+                    {"functionCall": {"name": "lookup", "args": {}}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let claude_resp = transform_response(&gemini_resp, &["\nQ:".to_string()], false).unwrap();
+        assert_eq!(claude_resp.stop_reason, "stop_sequence");
+        assert_eq!(claude_resp.stop_sequence.as_deref(), Some("\nQ:"));
+        assert_eq!(claude_resp.content.len(), 1);
+        assert!(matches!(&claude_resp.content[0], ContentBlock::Text { text } if text == "Answer: 42"));
+
+        let untouched = transform_response(&gemini_resp, &[], false).unwrap();
+        assert_eq!(untouched.stop_reason, "tool_use");
+        assert!(untouched.stop_sequence.is_none());
+    }
+
+    #[test]
+    fn test_stop_sequence_removed_upstream() {
+        // 上游命中默认停止序列时已将其删去，只返回 STOP: 不能误报为客户端序列
+        let gemini_resp: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Answer: 42"}]},
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let claude_resp = transform_response(&gemini_resp, &["\nQ:".to_string()], false).unwrap();
+        assert_eq!(claude_resp.stop_reason, "end_turn");
+        assert!(claude_resp.stop_sequence.is_none());
+        assert!(matches!(&claude_resp.content[0], ContentBlock::Text { text } if text == "Answer: 42"));
+    }
+
+    #[test]
+    fn test_disable_parallel_tool_use() {
+        let gemini_resp: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"functionCall": {"name": "lookup", "args": {"q": "a"}}},
+                    {"functionCall": {"name": "lookup", "args": {"q": "b"}}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let single = transform_response(&gemini_resp, &[], true).unwrap();
+        assert_eq!(single.content.len(), 1);
+        assert_eq!(single.stop_reason, "tool_use");
+
+        let parallel = transform_response(&gemini_resp, &[], false).unwrap();
+        assert_eq!(parallel.content.len(), 2);
+    }
 }
@@ -2,7 +2,7 @@
 // 对应 StreamingState + PartProcessor
 
 use super::models::*;
-use super::utils::to_claude_usage;
+use super::utils::{to_claude_usage, StopSequenceMatcher};
 use crate::proxy::mappers::signature_store::store_thought_signature;
 use bytes::Bytes;
 use serde_json::json;
@@ -89,9 +89,15 @@ pub struct StreamingState {
     trailing_signature: Option<String>,
     pub web_search_query: Option<String>,
     pub grounding_chunks: Option<Vec<serde_json::Value>>,
+    /// 最终的 usageMetadata (流结束时记录，用于用量统计)
+    pub final_usage: Option<UsageMetadata>,
     // [IMPROVED] Error recovery 状态追踪
     parse_error_count: usize,
     last_valid_state: Option<BlockType>,
+    /// 客户端 stop_sequences 的本地匹配
+    stop_matcher: StopSequenceMatcher,
+    /// disable_parallel_tool_use: 只输出首个 tool_use
+    single_tool_use: bool,
 }
 
 impl StreamingState {
@@ -106,12 +112,44 @@ impl StreamingState {
             trailing_signature: None,
             web_search_query: None,
             grounding_chunks: None,
+            final_usage: None,
             // [IMPROVED] 初始化 error recovery 字段
             parse_error_count: 0,
             last_valid_state: None,
+            stop_matcher: StopSequenceMatcher::default(),
+            single_tool_use: false,
         }
     }
 
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.stop_matcher = StopSequenceMatcher::new(stop_sequences);
+        self
+    }
+
+    pub fn with_single_tool_use(mut self, single_tool_use: bool) -> Self {
+        self.single_tool_use = single_tool_use;
+        self
+    }
+
+    /// 是否已命中停止序列 (之后的内容全部丢弃)
+    pub fn stop_sequence_matched(&self) -> bool {
+        self.stop_matcher.matched().is_some()
+    }
+
+    /// 输出因可能是停止序列前缀而暂缓的文本
+    pub fn flush_pending_text(&mut self) -> Vec<Bytes> {
+        let text = self.stop_matcher.flush();
+        if text.is_empty() {
+            return vec![];
+        }
+        let mut chunks = Vec::new();
+        if self.block_type != BlockType::Text {
+            chunks.extend(self.start_block(BlockType::Text, json!({ "type": "text", "text": "" })));
+        }
+        chunks.push(self.emit_delta("text_delta", json!({ "text": text })));
+        chunks
+    }
+
     /// 发送 SSE 事件
     pub fn emit(&self, event_type: &str, data: serde_json::Value) -> Bytes {
         let sse = format!(
@@ -241,7 +279,7 @@ impl StreamingState {
         finish_reason: Option<&str>,
         usage_metadata: Option<&UsageMetadata>,
     ) -> Vec<Bytes> {
-        let mut chunks = Vec::new();
+        let mut chunks = self.flush_pending_text();
 
         // 关闭最后一个块
         chunks.extend(self.end_block());
@@ -311,13 +349,21 @@ impl StreamingState {
         }
 
         // 确定 stop_reason
+        let stop_sequence = self.stop_matcher.matched();
         let stop_reason = if self.used_tool {
             "tool_use"
+        } else if stop_sequence.is_some() {
+            "stop_sequence"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
         } else {
             "end_turn"
         };
+        let stop_sequence = stop_sequence.map(str::to_string);
+
+        if let Some(u) = usage_metadata {
+            self.final_usage = Some(u.clone());
+        }
 
         let usage = usage_metadata
             .map(|u| to_claude_usage(u))
@@ -333,7 +379,7 @@ impl StreamingState {
             "message_delta",
             json!({
                 "type": "message_delta",
-                "delta": { "stop_reason": stop_reason, "stop_sequence": null },
+                "delta": { "stop_reason": stop_reason, "stop_sequence": stop_sequence },
                 "usage": usage
             }),
         ));
@@ -450,8 +496,23 @@ impl<'a> PartProcessor<'a> {
         let mut chunks = Vec::new();
         let signature = part.thought_signature.clone();
 
+        // 命中停止序列后不再输出任何内容
+        if self.state.stop_sequence_matched() {
+            return chunks;
+        }
+        // 非正文内容前先放出暂缓的文本，保持顺序
+        let is_plain_text = part.text.is_some() && !part.thought.unwrap_or(false);
+        if !is_plain_text {
+            chunks.extend(self.state.flush_pending_text());
+        }
+
         // 1. FunctionCall 处理
         if let Some(fc) = &part.function_call {
+            // Gemini 没有关闭并行调用的开关，多余的调用在此丢弃
+            if self.state.single_tool_use && self.state.used_tool {
+                tracing::debug!("[Claude-SSE] Dropping parallel tool call '{}'", fc.name);
+                return chunks;
+            }
             // 先处理 trailingSignature (B4/C3 场景)
             if self.state.has_trailing_signature() {
                 chunks.extend(self.state.end_block());
@@ -575,6 +636,16 @@ impl<'a> PartProcessor<'a> {
             return chunks;
         }
 
+        // 停止序列匹配: 可能只放出部分文本
+        let visible = self.state.stop_matcher.push(text);
+        if visible.is_empty() {
+            if signature.is_some() {
+                self.state.set_trailing_signature(signature);
+            }
+            return chunks;
+        }
+        let text = visible.as_str();
+
         // 处理之前的 trailingSignature
         if self.state.has_trailing_signature() {
             chunks.extend(self.state.end_block());
@@ -772,4 +843,32 @@ mod tests {
         // 3. content_block_stop
         assert!(output.contains(r#""type":"content_block_stop""#));
     }
+
+    #[test]
+    fn test_stop_sequence_across_chunks() {
+        let mut state = StreamingState::new().with_stop_sequences(vec!["STOP!".to_string()]);
+        let text_part = |text: &str| GeminiPart {
+            text: Some(text.to_string()),
+            function_call: None,
+            inline_data: None,
+            thought: None,
+            thought_signature: None,
+            function_response: None,
+        };
+
+        let mut output = Vec::new();
+        for text in ["Done. ST", "OP! ignored", "also ignored"] {
+            output.extend(PartProcessor::new(&mut state).process(&text_part(text)));
+        }
+        output.extend(state.emit_finish(Some("STOP"), None));
+        let output = output
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap())
+            .collect::<Vec<_>>()
+            .join("");
+
+        assert!(output.contains(r#""text":"Done. ""#));
+        assert!(!output.contains("ignored"));
+        assert!(output.contains(r#""stop_reason":"stop_sequence","stop_sequence":"STOP!""#));
+    }
 }
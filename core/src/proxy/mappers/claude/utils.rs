@@ -23,6 +23,81 @@ pub fn to_claude_usage(usage_metadata: &super::models::UsageMetadata) -> super::
     }
 }
 
+/// 查找最早出现的停止序列，返回 (字节位置, 序列)
+pub fn find_stop_sequence<'a>(text: &str, stop_sequences: &'a [String]) -> Option<(usize, &'a str)> {
+    stop_sequences
+        .iter()
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| text.find(s.as_str()).map(|pos| (pos, s.as_str())))
+        .min_by_key(|(pos, _)| *pos)
+}
+
+/// 客户端 stop_sequences 的本地匹配 (流式)
+///
+/// 客户端序列会下发上游，但 Gemini 单次最多接受 5 个，超出部分只能由这里截断；
+/// 输出中出现的序列 (上游未删去或未下发) 在这里匹配、截断并回报。
+/// 可能是序列前缀的尾部文本会暂缓输出，直到能够确定。
+#[derive(Debug, Default)]
+pub struct StopSequenceMatcher {
+    stop_sequences: Vec<String>,
+    pending: String,
+    matched: Option<String>,
+}
+
+impl StopSequenceMatcher {
+    pub fn new(stop_sequences: Vec<String>) -> Self {
+        Self {
+            stop_sequences: stop_sequences.into_iter().filter(|s| !s.is_empty()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// 已命中的停止序列
+    pub fn matched(&self) -> Option<&str> {
+        self.matched.as_deref()
+    }
+
+    /// 输入一段文本，返回可以立即输出的部分；命中后截断，之后的输入全部丢弃
+    pub fn push(&mut self, text: &str) -> String {
+        if self.matched.is_some() {
+            return String::new();
+        }
+        if self.stop_sequences.is_empty() {
+            return text.to_string();
+        }
+
+        self.pending.push_str(text);
+        if let Some((pos, seq)) = find_stop_sequence(&self.pending, &self.stop_sequences) {
+            self.matched = Some(seq.to_string());
+            let out = self.pending[..pos].to_string();
+            self.pending.clear();
+            return out;
+        }
+
+        let hold = self
+            .stop_sequences
+            .iter()
+            .map(|s| partial_match_len(&self.pending, s))
+            .max()
+            .unwrap_or(0);
+        let ready = self.pending.len() - hold;
+        self.pending.drain(..ready).collect()
+    }
+
+    /// 取出暂缓的文本 (流结束或插入非文本内容前调用)
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// text 结尾与 seq 某个真前缀重合的最大长度 (字节)
+fn partial_match_len(text: &str, seq: &str) -> usize {
+    (1..seq.len())
+        .rev()
+        .find(|&len| seq.is_char_boundary(len) && text.ends_with(&seq[..len]))
+        .unwrap_or(0)
+}
+
 /// 提取 thoughtSignature
 // 已移除未使用的 extract_thought_signature 函数
 
@@ -42,10 +117,35 @@ mod tests {
             candidates_token_count: Some(50),
             total_token_count: Some(150),
             cached_content_token_count: None,
+            thoughts_token_count: None,
         };
 
         let claude_usage = to_claude_usage(&usage);
         assert_eq!(claude_usage.input_tokens, 100);
         assert_eq!(claude_usage.output_tokens, 50);
     }
+
+    #[test]
+    fn test_stop_sequence_matcher() {
+        let stops = vec!["END".to_string(), "\n\nQ:".to_string()];
+        assert_eq!(find_stop_sequence("a\n\nQ: b END", &stops), Some((1, "\n\nQ:")));
+
+        // 跨增量的序列: 前缀先暂缓，命中后截断
+        let mut matcher = StopSequenceMatcher::new(stops.clone());
+        assert_eq!(matcher.push("Hello E"), "Hello ");
+        assert_eq!(matcher.push("NDING"), "");
+        assert_eq!(matcher.matched(), Some("END"));
+        assert_eq!(matcher.push("more"), "");
+
+        // 前缀未命中时原样放出
+        let mut matcher = StopSequenceMatcher::new(stops);
+        assert_eq!(matcher.push("abc E"), "abc ");
+        assert_eq!(matcher.push("Xyz"), "EXyz");
+        assert_eq!(matcher.push("tail\n"), "tail");
+        assert_eq!(matcher.flush(), "\n");
+        assert!(matcher.matched().is_none());
+
+        let mut passthrough = StopSequenceMatcher::new(Vec::new());
+        assert_eq!(passthrough.push("END"), "END");
+    }
 }
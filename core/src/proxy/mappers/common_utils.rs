@@ -171,6 +171,11 @@ pub fn deep_clean_undefined(value: &mut Value) {
     }
 }
 
+/// Whether a function/tool name asks for web search rather than a local function
+pub fn is_networking_tool_name(name: &str) -> bool {
+    matches!(name, "web_search" | "google_search" | "web_search_20250305" | "google_search_retrieval")
+}
+
 /// Detects if the tool list contains a request for networking/web search.
 /// Supported keywords: "web_search", "google_search", "web_search_20250305"
 pub fn detects_networking_tool(tools: &Option<Vec<Value>>) -> bool {
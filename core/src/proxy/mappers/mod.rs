@@ -1,6 +1,5 @@
 // Mappers module
 pub mod claude;
 pub mod common_utils;
+pub mod openai;
 pub mod signature_store;
-pub mod openai_to_gemini;
-pub mod gemini_to_openai;
@@ -0,0 +1,143 @@
+//! OpenAI embeddings ↔ Gemini batchEmbedContents conversion
+
+use base64::Engine;
+use serde_json::{json, Value};
+
+/// Embedding model used when the requested name maps to nothing Gemini knows
+pub const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+
+/// Texts per upstream `batchEmbedContents` call (Gemini limit)
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Whether `model` names a Gemini embedding model that can be sent as-is
+pub fn is_gemini_embedding_model(model: &str) -> bool {
+    let model = model.trim_start_matches("models/");
+    model.starts_with("gemini-embedding-") || model.starts_with("text-embedding-00") || model.starts_with("embedding-")
+}
+
+/// Texts of an OpenAI `input` (a string or an array of strings)
+pub fn embedding_inputs(body: &Value) -> Result<Vec<String>, String> {
+    let inputs = match body.get("input") {
+        Some(Value::String(text)) => vec![text.clone()],
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| "Token array inputs are not supported; send strings".to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err("'input' must be a string or an array of strings".to_string()),
+        None => return Err("'input' is required".to_string()),
+    };
+    if inputs.is_empty() {
+        return Err("'input' must not be empty".to_string());
+    }
+    if inputs.iter().any(|s| s.is_empty()) {
+        return Err("'input' must not contain empty strings".to_string());
+    }
+    Ok(inputs)
+}
+
+/// Gemini `batchEmbedContents` request for one batch of texts
+pub fn build_batch_request(gemini_model: &str, texts: &[String], dimensions: Option<u64>) -> Value {
+    let model = format!("models/{}", gemini_model.trim_start_matches("models/"));
+    let requests: Vec<Value> = texts
+        .iter()
+        .map(|text| {
+            let mut request = json!({
+                "model": model,
+                "content": { "parts": [{ "text": text }] }
+            });
+            if let Some(dimensions) = dimensions {
+                request["outputDimensionality"] = json!(dimensions);
+            }
+            request
+        })
+        .collect();
+    json!({ "requests": requests })
+}
+
+/// Vectors of a `batchEmbedContents` response, in request order
+pub fn batch_embeddings(response: &Value) -> Result<Vec<Vec<f64>>, String> {
+    let embeddings = response
+        .get("embeddings")
+        .and_then(|v| v.as_array())
+        .ok_or("Upstream response has no 'embeddings'")?;
+    embeddings
+        .iter()
+        .map(|e| {
+            e.get("values")
+                .and_then(|v| v.as_array())
+                .map(|values| values.iter().filter_map(|v| v.as_f64()).collect())
+                .ok_or_else(|| "Upstream embedding has no 'values'".to_string())
+        })
+        .collect()
+}
+
+/// Rough token count for usage reporting; Gemini does not return one for embeddings
+pub fn estimate_tokens(texts: &[String]) -> u32 {
+    texts.iter().map(|t| t.chars().count().div_ceil(4) as u32).sum()
+}
+
+/// OpenAI embeddings list response
+pub fn to_openai_embeddings(vectors: Vec<Vec<f64>>, model: &str, base64: bool, prompt_tokens: u32) -> Value {
+    let data: Vec<Value> = vectors
+        .into_iter()
+        .enumerate()
+        .map(|(index, vector)| {
+            let embedding = if base64 {
+                // OpenAI base64 embeddings are little-endian float32
+                let bytes: Vec<u8> = vector.iter().flat_map(|v| (*v as f32).to_le_bytes()).collect();
+                json!(base64::engine::general_purpose::STANDARD.encode(bytes))
+            } else {
+                json!(vector)
+            };
+            json!({ "object": "embedding", "index": index, "embedding": embedding })
+        })
+        .collect();
+    json!({
+        "object": "list",
+        "data": data,
+        "model": model,
+        "usage": { "prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_inputs() {
+        assert_eq!(embedding_inputs(&json!({"input": "hi"})).unwrap(), vec!["hi"]);
+        assert_eq!(embedding_inputs(&json!({"input": ["a", "b"]})).unwrap().len(), 2);
+        assert!(embedding_inputs(&json!({"input": [[1, 2, 3]]})).is_err());
+        assert!(embedding_inputs(&json!({"input": []})).is_err());
+        assert!(embedding_inputs(&json!({})).is_err());
+    }
+
+    #[test]
+    fn test_batch_request_and_response() {
+        let texts = vec!["a".to_string(), "b".to_string()];
+        let request = build_batch_request("text-embedding-004", &texts, Some(256));
+        assert_eq!(request["requests"][1]["model"], "models/text-embedding-004");
+        assert_eq!(request["requests"][1]["content"]["parts"][0]["text"], "b");
+        assert_eq!(request["requests"][0]["outputDimensionality"], 256);
+
+        let upstream = json!({"embeddings": [{"values": [0.5, -1.0]}, {"values": [0.25, 2.0]}]});
+        let vectors = batch_embeddings(&upstream).unwrap();
+        let response = to_openai_embeddings(vectors, "text-embedding-3-small", false, 2);
+        assert_eq!(response["data"][1]["index"], 1);
+        assert_eq!(response["data"][1]["embedding"][0], 0.25);
+        assert_eq!(response["usage"]["prompt_tokens"], 2);
+    }
+
+    #[test]
+    fn test_base64_encoding_is_float32_le() {
+        let response = to_openai_embeddings(vec![vec![1.0]], "m", true, 1);
+        let encoded = response["data"][0]["embedding"].as_str().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+        assert_eq!(bytes, 1.0f32.to_le_bytes());
+    }
+}
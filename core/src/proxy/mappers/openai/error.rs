@@ -0,0 +1,289 @@
+//! OpenAI error bodies (`{"error": {message, type, param, code}}`)
+//! Upstream Google errors are mapped by their `status` so SDK retry logic sees
+//! the error types it knows.
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use serde_json::{json, Value};
+
+use super::request::OpenAIRequestError;
+use crate::proxy::upstream::dispatcher::DispatchError;
+use crate::proxy::upstream::retry::parse_retry_delay;
+
+/// Error returned by the OpenAI-compatible routes
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum OpenAIError {
+    /// 400 `invalid_request_error`
+    #[error("{message}")]
+    InvalidRequest { message: String, param: Option<String>, code: Option<String> },
+    /// 401 `authentication_error`
+    #[error("{0}")]
+    Authentication(String),
+    /// 403 `permission_error`
+    #[error("{0}")]
+    PermissionDenied(String),
+    /// 404 `invalid_request_error`
+    #[error("{message}")]
+    NotFound { message: String, param: Option<String>, code: Option<String> },
+    /// 429 `rate_limit_exceeded`
+    #[error("{message}")]
+    RateLimit { message: String, retry_after: Option<u64> },
+    /// 503 `server_error` (no usable account, upstream overloaded)
+    #[error("{message}")]
+    Unavailable { message: String, retry_after: Option<u64> },
+    /// 504 `timeout_error`
+    #[error("{0}")]
+    Timeout(String),
+    /// Any other upstream or proxy failure (`server_error`)
+    #[error("{message}")]
+    Server { status: u16, message: String, param: Option<String>, code: Option<String> },
+}
+
+impl OpenAIError {
+    pub fn invalid_request(message: impl Into<String>, param: Option<&str>, code: Option<&str>) -> Self {
+        OpenAIError::InvalidRequest {
+            message: message.into(),
+            param: param.map(str::to_string),
+            code: code.map(str::to_string),
+        }
+    }
+
+    /// 502 for an upstream response the proxy could not use
+    pub fn bad_gateway(message: impl Into<String>) -> Self {
+        OpenAIError::Server { status: 502, message: message.into(), param: None, code: None }
+    }
+
+    /// Strict `json_schema` output that does not conform
+    pub fn schema_violation(message: impl Into<String>) -> Self {
+        OpenAIError::Server {
+            status: 502,
+            message: message.into(),
+            param: Some("response_format".to_string()),
+            code: Some("json_schema_validation_failed".to_string()),
+        }
+    }
+
+    /// Map an upstream error response by its Google `status`, falling back to the HTTP status
+    pub fn from_upstream(status: u16, body: &str, retry_after_header: Option<&str>) -> Self {
+        let parsed: Option<Value> = serde_json::from_str(body).ok();
+        // The upstream sometimes wraps the error in an array
+        let error = parsed.as_ref().map(|v| match v {
+            Value::Array(items) => items.first().unwrap_or(v),
+            other => other,
+        });
+        let error = error.and_then(|v| v.get("error"));
+        let message = error
+            .and_then(|e| e.get("message"))
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                if body.is_empty() {
+                    format!("Upstream returned HTTP {}", status)
+                } else {
+                    body.chars().take(500).collect()
+                }
+            });
+        let google_status = error.and_then(|e| e.get("status")).and_then(|s| s.as_str()).unwrap_or("");
+        let retry_after = retry_after_header
+            .and_then(|h| h.trim().parse::<u64>().ok())
+            .or_else(|| parse_retry_delay(body).map(|ms| ms.div_ceil(1000).max(1)));
+
+        match (google_status, status) {
+            ("INVALID_ARGUMENT" | "FAILED_PRECONDITION" | "OUT_OF_RANGE", _) | ("", 400) => {
+                OpenAIError::InvalidRequest { message, param: None, code: None }
+            }
+            ("UNAUTHENTICATED", _) | ("", 401) => OpenAIError::Authentication(message),
+            ("PERMISSION_DENIED", _) | ("", 403) => OpenAIError::PermissionDenied(message),
+            ("NOT_FOUND", _) | ("", 404) => OpenAIError::NotFound { message, param: None, code: None },
+            ("RESOURCE_EXHAUSTED", _) | ("", 429) => OpenAIError::RateLimit { message, retry_after },
+            ("UNAVAILABLE", _) | ("", 503) => OpenAIError::Unavailable { message, retry_after },
+            ("DEADLINE_EXCEEDED", _) | ("", 504) => OpenAIError::Timeout(message),
+            _ => OpenAIError::Server {
+                status: if status >= 500 { status } else { 502 },
+                message,
+                param: None,
+                code: None,
+            },
+        }
+    }
+
+    /// Error carried inside an upstream SSE event (`data: {"error": {...}}`)
+    pub fn from_stream_event(event: &Value) -> Option<Self> {
+        let error = event.get("error")?;
+        let status = error.get("code").and_then(|c| c.as_u64()).unwrap_or(500) as u16;
+        Some(Self::from_upstream(status, &event.to_string(), None))
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            OpenAIError::InvalidRequest { .. } => StatusCode::BAD_REQUEST,
+            OpenAIError::Authentication(_) => StatusCode::UNAUTHORIZED,
+            OpenAIError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            OpenAIError::NotFound { .. } => StatusCode::NOT_FOUND,
+            OpenAIError::RateLimit { .. } => StatusCode::TOO_MANY_REQUESTS,
+            OpenAIError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            OpenAIError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            OpenAIError::Server { status, .. } => StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY),
+        }
+    }
+
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            OpenAIError::InvalidRequest { .. } | OpenAIError::NotFound { .. } => "invalid_request_error",
+            OpenAIError::Authentication(_) => "authentication_error",
+            OpenAIError::PermissionDenied(_) => "permission_error",
+            OpenAIError::RateLimit { .. } => "rate_limit_exceeded",
+            OpenAIError::Timeout(_) => "timeout_error",
+            OpenAIError::Unavailable { .. } | OpenAIError::Server { .. } => "server_error",
+        }
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            OpenAIError::InvalidRequest { code, .. }
+            | OpenAIError::NotFound { code, .. }
+            | OpenAIError::Server { code, .. } => code.as_deref(),
+            OpenAIError::RateLimit { .. } => Some("rate_limit_exceeded"),
+            OpenAIError::Unavailable { .. } => Some("service_unavailable"),
+            OpenAIError::Timeout(_) => Some("timeout"),
+            OpenAIError::Authentication(_) | OpenAIError::PermissionDenied(_) => None,
+        }
+    }
+
+    pub fn param(&self) -> Option<&str> {
+        match self {
+            OpenAIError::InvalidRequest { param, .. }
+            | OpenAIError::NotFound { param, .. }
+            | OpenAIError::Server { param, .. } => param.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Seconds the client should wait before retrying
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            OpenAIError::RateLimit { retry_after, .. } | OpenAIError::Unavailable { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    pub fn to_body(&self) -> Value {
+        json!({
+            "error": {
+                "message": self.to_string(),
+                "type": self.error_type(),
+                "param": self.param(),
+                "code": self.code()
+            }
+        })
+    }
+
+    /// Terminal SSE chunk for an error after the stream has started
+    pub fn sse_chunk(&self) -> Bytes {
+        Bytes::from(format!("data: {}\n\n", self.to_body()))
+    }
+}
+
+impl IntoResponse for OpenAIError {
+    fn into_response(self) -> Response {
+        if self.status().is_server_error() {
+            tracing::error!("[OpenAI] {} {}: {}", self.status().as_u16(), self.error_type(), self);
+        }
+        let mut resp = (self.status(), Json(self.to_body())).into_response();
+        if let Some(secs) = self.retry_after() {
+            resp.headers_mut().insert(header::RETRY_AFTER, secs.into());
+        }
+        resp
+    }
+}
+
+impl From<DispatchError> for OpenAIError {
+    fn from(error: DispatchError) -> Self {
+        match error {
+            DispatchError::Upstream { status, body, retry_after } => {
+                Self::from_upstream(status, &body, retry_after.as_deref())
+            }
+            DispatchError::InvalidRequest(message) => OpenAIError::InvalidRequest { message, param: None, code: None },
+            DispatchError::NoAccounts(_) => OpenAIError::Unavailable { message: error.to_string(), retry_after: None },
+            DispatchError::Network(message) => OpenAIError::Server {
+                status: 502,
+                message,
+                param: None,
+                code: Some("upstream_error".to_string()),
+            },
+            DispatchError::Timeout(_) => OpenAIError::Timeout(error.to_string()),
+        }
+    }
+}
+
+impl From<OpenAIRequestError> for OpenAIError {
+    fn from(error: OpenAIRequestError) -> Self {
+        OpenAIError::InvalidRequest {
+            message: error.to_string(),
+            param: Some(error.param),
+            code: Some("invalid_value".to_string()),
+        }
+    }
+}
+
+impl From<JsonRejection> for OpenAIError {
+    fn from(rejection: JsonRejection) -> Self {
+        OpenAIError::InvalidRequest { message: rejection.body_text(), param: None, code: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_google_status_mapping() {
+        let body = r#"{"error": {"code": 429, "message": "Quota exceeded", "status": "RESOURCE_EXHAUSTED",
+            "details": [{"@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "12.5s"}]}}"#;
+        let error = OpenAIError::from_upstream(429, body, None);
+        assert_eq!(error.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(error.error_type(), "rate_limit_exceeded");
+        assert_eq!(error.retry_after(), Some(13));
+        assert_eq!(error.to_body()["error"]["message"], "Quota exceeded");
+
+        let body = r#"[{"error": {"code": 400, "message": "Bad schema", "status": "INVALID_ARGUMENT"}}]"#;
+        let error = OpenAIError::from_upstream(400, body, None);
+        assert_eq!(error.error_type(), "invalid_request_error");
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+
+        // Header wins over the body, plain-text bodies fall back to the HTTP status
+        let error = OpenAIError::from_upstream(503, "overloaded", Some("7"));
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(error.retry_after(), Some(7));
+        assert_eq!(error.to_string(), "overloaded");
+
+        let error = OpenAIError::from_upstream(418, "", None);
+        assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(error.error_type(), "server_error");
+    }
+
+    #[test]
+    fn test_response_has_retry_after_and_openai_body() {
+        let resp = OpenAIError::RateLimit { message: "slow down".to_string(), retry_after: Some(30) }.into_response();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "30");
+
+        let body = OpenAIError::from(OpenAIRequestError::new("temperature", "must be between 0 and 2")).to_body();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["param"], "temperature");
+    }
+
+    #[test]
+    fn test_stream_event_error() {
+        let event = json!({"error": {"code": 429, "message": "Too many", "status": "RESOURCE_EXHAUSTED"}});
+        let error = OpenAIError::from_stream_event(&event).unwrap();
+        let chunk = String::from_utf8(error.sse_chunk().to_vec()).unwrap();
+        assert!(chunk.starts_with("data: "));
+        assert!(chunk.contains("\"rate_limit_exceeded\""));
+        assert!(OpenAIError::from_stream_event(&json!({"response": {}})).is_none());
+    }
+}
@@ -0,0 +1,18 @@
+//! OpenAI mapper
+//! Gemini ↔ OpenAI Chat Completions conversion
+
+pub mod embeddings;
+pub mod error;
+pub mod models;
+pub mod request;
+pub mod response;
+pub mod responses;
+pub mod streaming;
+
+pub use error::OpenAIError;
+pub use models::OpenAIChatRequest;
+pub use request::{
+    parse_chat_request, resolve_openai_config, transform_openai_request, wrap_v1internal, OpenAIRequestError,
+};
+pub use response::convert_chat_response;
+pub use streaming::{create_openai_sse_stream, OpenAIStreamingState};
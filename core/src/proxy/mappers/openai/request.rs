@@ -0,0 +1,809 @@
+//! OpenAI request conversion (Chat Completions → Gemini v1internal)
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use super::models::{
+    OpenAIChatRequest, OpenAIContent, OpenAIContentPart, OpenAIFunction, OpenAIMessage, OpenAIRole, ResponseFormat,
+};
+use crate::proxy::common::json_schema::clean_json_schema;
+use crate::proxy::mappers::common_utils::{
+    inject_google_search_tool, is_networking_tool_name, resolve_request_config, RequestConfig,
+};
+use crate::proxy::mappers::signature_store::get_thought_signature;
+
+/// A request field the proxy cannot accept, reported as an OpenAI `invalid_request_error`
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{param}: {message}")]
+pub struct OpenAIRequestError {
+    /// Path of the offending field, e.g. `messages[2].tool_calls[0].function.arguments`
+    pub param: String,
+    pub message: String,
+}
+
+impl OpenAIRequestError {
+    pub fn new(param: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { param: param.into(), message: message.into() }
+    }
+}
+
+/// Deserialize and validate a chat completions body
+pub fn parse_chat_request(body: &Value) -> Result<OpenAIChatRequest, OpenAIRequestError> {
+    let request: OpenAIChatRequest = serde_path_to_error::deserialize(body).map_err(|e| {
+        let message = e.inner().to_string();
+        let param = match e.path().to_string() {
+            // Missing top-level fields have no path; name the field instead
+            root if root == "." => message
+                .strip_prefix("missing field `")
+                .and_then(|rest| rest.split('`').next())
+                .unwrap_or("body")
+                .to_string(),
+            path => path,
+        };
+        OpenAIRequestError::new(param, message)
+    })?;
+    validate(&request)?;
+    Ok(request)
+}
+
+/// Aspect ratios accepted by the image model
+const IMAGE_ASPECT_RATIOS: [&str; 10] = ["1:1", "2:3", "3:2", "3:4", "4:3", "4:5", "5:4", "9:16", "16:9", "21:9"];
+
+/// Checks serde cannot express
+fn validate(req: &OpenAIChatRequest) -> Result<(), OpenAIRequestError> {
+    if req.model.trim().is_empty() {
+        return Err(OpenAIRequestError::new("model", "must not be empty"));
+    }
+    if !req.messages.iter().any(|m| !matches!(m.role, OpenAIRole::System | OpenAIRole::Developer)) {
+        return Err(OpenAIRequestError::new(
+            "messages",
+            "must contain at least one user, assistant or tool message",
+        ));
+    }
+    if req.temperature.is_some_and(|t| !(0.0..=2.0).contains(&t)) {
+        return Err(OpenAIRequestError::new("temperature", "must be between 0 and 2"));
+    }
+    if req.top_p.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+        return Err(OpenAIRequestError::new("top_p", "must be between 0 and 1"));
+    }
+    if req.n.is_some_and(|n| n != 1) {
+        return Err(OpenAIRequestError::new("n", "only n=1 is supported"));
+    }
+    if let Some(effort) = &req.reasoning_effort {
+        if !matches!(effort.as_str(), "none" | "minimal" | "low" | "medium" | "high") {
+            return Err(OpenAIRequestError::new(
+                "reasoning_effort",
+                format!("unsupported value '{}'; expected none, minimal, low, medium or high", effort),
+            ));
+        }
+    }
+
+    if let Some(image_config) = &req.image_config {
+        if let Some(ratio) = &image_config.aspect_ratio {
+            if !IMAGE_ASPECT_RATIOS.contains(&ratio.as_str()) {
+                return Err(OpenAIRequestError::new(
+                    "image_config.aspect_ratio",
+                    format!("unsupported value '{}'; expected one of {}", ratio, IMAGE_ASPECT_RATIOS.join(", ")),
+                ));
+            }
+        }
+        if let Some(size) = &image_config.image_size {
+            if !matches!(size.to_uppercase().as_str(), "1K" | "2K" | "4K") {
+                return Err(OpenAIRequestError::new(
+                    "image_config.image_size",
+                    format!("unsupported value '{}'; expected 1K, 2K or 4K", size),
+                ));
+            }
+        }
+    }
+
+    for (i, msg) in req.messages.iter().enumerate() {
+        if let Some(OpenAIContent::Parts(parts)) = &msg.content {
+            for (j, part) in parts.iter().enumerate() {
+                if let OpenAIContentPart::ImageUrl { image_url } = part {
+                    if parse_data_url(&image_url.url).is_none() && !is_remote_url(&image_url.url) {
+                        return Err(OpenAIRequestError::new(
+                            format!("messages[{}].content[{}].image_url.url", i, j),
+                            "must be a data: URL or a fetchable http(s) URL",
+                        ));
+                    }
+                }
+            }
+        }
+        match msg.role {
+            OpenAIRole::Tool if msg.tool_call_id.as_deref().unwrap_or_default().is_empty() => {
+                return Err(OpenAIRequestError::new(format!("messages[{}].tool_call_id", i), "is required for tool messages"));
+            }
+            OpenAIRole::Function if msg.name.as_deref().unwrap_or_default().is_empty() => {
+                return Err(OpenAIRequestError::new(format!("messages[{}].name", i), "is required for function messages"));
+            }
+            _ => {}
+        }
+        for (j, call) in msg.tool_calls.iter().flatten().enumerate() {
+            if let Value::String(arguments) = &call.function.arguments {
+                if !arguments.trim().is_empty() && serde_json::from_str::<Value>(arguments).is_err() {
+                    return Err(OpenAIRequestError::new(
+                        format!("messages[{}].tool_calls[{}].function.arguments", i, j),
+                        "must be a JSON-encoded object",
+                    ));
+                }
+            }
+        }
+    }
+
+    for (i, tool) in req.tools.iter().flatten().enumerate() {
+        if tool.tool_type == "function" && tool.function.is_none() {
+            return Err(OpenAIRequestError::new(format!("tools[{}].function", i), "is required for function tools"));
+        }
+    }
+    if let Some(name) = named_tool_choice(req) {
+        if !declared_functions(req).iter().any(|f| f.name == name) {
+            return Err(OpenAIRequestError::new("tool_choice", format!("function '{}' is not in tools", name)));
+        }
+    }
+    Ok(())
+}
+
+/// `http(s)` image URL, downloaded by the handler once the request is authorized
+pub fn is_remote_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Parse data URL to extract mime type and base64 data
+pub fn parse_data_url(url: &str) -> Option<(String, String)> {
+    // Format: data:image/png;base64,<data>
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let mime = meta.split(';').next().filter(|m| !m.is_empty()).unwrap_or("application/octet-stream");
+    Some((mime.to_string(), data.to_string()))
+}
+
+/// Convert message content (string or content parts) to Gemini parts
+fn convert_content(content: &OpenAIContent) -> Vec<Value> {
+    match content {
+        OpenAIContent::Text(s) => vec![json!({ "text": s })],
+        OpenAIContent::Parts(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                OpenAIContentPart::Text { text } => Some(json!({ "text": text })),
+                // Remote URLs are inlined by the handler; validation rejects anything else
+                OpenAIContentPart::ImageUrl { image_url } => parse_data_url(&image_url.url)
+                    .map(|(mime, data)| json!({ "inlineData": { "mimeType": mime, "data": data } })),
+            })
+            .collect(),
+    }
+}
+
+/// Append one non-system message to Gemini `contents`.
+///
+/// Assistant `tool_calls` become `functionCall` parts and their ids are recorded
+/// in `tool_names`, so later `role: "tool"` messages can be turned into
+/// `functionResponse` parts. Consecutive tool results share one user turn.
+fn push_message(contents: &mut Vec<Value>, msg: &OpenAIMessage, tool_names: &mut HashMap<String, String>) {
+    if matches!(msg.role, OpenAIRole::Tool | OpenAIRole::Function) {
+        let call_id = msg.tool_call_id.as_deref().unwrap_or_default();
+        let name = msg
+            .name
+            .clone()
+            .or_else(|| tool_names.get(call_id).cloned())
+            .unwrap_or_else(|| call_id.to_string());
+        let mut result = msg.content.as_ref().map(OpenAIContent::text).unwrap_or_default();
+        if result.trim().is_empty() {
+            result = "Command executed successfully.".to_string();
+        }
+
+        let mut response = json!({ "name": name, "response": { "result": result } });
+        if !call_id.is_empty() {
+            response["id"] = json!(call_id);
+        }
+        let part = json!({ "functionResponse": response });
+
+        let continues_results = contents.last().is_some_and(|c| {
+            c["role"] == "user"
+                && c["parts"]
+                    .as_array()
+                    .is_some_and(|p| p.iter().all(|p| p.get("functionResponse").is_some()))
+        });
+        if continues_results {
+            if let Some(parts) = contents.last_mut().and_then(|c| c["parts"].as_array_mut()) {
+                parts.push(part);
+            }
+        } else {
+            contents.push(json!({ "role": "user", "parts": [part] }));
+        }
+        return;
+    }
+
+    let gemini_role = if msg.role == OpenAIRole::Assistant { "model" } else { "user" };
+    let mut parts = msg.content.as_ref().map(convert_content).unwrap_or_default();
+
+    if let Some(tool_calls) = &msg.tool_calls {
+        let signature = get_thought_signature();
+        for call in tool_calls {
+            let args = match &call.function.arguments {
+                Value::String(s) => serde_json::from_str(s).unwrap_or_else(|_| json!({})),
+                v @ Value::Object(_) => v.clone(),
+                _ => json!({}),
+            };
+            tool_names.insert(call.id.clone(), call.function.name.clone());
+            let mut part = json!({
+                "functionCall": { "name": call.function.name, "args": args, "id": call.id }
+            });
+            // Gemini 3+ requires the thought signature on replayed function calls
+            if let Some(sig) = &signature {
+                part["thoughtSignature"] = json!(sig);
+            }
+            parts.push(part);
+        }
+    }
+
+    if !parts.is_empty() {
+        contents.push(json!({ "role": gemini_role, "parts": parts }));
+    }
+}
+
+/// Function declarations from `tools` (or legacy `functions`)
+fn declared_functions(req: &OpenAIChatRequest) -> Vec<&OpenAIFunction> {
+    match &req.tools {
+        Some(tools) => tools
+            .iter()
+            .filter(|t| t.tool_type == "function")
+            .filter_map(|t| t.function.as_ref())
+            .collect(),
+        None => req.functions.iter().flatten().collect(),
+    }
+}
+
+/// Function forced by `tool_choice` / `function_call`, if any
+fn named_tool_choice(req: &OpenAIChatRequest) -> Option<&str> {
+    let choice = req.tool_choice.as_ref().or(req.function_call.as_ref())?.as_object()?;
+    choice
+        .get("function")
+        .and_then(|f| f.get("name"))
+        .or_else(|| choice.get("name"))
+        .and_then(|v| v.as_str())
+}
+
+/// Convert OpenAI `tools` (and legacy `functions`) to Gemini `tools`
+pub fn convert_tools(req: &OpenAIChatRequest) -> Option<Value> {
+    let declarations: Vec<Value> = declared_functions(req)
+        .into_iter()
+        .map(|f| {
+            let mut parameters = f
+                .parameters
+                .clone()
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+            clean_json_schema(&mut parameters);
+            let mut declaration = json!({ "name": f.name, "parameters": parameters });
+            if let Some(description) = &f.description {
+                declaration["description"] = json!(description);
+            }
+            declaration
+        })
+        .collect();
+
+    if declarations.is_empty() {
+        return None;
+    }
+    Some(json!([{ "functionDeclarations": declarations }]))
+}
+
+/// Convert OpenAI `tool_choice` (or legacy `function_call`) to Gemini `toolConfig`
+pub fn convert_tool_choice(req: &OpenAIChatRequest) -> Option<Value> {
+    let config = if let Some(name) = named_tool_choice(req) {
+        json!({ "mode": "ANY", "allowedFunctionNames": [name] })
+    } else {
+        match req.tool_choice.as_ref().or(req.function_call.as_ref())?.as_str()? {
+            "none" => json!({ "mode": "NONE" }),
+            "required" => json!({ "mode": "ANY" }),
+            _ => json!({ "mode": "AUTO" }),
+        }
+    };
+    Some(json!({ "functionCallingConfig": config }))
+}
+
+/// Apply `response_format` to the Gemini `generationConfig`.
+///
+/// `json_object` only forces JSON output; `json_schema` also sets a
+/// `responseSchema` cleaned for Gemini.
+pub fn apply_response_format(req: &OpenAIChatRequest, gen_config: &mut Value) {
+    match &req.response_format {
+        None | Some(ResponseFormat::Text) => {}
+        Some(ResponseFormat::JsonObject) => {
+            gen_config["responseMimeType"] = json!("application/json");
+        }
+        Some(ResponseFormat::JsonSchema { json_schema }) => {
+            let mut schema = json_schema.schema.clone();
+            clean_json_schema(&mut schema);
+            gen_config["responseMimeType"] = json!("application/json");
+            gen_config["responseSchema"] = schema;
+        }
+    }
+}
+
+/// Original schema and name of a `json_schema` response format with `strict: true`
+pub fn strict_response_schema(req: &OpenAIChatRequest) -> Option<(String, Value)> {
+    match &req.response_format {
+        Some(ResponseFormat::JsonSchema { json_schema }) if json_schema.strict => Some((
+            json_schema.name.clone().unwrap_or_else(|| "response".to_string()),
+            json_schema.schema.clone(),
+        )),
+        _ => None,
+    }
+}
+
+/// Thinking budget for an OpenAI `reasoning_effort`
+fn reasoning_budget(effort: &str) -> Option<u32> {
+    match effort {
+        "low" => Some(1024),
+        "medium" => Some(8192),
+        "high" => Some(24576),
+        _ => None,
+    }
+}
+
+/// Map `reasoning_effort` to a Gemini `thinkingConfig` on models that think.
+///
+/// Gemini 3 takes a `thinkingLevel` (low/high); other thinking models take a
+/// budget. `none`/`minimal` turn thinking off where the model allows it
+/// (2.5 Flash) and otherwise leave the model default.
+pub fn apply_reasoning_effort(req: &OpenAIChatRequest, gemini_model: &str, gen_config: &mut Value) {
+    let Some(effort) = req.reasoning_effort.as_deref() else {
+        return;
+    };
+    if gemini_model.contains("image") {
+        return;
+    }
+
+    let thinking_config = if gemini_model.starts_with("gemini-3") {
+        let level = if matches!(effort, "medium" | "high") { "high" } else { "low" };
+        json!({ "includeThoughts": true, "thinkingLevel": level })
+    } else if gemini_model.starts_with("gemini-2.5") || gemini_model.contains("-thinking") {
+        match reasoning_budget(effort) {
+            Some(budget) => json!({ "includeThoughts": true, "thinkingBudget": budget }),
+            None if gemini_model.starts_with("gemini-2.5-flash") => json!({ "thinkingBudget": 0 }),
+            None => return,
+        }
+    } else {
+        return;
+    };
+    gen_config["thinkingConfig"] = thinking_config;
+}
+
+/// Grounding and image routing for a chat request.
+///
+/// Runs the resolver shared with the Claude path: a `-online` model suffix,
+/// a `web_search`-style tool or `web_search_options` enables `googleSearch`,
+/// and image models get their `imageConfig` from the model-name suffixes.
+pub fn resolve_openai_config(req: &OpenAIChatRequest, mapped_model: &str) -> RequestConfig {
+    let mut tools: Vec<Value> = req
+        .tools
+        .iter()
+        .flatten()
+        .filter_map(|t| serde_json::to_value(t).ok())
+        .collect();
+    if req.web_search_options.is_some() {
+        tools.push(json!({ "type": "web_search" }));
+    }
+    resolve_request_config(&req.model, mapped_model, &(!tools.is_empty()).then_some(tools))
+}
+
+/// Build the Gemini request (`contents`, `systemInstruction`, config, tools).
+///
+/// Every system/developer message becomes a `systemInstruction` part, in order.
+pub fn transform_openai_request(req: &OpenAIChatRequest, config: &RequestConfig) -> Value {
+    let mut contents = Vec::new();
+    let mut system_parts = Vec::new();
+    let mut tool_names = HashMap::new();
+
+    for msg in &req.messages {
+        match msg.role {
+            OpenAIRole::System | OpenAIRole::Developer => {
+                let text = msg.content.as_ref().map(OpenAIContent::text).unwrap_or_default();
+                if !text.is_empty() {
+                    system_parts.push(json!({ "text": text }));
+                }
+            }
+            _ => push_message(&mut contents, msg, &mut tool_names),
+        }
+    }
+
+    let mut gen_config = json!({});
+    if let Some(max_tokens) = req.max_completion_tokens.or(req.max_tokens) {
+        gen_config["maxOutputTokens"] = json!(max_tokens);
+    }
+    if let Some(temperature) = req.temperature {
+        gen_config["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = req.top_p {
+        gen_config["topP"] = json!(top_p);
+    }
+    if let Some(stop) = &req.stop {
+        gen_config["stopSequences"] = json!(stop.to_vec());
+    }
+    apply_response_format(req, &mut gen_config);
+    apply_reasoning_effort(req, &config.final_model, &mut gen_config);
+
+    let mut inner_request = json!({
+        "contents": contents,
+        "safetySettings": [
+            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
+            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
+            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
+            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
+            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" }
+        ]
+    });
+    if !system_parts.is_empty() {
+        inner_request["systemInstruction"] = json!({ "parts": system_parts });
+    }
+
+    let mut tools = convert_tools(req);
+    if config.inject_google_search {
+        // Search "functions" are served by googleSearch, which cannot be mixed with real declarations
+        tools = tools.and_then(|mut tools| {
+            let declarations = tools[0]["functionDeclarations"].as_array_mut()?;
+            declarations.retain(|d| !d["name"].as_str().is_some_and(is_networking_tool_name));
+            (!declarations.is_empty()).then_some(tools)
+        });
+    }
+    if let Some(tools) = tools {
+        inner_request["tools"] = tools;
+        if let Some(tool_config) = convert_tool_choice(req) {
+            inner_request["toolConfig"] = tool_config;
+        }
+    }
+    if config.inject_google_search {
+        inject_google_search_tool(&mut inner_request);
+    }
+
+    if let Some(image_config) = &config.image_config {
+        apply_image_config(req, image_config, &mut inner_request, &mut gen_config);
+    }
+    if gen_config.as_object().is_some_and(|o| !o.is_empty()) {
+        inner_request["generationConfig"] = gen_config;
+    }
+    inner_request
+}
+
+/// Image generation: tools, system prompt and text-only settings are dropped;
+/// `image_config` from the request overrides the model-name suffixes.
+fn apply_image_config(req: &OpenAIChatRequest, suffix_config: &Value, inner_request: &mut Value, gen_config: &mut Value) {
+    if let Some(obj) = inner_request.as_object_mut() {
+        obj.remove("tools");
+        obj.remove("toolConfig");
+        obj.remove("systemInstruction");
+    }
+    if let Some(obj) = gen_config.as_object_mut() {
+        for key in ["thinkingConfig", "responseMimeType", "responseSchema", "responseModalities"] {
+            obj.remove(key);
+        }
+    }
+
+    let mut image_config = suffix_config.clone();
+    if let Some(options) = &req.image_config {
+        if let Some(ratio) = &options.aspect_ratio {
+            image_config["aspectRatio"] = json!(ratio);
+        }
+        if let Some(size) = &options.image_size {
+            image_config["imageSize"] = json!(size.to_uppercase());
+        }
+    }
+    gen_config["imageConfig"] = image_config;
+}
+
+/// Wrap a Gemini request for v1internal
+pub fn wrap_v1internal(inner_request: Value, config: &RequestConfig, project_id: &str) -> Value {
+    json!({
+        "project": project_id,
+        "requestId": format!("cli-{}", uuid::Uuid::new_v4().simple()),
+        "request": inner_request,
+        "model": config.final_model,
+        "userAgent": "antigravity-cli",
+        "requestType": config.request_type
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: Value) -> OpenAIChatRequest {
+        parse_chat_request(&body).unwrap()
+    }
+
+    fn parse_err(body: Value) -> OpenAIRequestError {
+        parse_chat_request(&body).unwrap_err()
+    }
+
+    fn transform(req: &OpenAIChatRequest, mapped_model: &str) -> Value {
+        transform_openai_request(req, &resolve_openai_config(req, mapped_model))
+    }
+
+    #[test]
+    fn test_simple_request() {
+        let req = parse(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "max_tokens": 100,
+            "temperature": 0.5,
+            "stop": "END"
+        }));
+        let inner = transform(&req, "gemini-2.5-flash");
+
+        assert_eq!(inner["contents"][0]["role"], "user");
+        assert_eq!(inner["contents"][0]["parts"][0]["text"], "Hello");
+        assert_eq!(inner["generationConfig"]["maxOutputTokens"], 100);
+        assert_eq!(inner["generationConfig"]["temperature"], 0.5);
+        assert_eq!(inner["generationConfig"]["stopSequences"], json!(["END"]));
+        assert!(inner.get("systemInstruction").is_none());
+
+        let wrapped = wrap_v1internal(inner, &resolve_openai_config(&req, "gemini-2.5-flash"), "proj-1");
+        assert_eq!(wrapped["project"], "proj-1");
+        assert_eq!(wrapped["model"], "gemini-2.5-flash");
+        assert_eq!(wrapped["requestType"], "agent");
+        assert_eq!(wrapped["request"]["contents"][0]["parts"][0]["text"], "Hello");
+    }
+
+    #[test]
+    fn test_all_system_messages_become_system_instruction() {
+        let req = parse(json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hi"},
+                {"role": "developer", "content": [{"type": "text", "text": "Answer in French."}]}
+            ]
+        }));
+        let inner = transform(&req, "gemini-2.5-flash");
+
+        let parts = inner["systemInstruction"]["parts"].as_array().unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0]["text"], "Be brief.");
+        assert_eq!(parts[1]["text"], "Answer in French.");
+        // No fake user/model exchange for the system prompt
+        assert_eq!(inner["contents"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_image_data_url() {
+        let req = parse(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo=", "detail": "low"}}
+            ]}]
+        }));
+        let inner = transform(&req, "gemini-2.5-flash");
+        let parts = inner["contents"][0]["parts"].as_array().unwrap();
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(parts[1]["inlineData"]["data"], "iVBORw0KGgo=");
+    }
+
+    #[test]
+    fn test_validation_errors_name_the_field() {
+        assert_eq!(parse_err(json!({"messages": [{"role": "user", "content": "Hi"}]})).param, "model");
+        assert_eq!(parse_err(json!({"model": "gpt-4o"})).param, "messages");
+        assert_eq!(parse_err(json!({"model": "gpt-4o", "messages": []})).param, "messages");
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [{"role": "system", "content": "x"}]})).param,
+            "messages"
+        );
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}, {"role": "robot", "content": "x"}]})).param,
+            "messages[1].role"
+        );
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}], "temperature": 3})).param,
+            "temperature"
+        );
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [{"role": "user", "content": [
+                {"type": "image_url", "image_url": {"url": "file-abc123"}}
+            ]}]}))
+            .param,
+            "messages[0].content[0].image_url.url"
+        );
+        // Remote URLs pass validation; the handler downloads them after authorization
+        assert!(parse_chat_request(&json!({"model": "gpt-4o", "messages": [{"role": "user", "content": [
+            {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+        ]}]}))
+        .is_ok());
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "tool_calls": [{"id": "c1", "type": "function", "function": {"name": "f", "arguments": "{oops"}}]}
+            ]}))
+            .param,
+            "messages[1].tool_calls[0].function.arguments"
+        );
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [{"role": "tool", "content": "18C"}]})).param,
+            "messages[0].tool_call_id"
+        );
+        assert_eq!(
+            parse_err(json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}],
+                             "response_format": {"type": "xml"}}))
+            .param,
+            "response_format.type"
+        );
+    }
+
+    #[test]
+    fn test_tool_round_trip_messages() {
+        let req = parse(json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "user", "content": "Weather in Paris and Rome?"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}},
+                    {"id": "call_2", "type": "function", "function": {"name": "get_weather", "arguments": {"city": "Rome"}}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "18C"},
+                {"role": "tool", "tool_call_id": "call_2", "content": ""}
+            ]
+        }));
+        let contents = transform(&req, "gemini-2.5-flash")["contents"].clone();
+
+        assert_eq!(contents.as_array().unwrap().len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["args"]["city"], "Paris");
+        assert_eq!(contents[1]["parts"][1]["functionCall"]["args"]["city"], "Rome");
+        assert_eq!(contents[1]["parts"][1]["functionCall"]["id"], "call_2");
+
+        let results = contents[2]["parts"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["functionResponse"]["name"], "get_weather");
+        assert_eq!(results[0]["functionResponse"]["response"]["result"], "18C");
+        assert_eq!(results[1]["functionResponse"]["response"]["result"], "Command executed successfully.");
+    }
+
+    #[test]
+    fn test_convert_tools_and_choice() {
+        let req = parse(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Find record 7"}],
+            "tools": [{"type": "function", "function": {
+                "name": "lookup",
+                "description": "Look up a record",
+                "parameters": {"type": "object", "additionalProperties": false, "properties": {"id": {"type": "string"}}}
+            }}],
+            "tool_choice": {"type": "function", "function": {"name": "lookup"}}
+        }));
+
+        let tools = convert_tools(&req).unwrap();
+        let declaration = &tools[0]["functionDeclarations"][0];
+        assert_eq!(declaration["name"], "lookup");
+        assert!(declaration["parameters"].get("additionalProperties").is_none());
+
+        let config = convert_tool_choice(&req).unwrap();
+        assert_eq!(config["functionCallingConfig"]["mode"], "ANY");
+        assert_eq!(config["functionCallingConfig"]["allowedFunctionNames"][0], "lookup");
+
+        let mut none = req.clone();
+        none.tool_choice = Some(json!("none"));
+        assert_eq!(convert_tool_choice(&none).unwrap()["functionCallingConfig"]["mode"], "NONE");
+
+        let mut unknown = serde_json::to_value(&req).unwrap();
+        unknown["tool_choice"] = json!({"type": "function", "function": {"name": "missing"}});
+        assert_eq!(parse_err(unknown).param, "tool_choice");
+    }
+
+    #[test]
+    fn test_apply_response_format() {
+        let mut req = parse(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "response_format": {"type": "json_object"}
+        }));
+        let mut gen_config = json!({});
+        apply_response_format(&req, &mut gen_config);
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert!(gen_config.get("responseSchema").is_none());
+
+        req.response_format = serde_json::from_value(json!({"type": "json_schema", "json_schema": {
+            "name": "person",
+            "strict": true,
+            "schema": {"type": "object", "additionalProperties": false,
+                       "properties": {"name": {"type": "string"}}, "required": ["name"]}
+        }}))
+        .unwrap();
+        let mut gen_config = json!({});
+        apply_response_format(&req, &mut gen_config);
+        assert_eq!(gen_config["responseSchema"]["properties"]["name"]["type"], "string");
+        assert!(gen_config["responseSchema"].get("additionalProperties").is_none());
+
+        // The strict schema is kept unmodified for validation
+        let (name, schema) = strict_response_schema(&req).unwrap();
+        assert_eq!(name, "person");
+        assert_eq!(schema["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_reasoning_effort_to_thinking_config() {
+        let with_effort = |effort: &str| {
+            parse(json!({
+                "model": "gpt-4o",
+                "messages": [{"role": "user", "content": "Hi"}],
+                "reasoning_effort": effort
+            }))
+        };
+
+        let mut gen_config = json!({});
+        apply_reasoning_effort(&with_effort("high"), "gemini-2.5-pro", &mut gen_config);
+        assert_eq!(gen_config["thinkingConfig"]["thinkingBudget"], 24576);
+        assert_eq!(gen_config["thinkingConfig"]["includeThoughts"], true);
+
+        let mut gen_config = json!({});
+        apply_reasoning_effort(&with_effort("medium"), "gemini-3-pro-preview", &mut gen_config);
+        assert_eq!(gen_config["thinkingConfig"]["thinkingLevel"], "high");
+
+        let mut gen_config = json!({});
+        apply_reasoning_effort(&with_effort("minimal"), "gemini-2.5-flash", &mut gen_config);
+        assert_eq!(gen_config["thinkingConfig"], json!({"thinkingBudget": 0}));
+
+        let mut gen_config = json!({});
+        apply_reasoning_effort(&with_effort("low"), "gemini-2.0-flash", &mut gen_config);
+        apply_reasoning_effort(&with_effort("minimal"), "gemini-2.5-pro", &mut gen_config);
+        assert!(gen_config.get("thinkingConfig").is_none());
+
+        assert_eq!(parse_err(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "reasoning_effort": "extreme"
+        })).param, "reasoning_effort");
+    }
+
+    #[test]
+    fn test_online_suffix_and_search_tools_enable_grounding() {
+        let req = parse(json!({
+            "model": "gpt-4o-online",
+            "messages": [{"role": "user", "content": "News?"}],
+            "tools": [{"type": "function", "function": {"name": "web_search", "parameters": {"type": "object"}}}]
+        }));
+        let config = resolve_openai_config(&req, "gemini-2.5-flash");
+        assert_eq!(config.request_type, "web_search");
+        let inner = transform_openai_request(&req, &config);
+        // The search "function" is replaced by googleSearch
+        assert_eq!(inner["tools"], json!([{"googleSearch": {}}]));
+        assert!(inner.get("toolConfig").is_none());
+
+        let req = parse(json!({
+            "model": "gpt-4o-search-preview",
+            "messages": [{"role": "user", "content": "News?"}],
+            "web_search_options": {}
+        }));
+        let config = resolve_openai_config(&req, "gemini-2.5-pro-thinking");
+        assert_eq!(config.final_model, "gemini-2.5-flash");
+        assert_eq!(wrap_v1internal(json!({}), &config, "p")["requestType"], "web_search");
+    }
+
+    #[test]
+    fn test_image_model_routing_and_image_config() {
+        let req = parse(json!({
+            "model": "gemini-3-pro-image-16x9",
+            "messages": [
+                {"role": "system", "content": "Be nice"},
+                {"role": "user", "content": "A cat"}
+            ],
+            "reasoning_effort": "high",
+            "image_config": {"image_size": "2k"}
+        }));
+        let config = resolve_openai_config(&req, "gemini-3-pro-image-16x9");
+        assert_eq!(config.request_type, "image_gen");
+        assert_eq!(config.final_model, "gemini-3-pro-image");
+
+        let inner = transform_openai_request(&req, &config);
+        assert!(inner.get("systemInstruction").is_none());
+        assert!(inner["generationConfig"].get("thinkingConfig").is_none());
+        assert_eq!(inner["generationConfig"]["imageConfig"], json!({"aspectRatio": "16:9", "imageSize": "2K"}));
+
+        let err = parse_err(json!({
+            "model": "gemini-3-pro-image",
+            "messages": [{"role": "user", "content": "A cat"}],
+            "image_config": {"aspectRatio": "7:5"}
+        }));
+        assert_eq!(err.param, "image_config.aspect_ratio");
+    }
+}
@@ -0,0 +1,356 @@
+//! OpenAI response conversion (Gemini → `chat.completion`)
+
+use serde_json::{json, Value};
+
+use super::streaming::{map_finish_reason, to_openai_usage};
+use crate::proxy::mappers::claude::UsageMetadata;
+use crate::proxy::mappers::signature_store::store_thought_signature;
+
+/// Convert Gemini response to OpenAI chat completion format
+pub fn convert_chat_response(gemini_response: &Value, original_model: &str) -> Value {
+    let candidates = gemini_response.get("candidates").and_then(|v| v.as_array());
+    
+    let mut choices = Vec::new();
+    
+    if let Some(candidates) = candidates {
+        for (i, candidate) in candidates.iter().enumerate() {
+            let parts = candidate
+                .get("content")
+                .and_then(|c| c.get("parts"))
+                .and_then(|p| p.as_array());
+            
+            let mut content = String::new();
+            let mut reasoning = String::new();
+            let mut tool_calls = Vec::new();
+            let mut images = Vec::new();
+            for part in parts.into_iter().flatten() {
+                if let Some(call) = tool_call_from_part(part) {
+                    tool_calls.push(call);
+                } else if let Some(image) = image_part_from_part(part) {
+                    images.push(image);
+                } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                    if part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false) {
+                        reasoning.push_str(text);
+                    } else {
+                        content.push_str(text);
+                    }
+                }
+            }
+            
+            let finish_reason = if !tool_calls.is_empty() {
+                "tool_calls"
+            } else {
+                candidate
+                    .get("finishReason")
+                    .and_then(|v| v.as_str())
+                    .map(map_finish_reason)
+                    .unwrap_or("stop")
+            };
+            
+            let mut message = json!({
+                "role": "assistant",
+                "content": content
+            });
+            if !images.is_empty() {
+                // Generated images come back as content parts after the text
+                let mut content_parts = Vec::new();
+                if !content.is_empty() {
+                    content_parts.push(json!({ "type": "text", "text": content }));
+                }
+                content_parts.extend(images);
+                message["content"] = json!(content_parts);
+            }
+            if let Some(grounding) = candidate.get("groundingMetadata") {
+                let annotations = url_citations(grounding, &content);
+                if !annotations.is_empty() {
+                    message["annotations"] = json!(annotations);
+                }
+            }
+            if !reasoning.is_empty() {
+                message["reasoning_content"] = json!(reasoning);
+            }
+            if !tool_calls.is_empty() {
+                if content.is_empty() {
+                    message["content"] = Value::Null;
+                }
+                message["tool_calls"] = json!(tool_calls);
+            }
+            
+            choices.push(json!({
+                "index": i,
+                "message": message,
+                "finish_reason": finish_reason
+            }));
+        }
+    }
+    
+    let usage = gemini_response
+        .get("usageMetadata")
+        .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok())
+        .map(|u| to_openai_usage(&u))
+        .unwrap_or(json!({
+            "prompt_tokens": 0,
+            "completion_tokens": 0,
+            "total_tokens": 0
+        }));
+    
+    json!({
+        "id": format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": original_model,
+        "choices": choices,
+        "usage": usage
+    })
+}
+
+/// Convert a Gemini part carrying `functionCall` to an OpenAI tool call.
+/// The part's thought signature is kept for replay on the next turn.
+pub fn tool_call_from_part(part: &Value) -> Option<Value> {
+    let function_call = part.get("functionCall")?;
+    if let Some(sig) = part.get("thoughtSignature").and_then(|v| v.as_str()) {
+        store_thought_signature(sig);
+    }
+
+    let name = function_call.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+    let arguments = function_call.get("args").cloned().unwrap_or_else(|| json!({}));
+    let id = function_call
+        .get("id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("call_{}", crate::proxy::common::utils::generate_random_id()));
+
+    Some(json!({
+        "id": id,
+        "type": "function",
+        "function": {
+            "name": name,
+            "arguments": arguments.to_string()
+        }
+    }))
+}
+
+/// Convert a Gemini `inlineData` part (generated image) to an `image_url` content part
+pub fn image_part_from_part(part: &Value) -> Option<Value> {
+    let inline = part.get("inlineData")?;
+    let data = inline.get("data").and_then(|v| v.as_str())?;
+    let mime = inline.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
+    Some(json!({
+        "type": "image_url",
+        "image_url": { "url": format!("data:{};base64,{}", mime, data) }
+    }))
+}
+
+/// Convert Gemini `groundingMetadata` to OpenAI `url_citation` annotations on `text`.
+///
+/// Gemini segment offsets are in bytes, OpenAI's in characters. Without
+/// `groundingSupports` each source is cited for the whole text.
+pub fn url_citations(grounding: &Value, text: &str) -> Vec<Value> {
+    let Some(chunks) = grounding.get("groundingChunks").and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+    let citation = |chunk_index: usize, start: usize, end: usize| {
+        let web = chunks.get(chunk_index)?.get("web")?;
+        let url = web.get("uri").and_then(|v| v.as_str())?;
+        Some(json!({
+            "type": "url_citation",
+            "url_citation": {
+                "start_index": start,
+                "end_index": end,
+                "url": url,
+                "title": web.get("title").and_then(|v| v.as_str()).unwrap_or(url)
+            }
+        }))
+    };
+
+    let supports = grounding.get("groundingSupports").and_then(|v| v.as_array()).filter(|s| !s.is_empty());
+    let Some(supports) = supports else {
+        let end = text.chars().count();
+        return (0..chunks.len()).filter_map(|i| citation(i, 0, end)).collect();
+    };
+
+    let mut annotations = Vec::new();
+    for support in supports {
+        let segment = support.get("segment");
+        let offset = |key: &str| segment.and_then(|s| s.get(key)).and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let start = byte_to_char_index(text, offset("startIndex"));
+        let end = byte_to_char_index(text, offset("endIndex"));
+        let indices = support.get("groundingChunkIndices").and_then(|v| v.as_array());
+        for index in indices.into_iter().flatten().filter_map(|v| v.as_u64()) {
+            annotations.extend(citation(index as usize, start, end));
+        }
+    }
+    annotations
+}
+
+/// Character index of a byte offset (clamped to the text, rounded down to a char boundary)
+fn byte_to_char_index(text: &str, byte_offset: usize) -> usize {
+    let mut offset = byte_offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    text[..offset].chars().count()
+}
+
+/// Compiled `json_schema` response format used to check `strict: true` outputs
+pub struct StrictSchema {
+    name: String,
+    validator: jsonschema::Validator,
+}
+
+impl StrictSchema {
+    pub fn new(name: String, schema: &Value) -> Result<Self, String> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| format!("Invalid response_format schema '{}': {}", name, e))?;
+        Ok(Self { name, validator })
+    }
+
+    /// Check model output against the schema
+    pub fn validate(&self, content: &str) -> Result<(), String> {
+        let value: Value = serde_json::from_str(content.trim())
+            .map_err(|e| format!("Model output is not valid JSON for schema '{}': {}", self.name, e))?;
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .take(3)
+            .map(|e| format!("{} at '{}'", e, e.instance_path))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Model output does not match schema '{}': {}", self.name, errors.join("; ")))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_call_from_part() {
+        let part = json!({"functionCall": {"name": "lookup", "args": {"id": "42"}}});
+        let call = tool_call_from_part(&part).unwrap();
+
+        assert!(call["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(call["function"]["name"], "lookup");
+        assert_eq!(call["function"]["arguments"], "{\"id\":\"42\"}");
+        assert!(tool_call_from_part(&json!({"text": "hi"})).is_none());
+    }
+
+    #[test]
+    fn test_strict_schema_validation() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"age": {"type": "integer", "minimum": 0}},
+            "required": ["age"],
+            "additionalProperties": false
+        });
+        let strict = StrictSchema::new("person".to_string(), &schema).unwrap();
+
+        assert!(strict.validate(r#"{"age": 30}"#).is_ok());
+        assert!(strict.validate(r#"{"age": -1}"#).unwrap_err().contains("/age"));
+        assert!(strict.validate(r#"{"age": 3, "name": "x"}"#).is_err());
+        assert!(strict.validate("not json").unwrap_err().contains("not valid JSON"));
+    }
+
+    #[test]
+    fn test_convert_function_call_response() {
+        let gemini = json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"functionCall": {"name": "get_weather", "args": {"city": "Paris"}, "id": "call_abc"}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        });
+
+        let resp = convert_chat_response(&gemini, "gpt-4o");
+        let choice = &resp["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        assert_eq!(choice["message"]["tool_calls"][0]["id"], "call_abc");
+        assert_eq!(choice["message"]["tool_calls"][0]["function"]["arguments"], "{\"city\":\"Paris\"}");
+    }
+
+    #[test]
+    fn test_thought_parts_become_reasoning_content() {
+        let gemini = json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "Compare the sizes first.", "thought": true},
+                    {"text": "42"}
+                ]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 2, "thoughtsTokenCount": 30, "totalTokenCount": 42}
+        });
+
+        let resp = convert_chat_response(&gemini, "gpt-4o");
+        let message = &resp["choices"][0]["message"];
+        assert_eq!(message["content"], "42");
+        assert_eq!(message["reasoning_content"], "Compare the sizes first.");
+        assert_eq!(resp["usage"]["completion_tokens"], 32);
+        assert_eq!(resp["usage"]["completion_tokens_details"]["reasoning_tokens"], 30);
+        assert_eq!(resp["usage"]["total_tokens"], 42);
+    }
+
+    #[test]
+    fn test_inline_images_become_content_parts() {
+        let gemini = json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "Here you go"},
+                    {"inlineData": {"mimeType": "image/jpeg", "data": "/9j/4AAQ"}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        });
+
+        let message = &convert_chat_response(&gemini, "gemini-3-pro-image")["choices"][0]["message"];
+        assert_eq!(message["content"][0], json!({"type": "text", "text": "Here you go"}));
+        assert_eq!(message["content"][1]["type"], "image_url");
+        assert_eq!(message["content"][1]["image_url"]["url"], "data:image/jpeg;base64,/9j/4AAQ");
+    }
+
+    #[test]
+    fn test_grounding_becomes_url_citations() {
+        // "Café" is 5 bytes but 4 characters
+        let text = "Café open. Closes at 9.";
+        let grounding = json!({
+            "webSearchQueries": ["cafe hours"],
+            "groundingChunks": [
+                {"web": {"uri": "https://a.example", "title": "A"}},
+                {"web": {"uri": "https://b.example"}}
+            ],
+            "groundingSupports": [
+                {"segment": {"endIndex": 11, "text": "Café open."}, "groundingChunkIndices": [0]},
+                {"segment": {"startIndex": 12, "endIndex": 24}, "groundingChunkIndices": [0, 1]}
+            ]
+        });
+
+        let annotations = url_citations(&grounding, text);
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(annotations[0]["type"], "url_citation");
+        assert_eq!(annotations[0]["url_citation"]["start_index"], 0);
+        assert_eq!(annotations[0]["url_citation"]["end_index"], 10);
+        assert_eq!(annotations[2]["url_citation"]["start_index"], 11);
+        assert_eq!(annotations[2]["url_citation"]["end_index"], 23);
+        assert_eq!(annotations[2]["url_citation"]["title"], "https://b.example");
+
+        let no_supports = json!({"groundingChunks": [{"web": {"uri": "https://a.example", "title": "A"}}]});
+        let annotations = url_citations(&no_supports, text);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0]["url_citation"]["end_index"], 23);
+
+        let gemini = json!({"candidates": [{
+            "content": {"role": "model", "parts": [{"text": text}]},
+            "groundingMetadata": grounding,
+            "finishReason": "STOP"
+        }]});
+        let message = &convert_chat_response(&gemini, "gpt-4o-online")["choices"][0]["message"];
+        assert_eq!(message["content"], text);
+        assert_eq!(message["annotations"].as_array().unwrap().len(), 3);
+    }
+}
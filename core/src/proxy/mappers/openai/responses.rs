@@ -0,0 +1,777 @@
+//! OpenAI Responses API conversion
+//! `/v1/responses` requests are rewritten into chat completions bodies so they go
+//! through the same Gemini request mapper; Gemini output is turned back into
+//! Responses output items and streaming events.
+
+use std::pin::Pin;
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+
+use super::error::OpenAIError;
+use super::response::{tool_call_from_part, StrictSchema};
+use super::streaming::{map_finish_reason, to_openai_usage};
+use crate::proxy::mappers::claude::UsageMetadata;
+use crate::proxy::upstream::timeouts::UpstreamByteStream;
+
+/// Convert Responses `input` (string or item list) to chat messages
+pub fn input_to_messages(input: &Value) -> Result<Vec<Value>, String> {
+    let items = match input {
+        Value::String(text) => return Ok(vec![json!({ "role": "user", "content": text })]),
+        Value::Array(items) => items,
+        Value::Null => return Ok(Vec::new()),
+        _ => return Err("'input' must be a string or an array of items".to_string()),
+    };
+
+    let mut messages: Vec<Value> = Vec::new();
+    for item in items {
+        let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("message");
+        match item_type {
+            "message" => {
+                let role = match item.get("role").and_then(|v| v.as_str()).unwrap_or("user") {
+                    "developer" | "system" => "system",
+                    "assistant" => "assistant",
+                    _ => "user",
+                };
+                let content = match item.get("content") {
+                    Some(Value::Array(parts)) => convert_content_parts(parts, role)?,
+                    Some(other) => other.clone(),
+                    None => Value::String(String::new()),
+                };
+                messages.push(json!({ "role": role, "content": content }));
+            }
+            "function_call" => {
+                let call = json!({
+                    "id": item.get("call_id").or_else(|| item.get("id")).cloned().unwrap_or_default(),
+                    "type": "function",
+                    "function": {
+                        "name": item.get("name").cloned().unwrap_or_default(),
+                        "arguments": item.get("arguments").cloned().unwrap_or_else(|| json!("{}"))
+                    }
+                });
+                // Parallel calls belong to one assistant turn
+                match messages.last_mut().and_then(|m| m.get_mut("tool_calls")).and_then(|t| t.as_array_mut()) {
+                    Some(calls) => calls.push(call),
+                    None => messages.push(json!({ "role": "assistant", "content": null, "tool_calls": [call] })),
+                }
+            }
+            "function_call_output" => {
+                let output = match item.get("output") {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Array(parts)) => parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    Some(other) => other.to_string(),
+                    None => String::new(),
+                };
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": item.get("call_id").cloned().unwrap_or_default(),
+                    "content": output
+                }));
+            }
+            // Reasoning items carry no replayable content for Gemini
+            "reasoning" => {}
+            other => return Err(format!("Unsupported input item type: '{}'", other)),
+        }
+    }
+    Ok(messages)
+}
+
+/// Convert Responses content parts to chat content parts (assistant text is flattened)
+fn convert_content_parts(parts: &[Value], role: &str) -> Result<Value, String> {
+    let mut converted = Vec::new();
+    for part in parts {
+        match part.get("type").and_then(|v| v.as_str()).unwrap_or_default() {
+            "input_text" | "output_text" | "text" | "refusal" => {
+                let text = part.get("text").or_else(|| part.get("refusal")).cloned().unwrap_or_default();
+                converted.push(json!({ "type": "text", "text": text }));
+            }
+            "input_image" => {
+                let url = part
+                    .get("image_url")
+                    .and_then(|v| v.as_str())
+                    .ok_or("input_image requires an 'image_url' (file_id is not supported)")?;
+                converted.push(json!({ "type": "image_url", "image_url": { "url": url } }));
+            }
+            other => return Err(format!("Unsupported content part type: '{}'", other)),
+        }
+    }
+
+    if role == "assistant" || role == "system" {
+        let text: Vec<&str> = converted.iter().filter_map(|p| p["text"].as_str()).collect();
+        return Ok(Value::String(text.join("")));
+    }
+    Ok(Value::Array(converted))
+}
+
+/// Build a chat completions body from a Responses request.
+///
+/// `history` is the stored conversation of `previous_response_id`. Returns the
+/// chat body and the conversation (history plus new input, without instructions)
+/// to store once the response completes.
+pub fn responses_to_chat(body: &Value, history: Vec<Value>) -> Result<(Value, Vec<Value>), String> {
+    let mut conversation = history;
+    conversation.extend(input_to_messages(body.get("input").unwrap_or(&Value::Null))?);
+    if conversation.is_empty() {
+        return Err("'input' is required".to_string());
+    }
+
+    // Instructions apply to this response only and are not carried over
+    let mut messages = Vec::new();
+    if let Some(instructions) = body.get("instructions").and_then(|v| v.as_str()) {
+        messages.push(json!({ "role": "system", "content": instructions }));
+    }
+    messages.extend(conversation.iter().cloned());
+
+    let mut chat = json!({
+        "model": body.get("model").cloned().unwrap_or_default(),
+        "messages": messages,
+        "stream": body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false)
+    });
+    for (from, to) in [
+        ("max_output_tokens", "max_tokens"),
+        ("temperature", "temperature"),
+        ("top_p", "top_p"),
+        ("user", "user"),
+    ] {
+        if let Some(v) = body.get(from).filter(|v| !v.is_null()) {
+            chat[to] = v.clone();
+        }
+    }
+    if let Some(effort) = body.get("reasoning").and_then(|r| r.get("effort")).filter(|v| !v.is_null()) {
+        chat["reasoning_effort"] = effort.clone();
+    }
+
+    if let Some(tools) = body.get("tools").and_then(|v| v.as_array()) {
+        let functions: Vec<Value> = tools
+            .iter()
+            .filter(|t| t.get("type").and_then(|v| v.as_str()) == Some("function"))
+            .map(|t| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.get("name").cloned().unwrap_or_default(),
+                        "description": t.get("description").cloned().unwrap_or_default(),
+                        "parameters": t.get("parameters").cloned().unwrap_or_else(|| json!({"type": "object", "properties": {}}))
+                    }
+                })
+            })
+            .collect();
+        // The built-in search tool maps to grounding
+        let search_tools = tools
+            .iter()
+            .filter(|t| matches!(t.get("type").and_then(|v| v.as_str()), Some("web_search" | "web_search_preview")))
+            .count();
+        if search_tools > 0 {
+            chat["web_search_options"] = json!({});
+        }
+        let ignored = tools.len() - functions.len() - search_tools;
+        if ignored > 0 {
+            tracing::debug!("[Responses] Ignoring {} non-function tool(s)", ignored);
+        }
+        if !functions.is_empty() {
+            chat["tools"] = json!(functions);
+        }
+    }
+    match body.get("tool_choice") {
+        Some(Value::Object(choice)) if choice.get("type").and_then(|v| v.as_str()) == Some("function") => {
+            chat["tool_choice"] = json!({ "type": "function", "function": { "name": choice.get("name") } });
+        }
+        Some(choice @ Value::String(_)) => chat["tool_choice"] = choice.clone(),
+        _ => {}
+    }
+
+    if let Some(format) = body.get("text").and_then(|t| t.get("format")) {
+        match format.get("type").and_then(|v| v.as_str()) {
+            Some("json_schema") => {
+                chat["response_format"] = json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": format.get("name").cloned().unwrap_or_else(|| json!("response")),
+                        "schema": format.get("schema").cloned().unwrap_or_default(),
+                        "strict": format.get("strict").cloned().unwrap_or(json!(false))
+                    }
+                });
+            }
+            Some("json_object") => chat["response_format"] = json!({ "type": "json_object" }),
+            _ => {}
+        }
+    }
+
+    Ok((chat, conversation))
+}
+
+/// Identity of a response being produced
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    pub id: String,
+    pub created_at: i64,
+    pub model: String,
+    pub previous_response_id: Option<String>,
+    pub instructions: Option<String>,
+}
+
+impl ResponseMeta {
+    pub fn new(body: &Value, model: &str) -> Self {
+        Self {
+            id: format!("resp_{}", uuid::Uuid::new_v4().simple()),
+            created_at: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+            previous_response_id: body.get("previous_response_id").and_then(|v| v.as_str()).map(str::to_string),
+            instructions: body.get("instructions").and_then(|v| v.as_str()).map(str::to_string),
+        }
+    }
+
+    /// Response object with the given status, output items and usage
+    pub fn response(&self, status: &str, output: &[Value], usage: Option<Value>) -> Value {
+        let mut response = json!({
+            "id": self.id,
+            "object": "response",
+            "created_at": self.created_at,
+            "status": status,
+            "model": self.model,
+            "output": output,
+            "previous_response_id": self.previous_response_id,
+            "instructions": self.instructions,
+            "usage": usage,
+            "error": null,
+            "incomplete_details": null
+        });
+        if status == "incomplete" {
+            response["incomplete_details"] = json!({ "reason": "max_output_tokens" });
+        }
+        response
+    }
+}
+
+fn message_item(id: &str, text: &str, status: &str) -> Value {
+    json!({
+        "type": "message",
+        "id": id,
+        "status": status,
+        "role": "assistant",
+        "content": [{ "type": "output_text", "text": text, "annotations": [] }]
+    })
+}
+
+fn function_call_item(call: &Value, status: &str) -> Value {
+    let call_id = call["id"].as_str().unwrap_or_default();
+    json!({
+        "type": "function_call",
+        "id": format!("fc_{}", call_id.trim_start_matches("call_")),
+        "call_id": call_id,
+        "name": call["function"]["name"],
+        "arguments": call["function"]["arguments"],
+        "status": status
+    })
+}
+
+/// Chat `usage` as Responses `usage`
+fn to_responses_usage(usage: &Value) -> Value {
+    json!({
+        "input_tokens": usage.get("prompt_tokens").cloned().unwrap_or(json!(0)),
+        "input_tokens_details": {
+            "cached_tokens": usage.pointer("/prompt_tokens_details/cached_tokens").cloned().unwrap_or(json!(0))
+        },
+        "output_tokens": usage.get("completion_tokens").cloned().unwrap_or(json!(0)),
+        "output_tokens_details": {
+            "reasoning_tokens": usage.pointer("/completion_tokens_details/reasoning_tokens").cloned().unwrap_or(json!(0))
+        },
+        "total_tokens": usage.get("total_tokens").cloned().unwrap_or(json!(0))
+    })
+}
+
+/// Convert a chat completion (from `convert_chat_response`) to a Responses object
+pub fn chat_to_response(chat: &Value, meta: &ResponseMeta) -> Value {
+    let choice = &chat["choices"][0];
+    let message = &choice["message"];
+    let mut output = Vec::new();
+
+    // Image responses carry content parts; only the text goes into `output_text`
+    let text = match message.get("content") {
+        Some(Value::Array(parts)) => parts.iter().filter_map(|p| p.get("text").and_then(|t| t.as_str())).collect(),
+        Some(content) => content.as_str().unwrap_or_default().to_string(),
+        None => String::new(),
+    };
+    if !text.is_empty() {
+        let mut item = message_item(&format!("msg_{}", uuid::Uuid::new_v4().simple()), &text, "completed");
+        // Chat nests the citation under `url_citation`, Responses has it flat
+        let annotations: Vec<Value> = message
+            .get("annotations")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|a| {
+                let mut citation = a.get("url_citation")?.clone();
+                citation["type"] = json!("url_citation");
+                Some(citation)
+            })
+            .collect();
+        item["content"][0]["annotations"] = json!(annotations);
+        output.push(item);
+    }
+    for call in message.get("tool_calls").and_then(|v| v.as_array()).into_iter().flatten() {
+        output.push(function_call_item(call, "completed"));
+    }
+
+    let status = if choice["finish_reason"] == "length" { "incomplete" } else { "completed" };
+    meta.response(status, &output, chat.get("usage").map(to_responses_usage))
+}
+
+/// Final state of a streamed response, handed to the completion callback
+pub struct CompletedResponse {
+    pub response: Value,
+    /// Assistant turn as a chat message, for the conversation store
+    pub assistant_message: Value,
+    pub usage: Option<UsageMetadata>,
+}
+
+pub type CompletionCallback = Box<dyn FnOnce(CompletedResponse) + Send>;
+
+/// Streaming state machine producing Responses SSE events
+pub struct ResponsesStreamingState {
+    meta: ResponseMeta,
+    sequence: u64,
+    started: bool,
+    /// Open message item: (output_index, item_id, text so far)
+    text_item: Option<(usize, String, String)>,
+    output: Vec<Value>,
+    tool_calls: Vec<Value>,
+    text: String,
+    finish_reason: Option<&'static str>,
+    /// `strict` json_schema the streamed text is checked against before completing
+    strict: Option<StrictSchema>,
+    pub final_usage: Option<UsageMetadata>,
+    pub finished: bool,
+}
+
+impl ResponsesStreamingState {
+    pub fn new(meta: ResponseMeta) -> Self {
+        Self {
+            meta,
+            sequence: 0,
+            started: false,
+            text_item: None,
+            output: Vec::new(),
+            tool_calls: Vec::new(),
+            text: String::new(),
+            finish_reason: None,
+            strict: None,
+            final_usage: None,
+            finished: false,
+        }
+    }
+
+    pub fn with_strict_schema(mut self, strict: Option<StrictSchema>) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    fn event(&mut self, event_type: &str, mut data: Value) -> Bytes {
+        data["type"] = json!(event_type);
+        data["sequence_number"] = json!(self.sequence);
+        self.sequence += 1;
+        Bytes::from(format!("event: {}\ndata: {}\n\n", event_type, data))
+    }
+
+    /// `response.created` and `response.in_progress`
+    pub fn emit_start(&mut self) -> Vec<Bytes> {
+        if self.started {
+            return vec![];
+        }
+        self.started = true;
+        let response = self.meta.response("in_progress", &[], None);
+        vec![
+            self.event("response.created", json!({ "response": response })),
+            self.event("response.in_progress", json!({ "response": response })),
+        ]
+    }
+
+    fn emit_text(&mut self, delta: &str) -> Vec<Bytes> {
+        let mut events = Vec::new();
+        if self.text_item.is_none() {
+            let output_index = self.output.len();
+            let item_id = format!("msg_{}", uuid::Uuid::new_v4().simple());
+            let mut item = message_item(&item_id, "", "in_progress");
+            item["content"] = json!([]);
+            events.push(self.event("response.output_item.added", json!({ "output_index": output_index, "item": item })));
+            events.push(self.event(
+                "response.content_part.added",
+                json!({
+                    "item_id": item_id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "part": { "type": "output_text", "text": "", "annotations": [] }
+                }),
+            ));
+            // Reserve the slot so later items get the following indexes
+            self.output.push(Value::Null);
+            self.text_item = Some((output_index, item_id, String::new()));
+        }
+        let (output_index, item_id) = match self.text_item.as_mut() {
+            Some((index, id, text)) => {
+                text.push_str(delta);
+                (*index, id.clone())
+            }
+            None => return events,
+        };
+        self.text.push_str(delta);
+        events.push(self.event(
+            "response.output_text.delta",
+            json!({ "item_id": item_id, "output_index": output_index, "content_index": 0, "delta": delta }),
+        ));
+        events
+    }
+
+    fn close_text(&mut self) -> Vec<Bytes> {
+        let Some((output_index, item_id, text)) = self.text_item.take() else {
+            return vec![];
+        };
+        let item = message_item(&item_id, &text, "completed");
+        let part = item["content"][0].clone();
+        self.output[output_index] = item.clone();
+        vec![
+            self.event(
+                "response.output_text.done",
+                json!({ "item_id": item_id, "output_index": output_index, "content_index": 0, "text": text }),
+            ),
+            self.event(
+                "response.content_part.done",
+                json!({ "item_id": item_id, "output_index": output_index, "content_index": 0, "part": part }),
+            ),
+            self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })),
+        ]
+    }
+
+    fn emit_function_call(&mut self, call: Value) -> Vec<Bytes> {
+        let mut events = self.close_text();
+        let output_index = self.output.len();
+        let item = function_call_item(&call, "completed");
+        let mut added = item.clone();
+        added["arguments"] = json!("");
+        added["status"] = json!("in_progress");
+        let item_id = item["id"].clone();
+        let arguments = item["arguments"].clone();
+
+        events.push(self.event("response.output_item.added", json!({ "output_index": output_index, "item": added })));
+        events.push(self.event(
+            "response.function_call_arguments.delta",
+            json!({ "item_id": item_id, "output_index": output_index, "delta": arguments }),
+        ));
+        events.push(self.event(
+            "response.function_call_arguments.done",
+            json!({ "item_id": item_id, "output_index": output_index, "arguments": arguments }),
+        ));
+        events.push(self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })));
+        self.output.push(item);
+        self.tool_calls.push(call);
+        events
+    }
+
+    /// Convert one upstream SSE line
+    pub fn process_line(&mut self, line: &str) -> Vec<Bytes> {
+        if self.finished {
+            return vec![];
+        }
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            return vec![];
+        };
+        let Ok(json_value) = serde_json::from_str::<Value>(data) else {
+            return vec![];
+        };
+        if let Some(error) = OpenAIError::from_stream_event(&json_value) {
+            return self.emit_error(&error);
+        }
+        let raw = json_value.get("response").unwrap_or(&json_value);
+
+        if let Some(usage) = raw
+            .get("usageMetadata")
+            .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok())
+        {
+            self.final_usage = Some(usage);
+        }
+
+        let mut events = self.emit_start();
+        let candidate = raw.get("candidates").and_then(|c| c.get(0));
+        if let Some(parts) = candidate
+            .and_then(|c| c.get("content"))
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+        {
+            for part in parts {
+                if part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    continue;
+                }
+                if let Some(call) = tool_call_from_part(part) {
+                    events.extend(self.emit_function_call(call));
+                } else if let Some(text) = part.get("text").and_then(|t| t.as_str()).filter(|t| !t.is_empty()) {
+                    events.extend(self.emit_text(text));
+                }
+            }
+        }
+        if let Some(reason) = candidate.and_then(|c| c.get("finishReason")).and_then(|f| f.as_str()) {
+            self.finish_reason = Some(map_finish_reason(reason));
+        }
+        events
+    }
+
+    /// Close open items and emit `response.completed` (or `response.incomplete`);
+    /// strict structured output that does not conform fails the response instead
+    pub fn emit_finish(&mut self) -> (Vec<Bytes>, Option<CompletedResponse>) {
+        let mut events = self.emit_start();
+        events.extend(self.close_text());
+        self.finished = true;
+
+        if let Some(strict) = self.strict.as_ref().filter(|_| self.tool_calls.is_empty()) {
+            if let Err(message) = strict.validate(&self.text) {
+                tracing::warn!("[Responses-Stream] {}", message);
+                events.extend(self.emit_error(&OpenAIError::schema_violation(message)));
+                return (events, None);
+            }
+        }
+
+        let (status, event_type) = match self.finish_reason {
+            Some("length") => ("incomplete", "response.incomplete"),
+            _ => ("completed", "response.completed"),
+        };
+        let usage = self.final_usage.as_ref().map(|u| to_responses_usage(&to_openai_usage(u)));
+        let output: Vec<Value> = self.output.iter().filter(|item| !item.is_null()).cloned().collect();
+        let response = self.meta.response(status, &output, usage);
+        events.push(self.event(event_type, json!({ "response": response })));
+
+        let mut assistant_message = json!({
+            "role": "assistant",
+            "content": if self.text.is_empty() { Value::Null } else { json!(self.text) }
+        });
+        if !self.tool_calls.is_empty() {
+            assistant_message["tool_calls"] = json!(self.tool_calls);
+        }
+        let completed = CompletedResponse { response, assistant_message, usage: self.final_usage.clone() };
+        (events, Some(completed))
+    }
+
+    /// Terminal `error` + `response.failed` events
+    pub fn emit_error(&mut self, error: &OpenAIError) -> Vec<Bytes> {
+        let code = error.code().unwrap_or(error.error_type());
+        let message = error.to_string();
+        self.finished = true;
+        let mut events = self.emit_start();
+        events.push(self.event("error", json!({ "code": code, "message": message, "param": null })));
+        let mut response = self.meta.response("failed", &[], None);
+        response["error"] = json!({ "code": code, "message": message });
+        events.push(self.event("response.failed", json!({ "response": response })));
+        events
+    }
+}
+
+/// Create the Gemini SSE → Responses SSE conversion stream
+pub fn create_responses_sse_stream(
+    mut gemini_stream: UpstreamByteStream,
+    meta: ResponseMeta,
+    strict: Option<StrictSchema>,
+    on_complete: Option<CompletionCallback>,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    Box::pin(async_stream::stream! {
+        let mut state = ResponsesStreamingState::new(meta).with_strict_schema(strict);
+        let mut buffer = BytesMut::new();
+
+        for event in state.emit_start() {
+            yield Ok(event);
+        }
+
+        while let Some(chunk_result) = gemini_stream.next().await {
+            match chunk_result {
+                Ok(chunk) => {
+                    buffer.extend_from_slice(&chunk);
+                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line_raw = buffer.split_to(pos + 1);
+                        if let Ok(line) = std::str::from_utf8(&line_raw) {
+                            for event in state.process_line(line.trim()) {
+                                yield Ok(event);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    let error = if e.is_timeout() {
+                        OpenAIError::Timeout(e.to_string())
+                    } else {
+                        OpenAIError::bad_gateway(format!("Stream error: {}", e))
+                    };
+                    for event in state.emit_error(&error) {
+                        yield Ok(event);
+                    }
+                    break;
+                }
+            }
+        }
+
+        if !state.finished {
+            let (events, completed) = state.emit_finish();
+            for event in events {
+                yield Ok(event);
+            }
+            if let (Some(callback), Some(completed)) = (on_complete, completed) {
+                callback(completed);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_types(events: &[Bytes]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| {
+                let text = String::from_utf8(e.to_vec()).unwrap();
+                text.lines().next().unwrap().trim_start_matches("event: ").to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_input_items_to_messages() {
+        let input = json!([
+            {"role": "developer", "content": "Be brief"},
+            {"type": "message", "role": "user", "content": [{"type": "input_text", "text": "Weather?"}]},
+            {"type": "function_call", "call_id": "call_1", "name": "weather", "arguments": "{}"},
+            {"type": "function_call", "call_id": "call_2", "name": "time", "arguments": "{}"},
+            {"type": "function_call_output", "call_id": "call_1", "output": "sunny"},
+            {"type": "reasoning", "summary": []}
+        ]);
+        let messages = input_to_messages(&input).unwrap();
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"][0]["text"], "Weather?");
+        assert_eq!(messages[2]["tool_calls"].as_array().unwrap().len(), 2);
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages[3]["tool_call_id"], "call_1");
+
+        assert!(input_to_messages(&json!([{"type": "computer_call"}])).is_err());
+    }
+
+    #[test]
+    fn test_responses_to_chat_with_history() {
+        let history = vec![
+            json!({"role": "user", "content": "Hi"}),
+            json!({"role": "assistant", "content": "Hello"}),
+        ];
+        let body = json!({
+            "model": "gpt-4o",
+            "instructions": "Answer in French",
+            "input": "How are you?",
+            "max_output_tokens": 100,
+            "reasoning": {"effort": "low"},
+            "tools": [{"type": "function", "name": "lookup", "parameters": {"type": "object"}}, {"type": "web_search_preview"}],
+            "tool_choice": {"type": "function", "name": "lookup"},
+            "text": {"format": {"type": "json_schema", "name": "reply", "schema": {"type": "object"}, "strict": true}}
+        });
+        let (chat, conversation) = responses_to_chat(&body, history).unwrap();
+
+        assert_eq!(chat["messages"][0]["role"], "system");
+        assert_eq!(chat["messages"].as_array().unwrap().len(), 4);
+        assert_eq!(conversation.len(), 3);
+        assert_eq!(chat["max_tokens"], 100);
+        assert_eq!(chat["reasoning_effort"], "low");
+        assert_eq!(chat["tools"].as_array().unwrap().len(), 1);
+        assert!(chat["web_search_options"].is_object());
+        assert_eq!(chat["tool_choice"]["function"]["name"], "lookup");
+        assert_eq!(chat["response_format"]["json_schema"]["strict"], true);
+    }
+
+    #[test]
+    fn test_chat_to_response() {
+        let meta = ResponseMeta::new(&json!({"previous_response_id": "resp_prev"}), "gpt-4o");
+        let chat = json!({
+            "choices": [{"message": {"role": "assistant", "content": "Done", "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{}"}}
+            ]}, "finish_reason": "tool_calls"}],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 3, "total_tokens": 8}
+        });
+        let response = chat_to_response(&chat, &meta);
+
+        assert_eq!(response["object"], "response");
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["previous_response_id"], "resp_prev");
+        assert_eq!(response["output"][0]["content"][0]["text"], "Done");
+        assert_eq!(response["output"][1]["type"], "function_call");
+        assert_eq!(response["output"][1]["call_id"], "call_1");
+        assert_eq!(response["usage"]["input_tokens"], 5);
+    }
+
+    #[test]
+    fn test_chat_to_response_annotations() {
+        let meta = ResponseMeta::new(&json!({}), "gpt-4o-online");
+        let chat = json!({
+            "choices": [{"message": {"role": "assistant", "content": "Open until 9.", "annotations": [
+                {"type": "url_citation", "url_citation": {"start_index": 0, "end_index": 13, "url": "https://a.example", "title": "A"}}
+            ]}, "finish_reason": "stop"}]
+        });
+        let annotation = &chat_to_response(&chat, &meta)["output"][0]["content"][0]["annotations"][0];
+        assert_eq!(annotation["type"], "url_citation");
+        assert_eq!(annotation["url"], "https://a.example");
+        assert_eq!(annotation["end_index"], 13);
+    }
+
+    #[test]
+    fn test_streaming_event_sequence() {
+        let mut state = ResponsesStreamingState::new(ResponseMeta::new(&json!({}), "gpt-4o"));
+        let mut events = state.process_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"Hel"}]}}]}}"#);
+        events.extend(state.process_line(
+            r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"lo"},{"functionCall":{"name":"lookup","args":{}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":4,"candidatesTokenCount":2}}}"#,
+        ));
+        let (finish, completed) = state.emit_finish();
+        events.extend(finish);
+        let completed = completed.unwrap();
+
+        assert_eq!(
+            event_types(&events),
+            vec![
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+        assert_eq!(completed.response["output"][0]["content"][0]["text"], "Hello");
+        assert_eq!(completed.response["output"][1]["name"], "lookup");
+        assert_eq!(completed.response["usage"]["output_tokens"], 2);
+        assert_eq!(completed.assistant_message["content"], "Hello");
+        assert_eq!(completed.assistant_message["tool_calls"][0]["function"]["name"], "lookup");
+    }
+
+    #[test]
+    fn test_streaming_strict_schema_violation_fails_response() {
+        let schema = json!({"type": "object", "properties": {"answer": {"type": "string"}}, "required": ["answer"]});
+        let strict = StrictSchema::new("reply".to_string(), &schema).unwrap();
+        let mut state =
+            ResponsesStreamingState::new(ResponseMeta::new(&json!({}), "gpt-4o")).with_strict_schema(Some(strict));
+        let mut events = state.process_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"{\"other\": 1}"}]},"finishReason":"STOP"}]}}"#);
+        let (finish, completed) = state.emit_finish();
+        events.extend(finish);
+
+        // Nothing is stored for a response that failed validation
+        assert!(completed.is_none());
+        let types = event_types(&events);
+        assert_eq!(&types[types.len() - 2..], ["error", "response.failed"]);
+        assert!(String::from_utf8(events.last().unwrap().to_vec()).unwrap().contains("json_schema_validation_failed"));
+    }
+}
@@ -0,0 +1,420 @@
+//! OpenAI streaming response conversion (Gemini SSE → `chat.completion.chunk` SSE)
+
+use std::pin::Pin;
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+
+use super::error::OpenAIError;
+use super::response::{image_part_from_part, tool_call_from_part, url_citations, StrictSchema};
+use crate::proxy::mappers::claude::{UsageCallback, UsageMetadata};
+use crate::proxy::upstream::timeouts::UpstreamByteStream;
+
+/// Map a Gemini `finishReason` to an OpenAI `finish_reason`
+pub fn map_finish_reason(reason: &str) -> &'static str {
+    match reason {
+        "MAX_TOKENS" => "length",
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// Gemini `usageMetadata` as an OpenAI `usage` object.
+///
+/// Gemini counts thinking separately from `candidatesTokenCount`; OpenAI includes
+/// reasoning in `completion_tokens` and breaks it out in `completion_tokens_details`.
+pub fn to_openai_usage(usage: &UsageMetadata) -> Value {
+    let prompt = usage.prompt_token_count.unwrap_or(0);
+    let reasoning = usage.thoughts_token_count.unwrap_or(0);
+    let completion = usage.candidates_token_count.unwrap_or(0) + reasoning;
+    let mut value = json!({
+        "prompt_tokens": prompt,
+        "completion_tokens": completion,
+        "total_tokens": usage.total_token_count.unwrap_or(prompt + completion)
+    });
+    if let Some(cached) = usage.cached_content_token_count {
+        value["prompt_tokens_details"] = json!({ "cached_tokens": cached });
+    }
+    if usage.thoughts_token_count.is_some() {
+        value["completion_tokens_details"] = json!({ "reasoning_tokens": reasoning });
+    }
+    value
+}
+
+/// Streaming state for one chat completion
+pub struct OpenAIStreamingState {
+    id: String,
+    created: i64,
+    model: String,
+    include_usage: bool,
+    role_sent: bool,
+    /// Number of tool calls emitted so far (the `index` of the next one)
+    tool_calls: usize,
+    pub finish_sent: bool,
+    pub done_sent: bool,
+    /// Latest usageMetadata seen on the stream
+    pub final_usage: Option<UsageMetadata>,
+    /// `strict` json_schema the streamed content is checked against before finishing
+    strict: Option<StrictSchema>,
+    /// Text streamed so far (validated against `strict`, cited by `grounding`)
+    content: String,
+    /// Latest groundingMetadata, sent as `annotations` before the finish chunk
+    grounding: Option<Value>,
+}
+
+impl OpenAIStreamingState {
+    pub fn new(model: &str, include_usage: bool) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
+            created: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+            include_usage,
+            role_sent: false,
+            tool_calls: 0,
+            finish_sent: false,
+            done_sent: false,
+            final_usage: None,
+            strict: None,
+            content: String::new(),
+            grounding: None,
+        }
+    }
+
+    pub fn with_strict_schema(mut self, strict: Option<StrictSchema>) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Build one `chat.completion.chunk` SSE event
+    fn chunk(&self, choices: Value, usage: Option<Value>) -> Bytes {
+        let mut data = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": choices
+        });
+        // With include_usage every chunk carries `usage`, null until the final one
+        if self.include_usage {
+            data["usage"] = usage.unwrap_or(Value::Null);
+        }
+        Bytes::from(format!("data: {}\n\n", data))
+    }
+
+    fn emit_delta(&mut self, mut delta: Value) -> Bytes {
+        if !self.role_sent {
+            delta["role"] = json!("assistant");
+            self.role_sent = true;
+        }
+        self.chunk(json!([{ "index": 0, "delta": delta, "finish_reason": null }]), None)
+    }
+
+    /// Emit the chunk carrying `finish_reason`
+    pub fn emit_finish(&mut self, finish_reason: &str) -> Vec<Bytes> {
+        if self.finish_sent {
+            return vec![];
+        }
+        let mut chunks = Vec::new();
+        if !self.role_sent {
+            chunks.push(self.emit_delta(json!({ "content": "" })));
+        }
+        self.finish_sent = true;
+
+        if let Some(grounding) = self.grounding.take() {
+            let annotations = url_citations(&grounding, &self.content);
+            if !annotations.is_empty() {
+                chunks.push(self.emit_delta(json!({ "annotations": annotations })));
+            }
+        }
+
+        // Strict structured output that does not conform ends the stream with an error
+        if let Some(strict) = self.strict.as_ref().filter(|_| self.tool_calls == 0) {
+            if let Err(message) = strict.validate(&self.content) {
+                tracing::warn!("[OpenAI-Stream] {}", message);
+                chunks.push(OpenAIError::schema_violation(message).sse_chunk());
+                self.done_sent = true;
+                return chunks;
+            }
+        }
+
+        // Like OpenAI, a turn that called tools finishes with `tool_calls`
+        let finish_reason = if self.tool_calls > 0 && finish_reason == "stop" {
+            "tool_calls"
+        } else {
+            finish_reason
+        };
+        chunks.push(self.chunk(json!([{ "index": 0, "delta": {}, "finish_reason": finish_reason }]), None));
+        chunks
+    }
+
+    /// Close the stream: pending finish, the usage chunk (if requested) and `[DONE]`
+    pub fn emit_done(&mut self) -> Vec<Bytes> {
+        if self.done_sent {
+            return vec![];
+        }
+        let mut chunks = self.emit_finish("stop");
+        if self.include_usage {
+            let usage = self.final_usage.as_ref().map(to_openai_usage).unwrap_or_else(|| {
+                json!({ "prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0 })
+            });
+            chunks.push(self.chunk(json!([]), Some(usage)));
+        }
+        chunks.push(Bytes::from_static(b"data: [DONE]\n\n"));
+        self.done_sent = true;
+        chunks
+    }
+
+    /// Terminal error chunk; no `[DONE]` follows
+    pub fn emit_error(&mut self, error: &OpenAIError) -> Vec<Bytes> {
+        if self.done_sent {
+            return vec![];
+        }
+        tracing::warn!("[OpenAI-Stream] {}: {}", error.error_type(), error);
+        self.finish_sent = true;
+        self.done_sent = true;
+        vec![error.sse_chunk()]
+    }
+
+    /// Convert one upstream SSE line
+    pub fn process_line(&mut self, line: &str) -> Vec<Bytes> {
+        if self.done_sent {
+            return vec![];
+        }
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            return vec![];
+        };
+        if data.is_empty() {
+            return vec![];
+        }
+        if data == "[DONE]" {
+            return self.emit_done();
+        }
+        let Ok(json_value) = serde_json::from_str::<Value>(data) else {
+            return vec![];
+        };
+        // An error event from upstream ends the stream
+        if let Some(error) = OpenAIError::from_stream_event(&json_value) {
+            return self.emit_error(&error);
+        }
+        // Unwrap the v1internal `response` field
+        let raw = json_value.get("response").unwrap_or(&json_value);
+
+        if let Some(usage) = raw
+            .get("usageMetadata")
+            .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok())
+        {
+            self.final_usage = Some(usage);
+        }
+
+        let mut chunks = Vec::new();
+        let candidate = raw.get("candidates").and_then(|c| c.get(0));
+
+        if let Some(parts) = candidate
+            .and_then(|c| c.get("content"))
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+        {
+            for part in parts {
+                // Thought parts go to `reasoning_content`, never into `content`
+                if part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    if let Some(text) = part.get("text").and_then(|t| t.as_str()).filter(|t| !t.is_empty()) {
+                        chunks.push(self.emit_delta(json!({ "reasoning_content": text })));
+                    }
+                    continue;
+                }
+                if let Some(mut call) = tool_call_from_part(part) {
+                    call["index"] = json!(self.tool_calls);
+                    self.tool_calls += 1;
+                    chunks.push(self.emit_delta(json!({ "content": null, "tool_calls": [call] })));
+                } else if let Some(image) = image_part_from_part(part) {
+                    chunks.push(self.emit_delta(json!({ "content": [image] })));
+                } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                    if !text.is_empty() {
+                        self.content.push_str(text);
+                        chunks.push(self.emit_delta(json!({ "content": text })));
+                    }
+                }
+            }
+        }
+
+        if let Some(grounding) = candidate.and_then(|c| c.get("groundingMetadata")) {
+            self.grounding = Some(grounding.clone());
+        }
+        if let Some(reason) = candidate.and_then(|c| c.get("finishReason")).and_then(|f| f.as_str()) {
+            chunks.extend(self.emit_finish(map_finish_reason(reason)));
+        }
+
+        chunks
+    }
+}
+
+/// Create the Gemini SSE → OpenAI SSE conversion stream
+pub fn create_openai_sse_stream(
+    mut gemini_stream: UpstreamByteStream,
+    model: String,
+    include_usage: bool,
+    strict: Option<StrictSchema>,
+    on_usage: Option<UsageCallback>,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    Box::pin(async_stream::stream! {
+        let mut state = OpenAIStreamingState::new(&model, include_usage).with_strict_schema(strict);
+        let mut buffer = BytesMut::new();
+
+        while let Some(chunk_result) = gemini_stream.next().await {
+            match chunk_result {
+                Ok(chunk) => {
+                    buffer.extend_from_slice(&chunk);
+                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line_raw = buffer.split_to(pos + 1);
+                        if let Ok(line) = std::str::from_utf8(&line_raw) {
+                            for out in state.process_line(line.trim()) {
+                                yield Ok(out);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    let error = if e.is_timeout() {
+                        OpenAIError::Timeout(e.to_string())
+                    } else {
+                        OpenAIError::bad_gateway(format!("Stream error: {}", e))
+                    };
+                    for out in state.emit_error(&error) {
+                        yield Ok(out);
+                    }
+                    break;
+                }
+            }
+        }
+
+        for out in state.emit_done() {
+            yield Ok(out);
+        }
+
+        if let (Some(callback), Some(usage)) = (on_usage, state.final_usage.as_ref()) {
+            callback(usage);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(chunks: &[Bytes]) -> Vec<Value> {
+        chunks
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap())
+            .filter_map(|s| serde_json::from_str(s.trim().strip_prefix("data: ")?).ok())
+            .collect()
+    }
+
+    #[test]
+    fn test_text_deltas_and_finish() {
+        let mut state = OpenAIStreamingState::new("gpt-4o", false);
+        let first = state.process_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"Hel"}]}}]}}"#);
+        let second = state.process_line(
+            r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"lo"}]},"finishReason":"MAX_TOKENS"}]}}"#,
+        );
+
+        let first = collect(&first);
+        assert_eq!(first[0]["object"], "chat.completion.chunk");
+        assert_eq!(first[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(first[0]["choices"][0]["delta"]["content"], "Hel");
+        assert!(first[0].get("usage").is_none());
+
+        let second = collect(&second);
+        assert!(second[0]["choices"][0]["delta"].get("role").is_none());
+        assert_eq!(second[1]["choices"][0]["finish_reason"], "length");
+
+        let done = state.emit_done();
+        assert_eq!(done.len(), 1);
+        assert_eq!(&done[0][..], b"data: [DONE]\n\n");
+    }
+
+    #[test]
+    fn test_include_usage_chunk() {
+        let mut state = OpenAIStreamingState::new("gpt-4o", true);
+        state.process_line(
+            r#"data: {"candidates":[{"content":{"parts":[{"text":"Hi"}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":7,"candidatesTokenCount":2,"totalTokenCount":9}}"#,
+        );
+        let done = state.emit_done();
+        let usage_chunk = &collect(&done)[0];
+
+        assert_eq!(usage_chunk["choices"], json!([]));
+        assert_eq!(usage_chunk["usage"]["prompt_tokens"], 7);
+        assert_eq!(usage_chunk["usage"]["total_tokens"], 9);
+        assert!(String::from_utf8(done.last().unwrap().to_vec()).unwrap().contains("[DONE]"));
+    }
+
+    #[test]
+    fn test_strict_schema_violation_ends_with_error() {
+        let schema = json!({"type": "object", "required": ["answer"]});
+        let strict = StrictSchema::new("answer".to_string(), &schema).unwrap();
+        let mut state = OpenAIStreamingState::new("gpt-4o", false).with_strict_schema(Some(strict));
+
+        state.process_line(r#"data: {"candidates":[{"content":{"parts":[{"text":"{\"other\":"}]}}]}"#);
+        let end = collect(&state.process_line(
+            r#"data: {"candidates":[{"content":{"parts":[{"text":"1}"}]},"finishReason":"STOP"}]}"#,
+        ));
+
+        assert_eq!(end.last().unwrap()["error"]["code"], "json_schema_validation_failed");
+        assert!(state.emit_done().is_empty());
+    }
+
+    #[test]
+    fn test_function_call_delta() {
+        let mut state = OpenAIStreamingState::new("gpt-4o", false);
+        let chunks = collect(&state.process_line(
+            r#"data: {"response":{"candidates":[{"content":{"parts":[{"functionCall":{"name":"lookup","args":{"id":"42"}}}]},"finishReason":"STOP"}]}}"#,
+        ));
+
+        let call = &chunks[0]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(call["index"], 0);
+        assert_eq!(call["type"], "function");
+        assert_eq!(call["function"]["name"], "lookup");
+        assert_eq!(call["function"]["arguments"], "{\"id\":\"42\"}");
+        assert_eq!(chunks[1]["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_done_without_content_still_finishes() {
+        let mut state = OpenAIStreamingState::new("gpt-4o", false);
+        let chunks = collect(&state.process_line("data: [DONE]"));
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(chunks[1]["choices"][0]["finish_reason"], "stop");
+        assert!(state.emit_done().is_empty());
+    }
+
+    #[test]
+    fn test_thought_parts_stream_as_reasoning_content() {
+        let mut state = OpenAIStreamingState::new("gpt-4o", false);
+        let chunks = collect(&state.process_line(
+            r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"Thinking...","thought":true},{"text":"Answer"}]}}]}}"#,
+        ));
+
+        assert_eq!(chunks[0]["choices"][0]["delta"]["reasoning_content"], "Thinking...");
+        assert!(chunks[0]["choices"][0]["delta"].get("content").is_none());
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "Answer");
+    }
+
+    #[test]
+    fn test_image_and_grounding_deltas() {
+        let mut state = OpenAIStreamingState::new("gemini-3-pro-image", false);
+        let chunks = collect(&state.process_line(
+            r#"data: {"candidates":[{"content":{"parts":[{"inlineData":{"mimeType":"image/png","data":"iVBORw0KGgo="}}]}}]}"#,
+        ));
+        assert_eq!(chunks[0]["choices"][0]["delta"]["content"][0]["image_url"]["url"], "data:image/png;base64,iVBORw0KGgo=");
+
+        let mut state = OpenAIStreamingState::new("gpt-4o-online", false);
+        state.process_line(r#"data: {"candidates":[{"content":{"parts":[{"text":"Open until 9."}]}}]}"#);
+        let chunks = collect(&state.process_line(
+            r#"data: {"candidates":[{"content":{"parts":[]},"groundingMetadata":{"groundingChunks":[{"web":{"uri":"https://a.example","title":"A"}}],"groundingSupports":[{"segment":{"endIndex":13},"groundingChunkIndices":[0]}]},"finishReason":"STOP"}]}"#,
+        ));
+        let annotation = &chunks[0]["choices"][0]["delta"]["annotations"][0];
+        assert_eq!(annotation["url_citation"]["url"], "https://a.example");
+        assert_eq!(annotation["url_citation"]["end_index"], 13);
+        assert_eq!(chunks[1]["choices"][0]["finish_reason"], "stop");
+    }
+}
@@ -0,0 +1,401 @@
+//! Remote media fetcher
+//! Downloads `http(s)://` images and documents referenced by client requests so
+//! they can be sent upstream as `inlineData`. Enforces the `[media]` size, MIME,
+//! time and host limits, refuses private addresses unless allowed (SSRF), and
+//! caches downloads by URL hash.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+
+use crate::config::MediaConfig;
+
+/// Redirects followed per download; every hop is checked like the original URL
+const MAX_REDIRECTS: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MediaError {
+    #[error("remote media fetching is disabled")]
+    Disabled,
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("host '{0}' is not allowed")]
+    HostNotAllowed(String),
+    #[error("HTTP {0}")]
+    Http(u16),
+    #[error("larger than the {0} byte limit")]
+    TooLarge(u64),
+    #[error("MIME type '{0}' is not allowed")]
+    UnsupportedMime(String),
+    #[error("download did not complete within {0}s")]
+    Timeout(u64),
+    #[error("{0}")]
+    Network(String),
+}
+
+/// Downloaded media, base64 encoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedMedia {
+    pub mime_type: String,
+    pub data: String,
+}
+
+impl FetchedMedia {
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+#[derive(Default)]
+struct MediaCache {
+    entries: HashMap<String, (Instant, FetchedMedia)>,
+    /// Insertion order, oldest first
+    order: VecDeque<String>,
+}
+
+pub struct MediaFetcher {
+    config: MediaConfig,
+    cache: Mutex<MediaCache>,
+}
+
+impl MediaFetcher {
+    pub fn new(config: MediaConfig) -> Self {
+        Self { config, cache: Mutex::new(MediaCache::default()) }
+    }
+
+    /// Download `url` (or return the cached copy)
+    pub async fn fetch(&self, url: &str) -> Result<FetchedMedia, MediaError> {
+        if !self.config.fetch_remote {
+            return Err(MediaError::Disabled);
+        }
+        let key = hex_sha256(url);
+        if let Some(media) = self.cached(&key) {
+            tracing::debug!("[Media] Cache hit for {}", url);
+            return Ok(media);
+        }
+
+        let limit = Duration::from_secs(self.config.timeout);
+        let media = tokio::time::timeout(limit, self.download(url))
+            .await
+            .map_err(|_| MediaError::Timeout(self.config.timeout))??;
+        tracing::debug!("[Media] Fetched {} ({}, {} base64 bytes)", url, media.mime_type, media.data.len());
+
+        self.store(key, media.clone());
+        Ok(media)
+    }
+
+    fn cached(&self, key: &str) -> Option<FetchedMedia> {
+        let ttl = Duration::from_secs(self.config.cache_ttl);
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entries
+            .get(key)
+            .filter(|(stored_at, _)| stored_at.elapsed() < ttl)
+            .map(|(_, media)| media.clone())
+    }
+
+    fn store(&self, key: String, media: FetchedMedia) {
+        if self.config.cache_entries == 0 {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.entries.insert(key.clone(), (Instant::now(), media)).is_none() {
+            cache.order.push_back(key);
+        }
+        while cache.order.len() > self.config.cache_entries {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.entries.remove(&oldest);
+            }
+        }
+    }
+
+    async fn download(&self, url: &str) -> Result<FetchedMedia, MediaError> {
+        let mut url = reqwest::Url::parse(url).map_err(|e| MediaError::InvalidUrl(e.to_string()))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let (host, addrs) = self.check_url(&url).await?;
+
+            // Pin the checked addresses so the connection cannot be re-resolved elsewhere
+            let client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .no_proxy()
+                .resolve_to_addrs(&host, &addrs)
+                .build()
+                .map_err(|e| MediaError::Network(e.to_string()))?;
+            let response = client
+                .get(url.clone())
+                .send()
+                .await
+                .map_err(|e| MediaError::Network(e.to_string()))?;
+
+            let status = response.status();
+            if status.is_redirection() {
+                let location = response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or(MediaError::Http(status.as_u16()))?;
+                url = url.join(location).map_err(|e| MediaError::InvalidUrl(e.to_string()))?;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(MediaError::Http(status.as_u16()));
+            }
+            return self.read_body(response).await;
+        }
+        Err(MediaError::Network(format!("more than {} redirects", MAX_REDIRECTS)))
+    }
+
+    /// Validate scheme and host, and resolve the host to addresses that may be contacted
+    async fn check_url(&self, url: &reqwest::Url) -> Result<(String, Vec<SocketAddr>), MediaError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(MediaError::InvalidUrl(format!("unsupported scheme '{}'", url.scheme())));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| MediaError::InvalidUrl("missing host".to_string()))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_lowercase();
+        if !self.host_allowed(&host) {
+            return Err(MediaError::HostNotAllowed(host));
+        }
+
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|e| MediaError::Network(format!("failed to resolve '{}': {}", host, e)))?
+            .collect();
+        if addrs.is_empty() {
+            return Err(MediaError::Network(format!("'{}' did not resolve", host)));
+        }
+        if !self.config.allow_private_networks && addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+            return Err(MediaError::HostNotAllowed(host));
+        }
+        Ok((host, addrs))
+    }
+
+    fn host_allowed(&self, host: &str) -> bool {
+        if self.config.deny_hosts.iter().any(|pattern| host_matches(host, pattern)) {
+            return false;
+        }
+        self.config.allow_hosts.is_empty() || self.config.allow_hosts.iter().any(|pattern| host_matches(host, pattern))
+    }
+
+    async fn read_body(&self, response: reqwest::Response) -> Result<FetchedMedia, MediaError> {
+        let max_bytes = self.config.max_bytes;
+        if response.content_length().is_some_and(|len| len > max_bytes) {
+            return Err(MediaError::TooLarge(max_bytes));
+        }
+        let declared = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or_default().trim().to_lowercase())
+            .filter(|v| !v.is_empty() && v != "application/octet-stream");
+
+        let mut body = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| MediaError::Network(e.to_string()))?;
+            if (body.len() + chunk.len()) as u64 > max_bytes {
+                return Err(MediaError::TooLarge(max_bytes));
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let mime_type = declared
+            .or_else(|| sniff_mime(&body).map(str::to_string))
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        if !self.mime_allowed(&mime_type) {
+            return Err(MediaError::UnsupportedMime(mime_type));
+        }
+        Ok(FetchedMedia {
+            mime_type,
+            data: base64::engine::general_purpose::STANDARD.encode(&body),
+        })
+    }
+
+    fn mime_allowed(&self, mime_type: &str) -> bool {
+        self.config.allowed_mime_types.iter().any(|pattern| match pattern.strip_suffix("/*") {
+            Some(prefix) => mime_type.split('/').next() == Some(prefix),
+            None => pattern.eq_ignore_ascii_case(mime_type),
+        })
+    }
+}
+
+impl Default for MediaFetcher {
+    fn default() -> Self {
+        Self::new(MediaConfig::default())
+    }
+}
+
+fn hex_sha256(input: &str) -> String {
+    Sha256::digest(input.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `example.com` matches itself and its subdomains; a leading `*.` is accepted
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim().trim_start_matches("*.").to_lowercase();
+    !pattern.is_empty() && (host == pattern || host.ends_with(&format!(".{}", pattern)))
+}
+
+/// Addresses that are reachable on the public internet
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// MIME type from magic bytes, for servers that send no useful Content-Type
+fn sniff_mime(body: &[u8]) -> Option<&'static str> {
+    if body.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if body.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if body.starts_with(b"GIF87a") || body.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if body.len() >= 12 && &body[..4] == b"RIFF" && &body[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if body.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use axum::{http::header, response::IntoResponse, routing::get, Router};
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+
+    /// Serve test media on a local port; returns the base URL and a hit counter
+    async fn serve() -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = Router::new()
+            .route(
+                "/cat.png",
+                get(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    async { ([(header::CONTENT_TYPE, "image/png")], PNG) }
+                }),
+            )
+            .route("/untyped", get(|| async { ([(header::CONTENT_TYPE, "application/octet-stream")], PNG) }))
+            .route("/page.html", get(|| async { ([(header::CONTENT_TYPE, "text/html")], "<html></html>") }))
+            .route("/big", get(|| async { ([(header::CONTENT_TYPE, "image/png")], vec![0u8; 4096]) }))
+            .route(
+                "/moved",
+                get(|| async { (axum::http::StatusCode::FOUND, [(header::LOCATION, "/cat.png")]).into_response() }),
+            )
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    PNG
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), hits)
+    }
+
+    fn local_config() -> MediaConfig {
+        MediaConfig { allow_private_networks: true, max_bytes: 1024, timeout: 1, ..MediaConfig::default() }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_cache() {
+        let (base, hits) = serve().await;
+        let fetcher = MediaFetcher::new(local_config());
+
+        let media = fetcher.fetch(&format!("{}/cat.png", base)).await.unwrap();
+        assert_eq!(media.mime_type, "image/png");
+        assert_eq!(media.data, base64::engine::general_purpose::STANDARD.encode(PNG));
+        assert!(media.data_url().starts_with("data:image/png;base64,"));
+
+        fetcher.fetch(&format!("{}/cat.png", base)).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Redirects are followed; the untyped body is sniffed
+        assert_eq!(fetcher.fetch(&format!("{}/moved", base)).await.unwrap().mime_type, "image/png");
+        assert_eq!(fetcher.fetch(&format!("{}/untyped", base)).await.unwrap().mime_type, "image/png");
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let (base, _) = serve().await;
+        let fetcher = MediaFetcher::new(local_config());
+
+        assert!(matches!(fetcher.fetch(&format!("{}/big", base)).await, Err(MediaError::TooLarge(1024))));
+        assert!(matches!(fetcher.fetch(&format!("{}/page.html", base)).await, Err(MediaError::UnsupportedMime(_))));
+        assert!(matches!(fetcher.fetch(&format!("{}/slow", base)).await, Err(MediaError::Timeout(1))));
+        assert!(matches!(fetcher.fetch(&format!("{}/missing", base)).await, Err(MediaError::Http(404))));
+        assert!(matches!(fetcher.fetch("file:///etc/passwd").await, Err(MediaError::InvalidUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_host_policy() {
+        let (base, hits) = serve().await;
+
+        // Private addresses are refused by default
+        let fetcher = MediaFetcher::new(MediaConfig { allow_private_networks: false, ..local_config() });
+        assert!(matches!(fetcher.fetch(&format!("{}/cat.png", base)).await, Err(MediaError::HostNotAllowed(_))));
+
+        let fetcher = MediaFetcher::new(MediaConfig { deny_hosts: vec!["127.0.0.1".to_string()], ..local_config() });
+        assert!(matches!(fetcher.fetch(&format!("{}/cat.png", base)).await, Err(MediaError::HostNotAllowed(_))));
+
+        let fetcher = MediaFetcher::new(MediaConfig { allow_hosts: vec!["cdn.example.com".to_string()], ..local_config() });
+        assert!(matches!(fetcher.fetch(&format!("{}/cat.png", base)).await, Err(MediaError::HostNotAllowed(_))));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        let fetcher = MediaFetcher::new(MediaConfig { fetch_remote: false, ..local_config() });
+        assert!(matches!(fetcher.fetch(&format!("{}/cat.png", base)).await, Err(MediaError::Disabled)));
+    }
+
+    #[test]
+    fn test_host_and_ip_rules() {
+        assert!(host_matches("img.example.com", "example.com"));
+        assert!(host_matches("example.com", "*.example.com"));
+        assert!(!host_matches("badexample.com", "example.com"));
+
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.0.1", "169.254.169.254", "100.64.0.1", "::1", "fd00::1", "::ffff:10.0.0.1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public_ip("8.8.8.8".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+    }
+}
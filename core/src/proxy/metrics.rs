@@ -0,0 +1,316 @@
+//! Prometheus metrics
+//! Process-wide registry rendered in the text exposition format at `/metrics`
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+
+/// Latency histogram buckets in seconds
+const LATENCY_BUCKETS: [f64; 12] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Global metrics registry
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+            if secs <= *le {
+                self.buckets[i] += 1;
+            }
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+/// Model and account a request was served with, attached to response
+/// extensions by handlers so the metrics middleware can label the request
+#[derive(Debug, Clone, Default)]
+pub struct RequestLabels {
+    pub model: String,
+    pub account: String,
+}
+
+/// Pool state sampled at scrape time
+#[derive(Debug, Clone, Default)]
+pub struct PoolGauges {
+    pub accounts_total: usize,
+    pub accounts_rate_limited: usize,
+}
+
+pub struct Metrics {
+    hash_accounts: AtomicBool,
+    /// (route, model, account, status)
+    requests: DashMap<(String, String, String, u16), u64>,
+    /// (route, model, account)
+    latency: DashMap<(String, String, String), Histogram>,
+    /// (model, account, status)
+    upstream_responses: DashMap<(String, String, u16), u64>,
+    /// (model, account, kind)
+    tokens: DashMap<(String, String, &'static str), u64>,
+    /// (account, status)
+    rate_limit_events: DashMap<(String, u16), u64>,
+    /// (account, result)
+    token_refreshes: DashMap<(String, &'static str), u64>,
+    /// route
+    active_streams: DashMap<String, AtomicI64>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            hash_accounts: AtomicBool::new(true),
+            requests: DashMap::new(),
+            latency: DashMap::new(),
+            upstream_responses: DashMap::new(),
+            tokens: DashMap::new(),
+            rate_limit_events: DashMap::new(),
+            token_refreshes: DashMap::new(),
+            active_streams: DashMap::new(),
+        }
+    }
+
+    /// Choose whether the `account` label carries a hash of the email or the email itself
+    pub fn set_hash_accounts(&self, hash: bool) {
+        self.hash_accounts.store(hash, Ordering::Relaxed);
+    }
+
+    /// Account label value for an email, honouring the configured label mode
+    pub fn account_label(&self, email: &str) -> String {
+        if email.is_empty() || !self.hash_accounts.load(Ordering::Relaxed) {
+            return email.to_string();
+        }
+        let digest = Sha256::digest(email.as_bytes());
+        digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Record a completed client request (latency is time until response headers)
+    pub fn record_request(&self, route: &str, labels: &RequestLabels, status: u16, elapsed: Duration) {
+        let account = self.account_label(&labels.account);
+        *self
+            .requests
+            .entry((route.to_string(), labels.model.clone(), account.clone(), status))
+            .or_default() += 1;
+        self.latency
+            .entry((route.to_string(), labels.model.clone(), account))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Record the HTTP status of an upstream (v1internal) call
+    pub fn record_upstream(&self, model: &str, email: &str, status: u16) {
+        *self
+            .upstream_responses
+            .entry((model.to_string(), self.account_label(email), status))
+            .or_default() += 1;
+    }
+
+    /// Record token usage reported in `usageMetadata`
+    pub fn record_tokens(&self, model: &str, email: &str, input: u64, output: u64, cached: u64) {
+        let account = self.account_label(email);
+        for (kind, value) in [("input", input), ("output", output), ("cached", cached)] {
+            if value > 0 {
+                *self
+                    .tokens
+                    .entry((model.to_string(), account.clone(), kind))
+                    .or_default() += value;
+            }
+        }
+    }
+
+    /// Record an account being put into rate-limit cooldown
+    pub fn record_rate_limit(&self, email: &str, status: u16) {
+        *self
+            .rate_limit_events
+            .entry((self.account_label(email), status))
+            .or_default() += 1;
+    }
+
+    /// Record an OAuth access-token refresh attempt
+    pub fn record_token_refresh(&self, email: &str, success: bool) {
+        let result = if success { "success" } else { "failure" };
+        *self
+            .token_refreshes
+            .entry((self.account_label(email), result))
+            .or_default() += 1;
+    }
+
+    /// Count a live SSE stream until the returned guard is dropped
+    pub fn stream_guard(&'static self, route: &str) -> StreamGuard {
+        self.active_streams
+            .entry(route.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+        StreamGuard {
+            metrics: self,
+            route: route.to_string(),
+        }
+    }
+
+    /// Render all series in the Prometheus text exposition format
+    pub fn render(&self, pool: &PoolGauges) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "antigravity_requests_total", "counter", "Client requests by route, mapped model, account and status");
+        for ((route, model, account, status), v) in sorted(&self.requests) {
+            sample(&mut out, "antigravity_requests_total", &[("route", &route), ("model", &model), ("account", &account), ("status", &status.to_string())], v);
+        }
+
+        header(&mut out, "antigravity_request_duration_seconds", "histogram", "Time until response headers were sent");
+        for ((route, model, account), h) in sorted(&self.latency) {
+            let base = [("route", route.as_str()), ("model", model.as_str()), ("account", account.as_str())];
+            for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+                let le = le.to_string();
+                let labels = [base[0], base[1], base[2], ("le", le.as_str())];
+                sample(&mut out, "antigravity_request_duration_seconds_bucket", &labels, h.buckets[i]);
+            }
+            let labels = [base[0], base[1], base[2], ("le", "+Inf")];
+            sample(&mut out, "antigravity_request_duration_seconds_bucket", &labels, h.count);
+            sample(&mut out, "antigravity_request_duration_seconds_sum", &base, h.sum);
+            sample(&mut out, "antigravity_request_duration_seconds_count", &base, h.count);
+        }
+
+        header(&mut out, "antigravity_upstream_responses_total", "counter", "Upstream responses by mapped model, account and status");
+        for ((model, account, status), v) in sorted(&self.upstream_responses) {
+            sample(&mut out, "antigravity_upstream_responses_total", &[("model", &model), ("account", &account), ("status", &status.to_string())], v);
+        }
+
+        header(&mut out, "antigravity_tokens_total", "counter", "Tokens reported by upstream usageMetadata");
+        for ((model, account, kind), v) in sorted(&self.tokens) {
+            sample(&mut out, "antigravity_tokens_total", &[("model", &model), ("account", &account), ("kind", kind)], v);
+        }
+
+        header(&mut out, "antigravity_rate_limit_events_total", "counter", "Accounts put into rate-limit cooldown");
+        for ((account, status), v) in sorted(&self.rate_limit_events) {
+            sample(&mut out, "antigravity_rate_limit_events_total", &[("account", &account), ("status", &status.to_string())], v);
+        }
+
+        header(&mut out, "antigravity_token_refreshes_total", "counter", "OAuth access token refreshes");
+        for ((account, result), v) in sorted(&self.token_refreshes) {
+            sample(&mut out, "antigravity_token_refreshes_total", &[("account", &account), ("result", result)], v);
+        }
+
+        header(&mut out, "antigravity_active_streams", "gauge", "SSE streams currently open");
+        let mut streams: Vec<(String, i64)> = self
+            .active_streams
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        streams.sort();
+        for (route, v) in streams {
+            sample(&mut out, "antigravity_active_streams", &[("route", &route)], v);
+        }
+
+        header(&mut out, "antigravity_pool_accounts", "gauge", "Accounts in the pool by availability");
+        let available = pool.accounts_total.saturating_sub(pool.accounts_rate_limited);
+        sample(&mut out, "antigravity_pool_accounts", &[("state", "available")], available);
+        sample(&mut out, "antigravity_pool_accounts", &[("state", "rate_limited")], pool.accounts_rate_limited);
+
+        out
+    }
+}
+
+/// Decrements the live-stream gauge when the stream it is moved into is dropped
+pub struct StreamGuard {
+    metrics: &'static Metrics,
+    route: String,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if let Some(gauge) = self.metrics.active_streams.get(&self.route) {
+            gauge.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn sorted<K: Clone + Ord + std::hash::Hash + Eq, V: Clone>(map: &DashMap<K, V>) -> Vec<(K, V)> {
+    let mut all: Vec<(K, V)> = map.iter().map(|e| (e.key().clone(), e.value().clone())).collect();
+    all.sort_by(|a, b| a.0.cmp(&b.0));
+    all
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_histogram() {
+        let m = Metrics::new();
+        m.set_hash_accounts(false);
+        let labels = RequestLabels {
+            model: "gemini-2.5-flash".to_string(),
+            account: "a@example.com".to_string(),
+        };
+        m.record_request("/v1/messages", &labels, 200, Duration::from_millis(300));
+        m.record_request("/v1/messages", &labels, 200, Duration::from_secs(3));
+        m.record_tokens("gemini-2.5-flash", "a@example.com", 10, 5, 0);
+
+        let text = m.render(&PoolGauges { accounts_total: 3, accounts_rate_limited: 1 });
+        assert!(text.contains(
+            "antigravity_requests_total{route=\"/v1/messages\",model=\"gemini-2.5-flash\",account=\"a@example.com\",status=\"200\"} 2"
+        ));
+        // Buckets are cumulative
+        assert!(text.contains("le=\"0.5\"} 1"));
+        assert!(text.contains("le=\"5\"} 2"));
+        assert!(text.contains("le=\"+Inf\"} 2"));
+        assert!(text.contains("kind=\"input\"} 10"));
+        assert!(!text.contains("kind=\"cached\""));
+        assert!(text.contains("antigravity_pool_accounts{state=\"available\"} 2"));
+    }
+
+    #[test]
+    fn test_account_label_hashing() {
+        let m = Metrics::new();
+        let hashed = m.account_label("a@example.com");
+        assert_eq!(hashed.len(), 12);
+        assert_ne!(hashed, "a@example.com");
+        assert_eq!(hashed, m.account_label("a@example.com"));
+
+        m.set_hash_accounts(false);
+        assert_eq!(m.account_label("a@example.com"), "a@example.com");
+    }
+
+    #[test]
+    fn test_stream_guard_and_escaping() {
+        let m: &'static Metrics = Box::leak(Box::new(Metrics::new()));
+        let guard = m.stream_guard("/v1/messages");
+        assert!(m.render(&PoolGauges::default()).contains("antigravity_active_streams{route=\"/v1/messages\"} 1"));
+        drop(guard);
+        assert!(m.render(&PoolGauges::default()).contains("antigravity_active_streams{route=\"/v1/messages\"} 0"));
+
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
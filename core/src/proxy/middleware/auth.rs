@@ -1,4 +1,339 @@
-//! Auth middleware (placeholder)
+//! API key authentication middleware
+//! Enforces `[auth].mode` for every route registered on the proxy router
 
-// Auth middleware would go here
-// For now, auth is handled in server.rs by checking headers
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+
+use crate::config::AuthMode;
+use crate::proxy::common::utils::glob_match;
+use crate::proxy::server::AppState;
+
+/// Paths that stay public under `AuthMode::AllExceptHealth`
+const HEALTH_PATHS: [&str; 2] = ["/healthz", "/health"];
+
+/// Client protocol inferred from the request path, used to shape error bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientProtocol {
+    OpenAI,
+    Anthropic,
+    Gemini,
+}
+
+impl ClientProtocol {
+    pub fn from_path(path: &str) -> Self {
+        if path.starts_with("/v1/messages") {
+            ClientProtocol::Anthropic
+        } else if path.starts_with("/v1beta") {
+            ClientProtocol::Gemini
+        } else {
+            ClientProtocol::OpenAI
+        }
+    }
+
+    /// Like `from_path`, but `/v1/models` is shared and answers Anthropic SDKs in their shape
+    pub fn from_request(path: &str, headers: &HeaderMap) -> Self {
+        if path.starts_with("/v1/models") && headers.contains_key("anthropic-version") {
+            ClientProtocol::Anthropic
+        } else {
+            Self::from_path(path)
+        }
+    }
+}
+
+/// A resolved client API key, attached to request extensions once authenticated
+#[derive(Debug, Clone)]
+pub struct ClientKey {
+    pub name: String,
+    pub key: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub allowed_models: Vec<String>,
+    pub allowed_accounts: Option<Vec<String>>,
+    pub admin: bool,
+}
+
+impl ClientKey {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| chrono::Utc::now() >= t)
+    }
+
+    /// Check a model against the key's model globs.
+    ///
+    /// Handlers pass the model actually sent upstream (after mappings, `-online`, reasoning
+    /// and image variants). Client-facing aliases are never matched on their own, so a
+    /// mapping can not widen what a key may use; list upstream model names in `allowed_models`.
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.allowed_models.is_empty()
+            || self.allowed_models.iter().any(|pattern| glob_match(pattern, model))
+    }
+}
+
+/// Axum middleware enforcing API key authentication
+pub async fn auth_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // CORS preflight requests never carry credentials
+    if request.method() == axum::http::Method::OPTIONS {
+        return next.run(request).await;
+    }
+
+    let security = state.security_config.read().await.clone();
+    let path = request.uri().path().to_string();
+
+    let requires_auth = match security.auth_mode {
+        AuthMode::Off => false,
+        AuthMode::Strict => true,
+        AuthMode::AllExceptHealth => !HEALTH_PATHS.contains(&path.as_str()),
+    };
+
+    // Keys are resolved even when auth is off so per-key restrictions and usage still apply
+    let protocol = ClientProtocol::from_request(&path, request.headers());
+    let provided = extract_api_key(request.headers(), request.uri().query());
+    let matched = provided.as_deref().and_then(|k| security.find_key(k)).cloned();
+
+    let client_key = match (matched, provided.is_some()) {
+        (Some(key), _) if key.is_expired() => {
+            if requires_auth {
+                tracing::warn!("Rejected request to {} with expired API key '{}'", path, key.name);
+                return unauthorized(protocol, "API key has expired");
+            }
+            None
+        }
+        (Some(key), _) => Some(key),
+        (None, true) if requires_auth => {
+            tracing::warn!("Rejected request to {} with invalid API key", path);
+            return unauthorized(protocol, "Invalid API key");
+        }
+        (None, false) if requires_auth => {
+            tracing::debug!("Rejected unauthenticated request to {}", path);
+            return unauthorized(protocol, "Missing API key");
+        }
+        (None, _) => None,
+    };
+
+    let mut request = request;
+    if let Some(key) = client_key {
+        tracing::debug!("Authenticated request to {} with key '{}'", path, key.name);
+        state.usage.record_request(&key.name);
+        request.extensions_mut().insert(key);
+    }
+
+    next.run(request).await
+}
+
+/// Extract the client API key from the supported locations, in priority order:
+/// `Authorization: Bearer`, `x-api-key`, `x-goog-api-key`, `?key=` (percent-decoded).
+/// An `Authorization` header with any other scheme is not treated as a key.
+pub fn extract_api_key(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    if let Some(auth) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        if let Some((scheme, token)) = auth.trim().split_once(' ') {
+            let token = token.trim();
+            if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() {
+                return Some(token.to_string());
+            }
+        }
+    }
+
+    for name in ["x-api-key", "x-goog-api-key"] {
+        if let Some(key) = headers.get(name).and_then(|v| v.to_str().ok()) {
+            let key = key.trim();
+            if !key.is_empty() {
+                return Some(key.to_string());
+            }
+        }
+    }
+
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == "key")
+        .and_then(|(_, v)| form_decode(v))
+        .filter(|v| !v.is_empty())
+}
+
+/// Decode an `application/x-www-form-urlencoded` value (`+` is a space, `%XX` a byte).
+/// Malformed escapes are kept literally; invalid UTF-8 yields `None`.
+fn form_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Compare two secrets without short-circuiting on the first mismatch
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Build a protocol-appropriate 401 response
+fn unauthorized(protocol: ClientProtocol, message: &str) -> Response {
+    error_response(protocol, StatusCode::UNAUTHORIZED, message)
+}
+
+/// Build a protocol-appropriate 403 response for a key that may not use a model
+pub fn model_not_allowed(protocol: ClientProtocol, key_name: &str, model: &str) -> Response {
+    tracing::warn!("API key '{}' is not allowed to use model '{}'", key_name, model);
+    error_response(
+        protocol,
+        StatusCode::FORBIDDEN,
+        &format!("API key '{}' is not allowed to use model '{}'", key_name, model),
+    )
+}
+
+fn error_response(protocol: ClientProtocol, status: StatusCode, message: &str) -> Response {
+    let (openai_code, anthropic_type, gemini_status) = if status == StatusCode::FORBIDDEN {
+        ("model_not_allowed", "permission_error", "PERMISSION_DENIED")
+    } else {
+        ("invalid_api_key", "authentication_error", "UNAUTHENTICATED")
+    };
+
+    let body = match protocol {
+        ClientProtocol::OpenAI => json!({
+            "error": {
+                "message": message,
+                "type": "invalid_request_error",
+                "param": null,
+                "code": openai_code
+            }
+        }),
+        ClientProtocol::Anthropic => json!({
+            "type": "error",
+            "error": {
+                "type": anthropic_type,
+                "message": message
+            }
+        }),
+        ClientProtocol::Gemini => json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message,
+                "status": gemini_status
+            }
+        }),
+    };
+
+    (status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_extract_api_key_sources() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer sk-openai"));
+        assert_eq!(extract_api_key(&headers, None).as_deref(), Some("sk-openai"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("sk-anthropic"));
+        assert_eq!(extract_api_key(&headers, None).as_deref(), Some("sk-anthropic"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-goog-api-key", HeaderValue::from_static("sk-gemini"));
+        assert_eq!(extract_api_key(&headers, None).as_deref(), Some("sk-gemini"));
+
+        let headers = HeaderMap::new();
+        assert_eq!(
+            extract_api_key(&headers, Some("alt=sse&key=sk-query")).as_deref(),
+            Some("sk-query")
+        );
+        assert_eq!(extract_api_key(&headers, Some("alt=sse")), None);
+    }
+
+    #[test]
+    fn test_extract_api_key_requires_bearer_scheme() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("bearer sk-lower"));
+        assert_eq!(extract_api_key(&headers, None).as_deref(), Some("sk-lower"));
+
+        // A raw key or another scheme is not a key; later sources are still checked
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("sk-raw"));
+        assert_eq!(extract_api_key(&headers, None), None);
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic dXNlcjpwYXNz"));
+        assert_eq!(extract_api_key(&headers, None), None);
+        headers.insert("x-api-key", HeaderValue::from_static("sk-anthropic"));
+        assert_eq!(extract_api_key(&headers, None).as_deref(), Some("sk-anthropic"));
+    }
+
+    #[test]
+    fn test_extract_api_key_decodes_query() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            extract_api_key(&headers, Some("key=sk%2Fteam%3Dprod&alt=sse")).as_deref(),
+            Some("sk/team=prod")
+        );
+        assert_eq!(extract_api_key(&headers, Some("key=a+b")).as_deref(), Some("a b"));
+        assert_eq!(extract_api_key(&headers, Some("key=100%")).as_deref(), Some("100%"));
+        assert_eq!(extract_api_key(&headers, Some("key=%FF")), None);
+    }
+
+    #[test]
+    fn test_protocol_from_path() {
+        assert_eq!(ClientProtocol::from_path("/v1/messages"), ClientProtocol::Anthropic);
+        assert_eq!(
+            ClientProtocol::from_path("/v1beta/models/gemini-2.5-flash:generateContent"),
+            ClientProtocol::Gemini
+        );
+        assert_eq!(ClientProtocol::from_path("/v1/chat/completions"), ClientProtocol::OpenAI);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(ClientProtocol::from_request("/v1/models", &headers), ClientProtocol::OpenAI);
+        headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
+        assert_eq!(ClientProtocol::from_request("/v1/models/claude-sonnet-4-5", &headers), ClientProtocol::Anthropic);
+        assert_eq!(ClientProtocol::from_request("/v1/chat/completions", &headers), ClientProtocol::OpenAI);
+    }
+
+    #[test]
+    fn test_client_key_restrictions() {
+        let key = ClientKey {
+            name: "ci".to_string(),
+            key: "sk-ci".to_string(),
+            expires_at: Some(chrono::Utc::now() - chrono::Duration::seconds(1)),
+            allowed_models: vec!["gemini-*".to_string()],
+            allowed_accounts: None,
+            admin: false,
+        };
+        assert!(key.is_expired());
+        assert!(key.allows_model("gemini-2.5-flash"));
+        assert!(!key.allows_model("claude-opus-4-5-thinking"));
+
+        let open = ClientKey { expires_at: None, allowed_models: vec![], ..key };
+        assert!(!open.is_expired());
+        assert!(open.allows_model("claude-opus-4-5-thinking"));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"sk-abc", b"sk-abc"));
+        assert!(!constant_time_eq(b"sk-abc", b"sk-abd"));
+        assert!(!constant_time_eq(b"sk-abc", b"sk-abcd"));
+    }
+}
@@ -0,0 +1,34 @@
+//! Request metrics middleware
+//! Records count and latency per matched route, labelled with the model and
+//! account the handler reported via `RequestLabels` response extensions
+
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::proxy::metrics::{metrics, RequestLabels};
+
+pub async fn metrics_middleware(request: Request, next: Next) -> Response {
+    // Route templates keep label cardinality bounded; unmatched paths share one series
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    if route == "/metrics" {
+        return next.run(request).await;
+    }
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    let labels = response.extensions().get::<RequestLabels>().cloned().unwrap_or_default();
+    metrics().record_request(&route, &labels, response.status().as_u16(), start.elapsed());
+
+    response
+}
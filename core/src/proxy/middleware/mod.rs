@@ -1,3 +1,4 @@
 //! Middleware modules
 
 pub mod auth;
+pub mod metrics;
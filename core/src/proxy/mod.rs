@@ -13,6 +13,12 @@ pub mod rate_limit;
 pub mod sticky_config;
 pub mod session_manager;
 pub mod project_resolver;
+pub mod usage;
+pub mod reload;
+pub mod metrics;
+pub mod media;
+pub mod responses_store;
+pub mod model_catalog;
 
 pub use config::ProxyConfig;
 pub use token_manager::TokenManager;
@@ -0,0 +1,251 @@
+//! Model catalogue behind `/v1/models`
+//! Merges configured mapping keys, built-in routes and image variants with the
+//! models the pool's accounts can use (`fetchAvailableModels`, cached).
+
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::proxy::common::model_mapping::get_all_dynamic_models;
+use crate::proxy::handlers::openai::resolve_model;
+use crate::proxy::server::AppState;
+
+/// How long a successful upstream listing is reused
+const UPSTREAM_TTL: Duration = Duration::from_secs(600);
+
+/// How long to wait before retrying after a failed listing
+const FAILURE_TTL: Duration = Duration::from_secs(60);
+
+/// Limit on the upstream call, so a slow listing does not hold up `/v1/models`
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fixed timestamp reported for every model (the upstream has no creation dates)
+const MODEL_CREATED: i64 = 1700000000;
+
+struct CachedListing {
+    fetched_at: Instant,
+    ttl: Duration,
+    models: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct ModelCatalog {
+    /// Held across the upstream call so concurrent requests share one fetch
+    upstream: Mutex<Option<CachedListing>>,
+}
+
+impl ModelCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All model ids the proxy accepts, sorted; only ids the dispatch path's resolver
+    /// maps to a model are listed, with or without a client key
+    pub async fn list(&self, state: &AppState) -> Vec<String> {
+        let mut ids: BTreeSet<String> = get_all_dynamic_models(
+            &state.openai_mapping,
+            &state.custom_mapping,
+            &state.anthropic_mapping,
+        )
+        .await
+        .into_iter()
+        .collect();
+        ids.extend(self.upstream_models(state).await);
+
+        let mut listed = Vec::with_capacity(ids.len());
+        for id in ids {
+            if resolve_model(state, &id).await.is_ok() {
+                listed.push(id);
+            }
+        }
+        listed
+    }
+
+    /// Drop the cached upstream listing (e.g. after the account pool changes)
+    pub async fn invalidate(&self) {
+        *self.upstream.lock().await = None;
+    }
+
+    async fn upstream_models(&self, state: &AppState) -> Vec<String> {
+        let mut cached = self.upstream.lock().await;
+        if let Some(listing) = cached.as_ref() {
+            if listing.fetched_at.elapsed() < listing.ttl {
+                return listing.models.clone();
+            }
+        }
+
+        let (models, ttl) = match tokio::time::timeout(FETCH_TIMEOUT, fetch_available_models(state)).await {
+            Ok(Ok(models)) => {
+                tracing::debug!("Fetched {} upstream models", models.len());
+                (models, UPSTREAM_TTL)
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to fetch upstream models: {}", e);
+                (Vec::new(), FAILURE_TTL)
+            }
+            Err(_) => {
+                tracing::warn!("Fetching upstream models timed out after {}s", FETCH_TIMEOUT.as_secs());
+                (Vec::new(), FAILURE_TTL)
+            }
+        };
+        *cached = Some(CachedListing { fetched_at: Instant::now(), ttl, models: models.clone() });
+        models
+    }
+}
+
+/// Ask one pool account which models it can use
+async fn fetch_available_models(state: &AppState) -> Result<Vec<String>, String> {
+    // Rotating keeps the listing from claiming the 60s "last used" account slot
+    let (access_token, project_id, _, _) = state
+        .token_manager
+        .get_token("text", "gemini-2.5-flash", true, None, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    let response = state
+        .upstream
+        .call_v1_internal("fetchAvailableModels", &access_token, json!({ "project": project_id }), None)
+        .await?;
+    if !response.status().is_success() {
+        return Err(format!("fetchAvailableModels returned {}", response.status()));
+    }
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    Ok(upstream_model_ids(&body))
+}
+
+/// Chat model ids of a `fetchAvailableModels` response
+pub fn upstream_model_ids(body: &Value) -> Vec<String> {
+    let mut ids: Vec<String> = body
+        .get("models")
+        .and_then(|m| m.as_object())
+        .map(|models| {
+            models
+                .keys()
+                .filter(|name| {
+                    let lower = name.to_lowercase();
+                    lower.contains("gemini") || lower.contains("claude")
+                })
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort();
+    ids
+}
+
+/// OpenAI model object
+pub fn openai_model(id: &str) -> Value {
+    json!({
+        "id": id,
+        "object": "model",
+        "created": MODEL_CREATED,
+        "owned_by": "antigravity-proxy"
+    })
+}
+
+/// Anthropic model object
+pub fn anthropic_model(id: &str) -> Value {
+    let created_at = chrono::DateTime::from_timestamp(MODEL_CREATED, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    json!({
+        "type": "model",
+        "id": id,
+        "display_name": id,
+        "created_at": created_at
+    })
+}
+
+/// One page of the Anthropic model list (`limit`, `after_id`, `before_id`)
+pub fn anthropic_page(ids: &[String], limit: usize, after_id: Option<&str>, before_id: Option<&str>) -> Value {
+    let limit = limit.clamp(1, 1000);
+    let (page, has_more): (&[String], bool) = if let Some(before) = before_id {
+        let end = ids.iter().position(|id| id == before).unwrap_or(0);
+        let start = end.saturating_sub(limit);
+        (&ids[start..end], start > 0)
+    } else {
+        let start = after_id
+            .map(|after| ids.iter().position(|id| id == after).map_or(ids.len(), |i| i + 1))
+            .unwrap_or(0);
+        let end = (start + limit).min(ids.len());
+        (&ids[start..end], end < ids.len())
+    };
+    json!({
+        "data": page.iter().map(|id| anthropic_model(id)).collect::<Vec<_>>(),
+        "has_more": has_more,
+        "first_id": page.first(),
+        "last_id": page.last()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("m{}", i)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_listed_models_resolve() {
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            [model_mapping.openai]
+            gpt-4-series = "gemini-2.5-pro"
+            team-fast = "gemini-2.5-flash"
+
+            [model_mapping.custom]
+            "legacy-pro" = "gemini-3-pro-high"
+            "#,
+        )
+        .unwrap();
+        let state = AppState::for_tests(&config);
+
+        let listed = state.models.list(&state).await;
+        assert!(listed.iter().any(|id| id == "team-fast"));
+        assert!(listed.iter().any(|id| id == "gpt-4o"));
+        assert!(!listed.iter().any(|id| id == "gpt-4-series"));
+        for id in &listed {
+            assert!(resolve_model(&state, id).await.is_ok(), "{} is listed but does not resolve", id);
+        }
+    }
+
+    #[test]
+    fn test_upstream_model_ids_keeps_chat_models() {
+        let body = json!({"models": {
+            "gemini-2.5-flash": {"quotaInfo": {"remainingFraction": 1.0}},
+            "claude-sonnet-4-5": {},
+            "chat_20706": {},
+            "rev19-uic3-1p": {}
+        }});
+        assert_eq!(upstream_model_ids(&body), vec!["claude-sonnet-4-5", "gemini-2.5-flash"]);
+        assert!(upstream_model_ids(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_anthropic_page() {
+        let all = ids(5);
+
+        let first = anthropic_page(&all, 2, None, None);
+        assert_eq!(first["data"].as_array().unwrap().len(), 2);
+        assert_eq!(first["first_id"], "m0");
+        assert_eq!(first["last_id"], "m1");
+        assert_eq!(first["has_more"], true);
+        assert_eq!(first["data"][0]["type"], "model");
+        assert_eq!(first["data"][0]["created_at"], "2023-11-14T22:13:20Z");
+
+        let last = anthropic_page(&all, 2, Some("m3"), None);
+        assert_eq!(last["first_id"], "m4");
+        assert_eq!(last["has_more"], false);
+
+        let before = anthropic_page(&all, 2, None, Some("m3"));
+        assert_eq!(before["first_id"], "m1");
+        assert_eq!(before["last_id"], "m2");
+        assert_eq!(before["has_more"], true);
+
+        let empty = anthropic_page(&all, 2, Some("unknown"), None);
+        assert!(empty["data"].as_array().unwrap().is_empty());
+        assert!(empty["first_id"].is_null());
+    }
+}
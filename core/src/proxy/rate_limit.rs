@@ -1,11 +1,40 @@
 //! Rate limit tracking
+//! Cooldowns are keyed by account and model family, since Cloud Code quotas are
+//! per model: an account out of Claude quota keeps serving Gemini traffic
 
 use dashmap::DashMap;
 use std::time::{Duration, Instant};
 
+use crate::proxy::upstream::retry::parse_retry_delay;
+
+/// Quota family of an upstream model. Models in one family share a cooldown.
+pub fn model_family(model: &str) -> String {
+    let model = model.to_lowercase();
+    let family = if model.contains("claude") {
+        "claude"
+    } else if model.contains("image") {
+        "gemini-image"
+    } else if model.contains("flash") {
+        "gemini-flash"
+    } else if model.contains("gemini") {
+        "gemini-pro"
+    } else {
+        return model;
+    };
+    family.to_string()
+}
+
+/// Active cooldown of one account for one model family
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FamilyLimit {
+    pub family: String,
+    pub reset_seconds: u64,
+    pub reason: String,
+}
+
 pub struct RateLimitTracker {
-    /// account_id -> (reset_time, reason)
-    limits: DashMap<String, (Instant, String)>,
+    /// (account_id, family) -> (reset_time, reason)
+    limits: DashMap<(String, String), (Instant, String)>,
 }
 
 impl RateLimitTracker {
@@ -15,26 +44,28 @@ impl RateLimitTracker {
         }
     }
     
-    /// Mark an account as rate limited
-    pub fn mark_limited(&self, account_id: &str, duration_secs: u64, reason: &str) {
+    /// Mark an account as rate limited for a model family
+    pub fn mark_limited(&self, account_id: &str, family: &str, duration_secs: u64, reason: &str) {
         let reset_time = Instant::now() + Duration::from_secs(duration_secs);
-        self.limits.insert(account_id.to_string(), (reset_time, reason.to_string()));
+        self.limits.insert((account_id.to_string(), family.to_string()), (reset_time, reason.to_string()));
     }
     
-    /// Parse rate limit from error response
+    /// Parse rate limit from error response.
+    /// Returns the cooldown in seconds, or `None` if the status does not limit the account.
     pub fn parse_from_error(
         &self,
         account_id: &str,
+        family: &str,
         status: u16,
         retry_after_header: Option<&str>,
         error_body: &str,
-    ) {
+    ) -> Option<u64> {
         // Default wait time based on status
         let mut wait_secs = match status {
             429 => 60,       // Too Many Requests
             503 => 30,       // Service Unavailable
             500..=599 => 10, // Other server errors
-            _ => return,     // Don't mark for other statuses
+            _ => return None, // Don't mark for other statuses
         };
         
         // Try to parse Retry-After header
@@ -44,63 +75,81 @@ impl RateLimitTracker {
             }
         }
         
-        // Try to parse Google's RetryInfo from error body
-        if error_body.contains("retryDelay") {
-            if let Some(delay) = Self::parse_retry_delay(error_body) {
-                wait_secs = delay;
-            }
+        // Google's RetryInfo / quotaResetDelay is more precise than the header
+        if let Some(delay_ms) = parse_retry_delay(error_body) {
+            wait_secs = delay_ms.div_ceil(1000).max(1);
         }
         
-        let reason = format!("HTTP {} - {}", status, &error_body[..error_body.len().min(200)]);
-        self.mark_limited(account_id, wait_secs, &reason);
+        // Truncate on a char boundary: upstream bodies may carry localized (multibyte) messages
+        let reason = format!("HTTP {} - {}", status, error_body.chars().take(200).collect::<String>());
+        self.mark_limited(account_id, family, wait_secs, &reason);
         
-        tracing::warn!("Account {} rate limited for {}s: {}", account_id, wait_secs, reason);
-    }
-    
-    /// Parse retryDelay from Google error response
-    fn parse_retry_delay(body: &str) -> Option<u64> {
-        // Look for patterns like "retryDelay": "60s" or "retry_delay": {"seconds": 60}
-        let re = regex::Regex::new(r#"(?:"retryDelay"|"retry_delay")\s*:\s*"?(\d+)"#).ok()?;
-        re.captures(body)
-            .and_then(|cap| cap.get(1))
-            .and_then(|m| m.as_str().parse().ok())
+        tracing::warn!("Account {} rate limited for {} for {}s: {}", account_id, family, wait_secs, reason);
+        Some(wait_secs)
     }
     
-    /// Check if account is currently rate limited
-    pub fn is_rate_limited(&self, account_id: &str) -> bool {
-        if let Some(entry) = self.limits.get(account_id) {
+    /// Check if account is currently rate limited for a model family
+    pub fn is_rate_limited(&self, account_id: &str, family: &str) -> bool {
+        let key = (account_id.to_string(), family.to_string());
+        if let Some(entry) = self.limits.get(&key) {
             if Instant::now() < entry.0 {
                 return true;
             }
             // Expired, remove it
             drop(entry);
-            self.limits.remove(account_id);
+            self.limits.remove(&key);
         }
         false
     }
     
-    /// Get remaining wait time in seconds
-    pub fn get_remaining_wait(&self, account_id: &str) -> u64 {
-        if let Some(entry) = self.limits.get(account_id) {
-            let remaining = entry.0.saturating_duration_since(Instant::now());
-            return remaining.as_secs();
-        }
-        0
+    /// Get remaining wait time in seconds for a model family
+    pub fn get_remaining_wait(&self, account_id: &str, family: &str) -> u64 {
+        self.get_reset_seconds(account_id, family).unwrap_or(0)
     }
     
     /// Get reset time in seconds (None if not limited)
-    pub fn get_reset_seconds(&self, account_id: &str) -> Option<u64> {
-        if let Some(entry) = self.limits.get(account_id) {
-            if Instant::now() < entry.0 {
-                return Some(entry.0.saturating_duration_since(Instant::now()).as_secs());
-            }
-        }
-        None
+    pub fn get_reset_seconds(&self, account_id: &str, family: &str) -> Option<u64> {
+        let entry = self.limits.get(&(account_id.to_string(), family.to_string()))?;
+        let now = Instant::now();
+        (now < entry.0).then(|| entry.0.saturating_duration_since(now).as_secs())
+    }
+    
+    /// Active limits of an account across all families
+    pub fn get_limits(&self, account_id: &str) -> Vec<FamilyLimit> {
+        let now = Instant::now();
+        let mut limits: Vec<FamilyLimit> = self
+            .limits
+            .iter()
+            .filter(|e| e.key().0 == account_id && now < e.value().0)
+            .map(|e| FamilyLimit {
+                family: e.key().1.clone(),
+                reset_seconds: e.value().0.saturating_duration_since(now).as_secs(),
+                reason: e.value().1.clone(),
+            })
+            .collect();
+        limits.sort_by(|a, b| a.family.cmp(&b.family));
+        limits
+    }
+    
+    /// Whether the account is limited for any family
+    pub fn is_limited_any(&self, account_id: &str) -> bool {
+        let now = Instant::now();
+        self.limits.iter().any(|e| e.key().0 == account_id && now < e.value().0)
     }
     
-    /// Clear rate limit for account
-    pub fn clear(&self, account_id: &str) -> bool {
-        self.limits.remove(account_id).is_some()
+    /// Clear rate limits for an account, for one family or all of them.
+    /// Returns whether an active limit was removed.
+    pub fn clear(&self, account_id: &str, family: Option<&str>) -> bool {
+        let now = Instant::now();
+        let mut cleared = false;
+        self.limits.retain(|(id, fam), (reset_time, _)| {
+            let matches = id == account_id && family.is_none_or(|f| f == fam);
+            if matches && now < *reset_time {
+                cleared = true;
+            }
+            !matches
+        });
+        cleared
     }
     
     /// Cleanup expired entries
@@ -124,3 +173,63 @@ impl Default for RateLimitTracker {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_from_error_uses_quota_reset_delay() {
+        let tracker = RateLimitTracker::new();
+        let body = r#"{"error":{"code":429,"details":[{
+            "@type":"type.googleapis.com/google.rpc.ErrorInfo",
+            "metadata":{"quotaResetDelay":"2h30m0.5s"}
+        }]}}"#;
+
+        assert_eq!(tracker.parse_from_error("a", "claude", 429, Some("60"), body), Some(9001));
+        assert!(tracker.is_rate_limited("a", "claude"));
+
+        // Header only, then status default
+        assert_eq!(tracker.parse_from_error("b", "claude", 429, Some("15"), "quota exceeded"), Some(15));
+        assert_eq!(tracker.parse_from_error("c", "claude", 503, None, ""), Some(30));
+        assert_eq!(tracker.parse_from_error("d", "claude", 400, None, body), None);
+        assert!(!tracker.is_rate_limited("d", "claude"));
+    }
+
+    #[test]
+    fn test_parse_from_error_truncates_multibyte_body() {
+        let tracker = RateLimitTracker::new();
+        // Byte 200 falls inside a 3-byte character
+        let body = format!("x{}", "配额".repeat(100));
+        assert_eq!(tracker.parse_from_error("a", "claude", 429, None, &body), Some(60));
+        let reason = &tracker.get_limits("a")[0].reason;
+        assert_eq!(reason.chars().count(), "HTTP 429 - ".len() + 200);
+        assert!(reason.ends_with('配'));
+    }
+
+    #[test]
+    fn test_limits_are_per_family() {
+        let tracker = RateLimitTracker::new();
+        tracker.mark_limited("a", &model_family("claude-opus-4-5-thinking"), 60, "HTTP 429");
+
+        assert!(tracker.is_rate_limited("a", &model_family("claude-sonnet-4-5")));
+        assert!(!tracker.is_rate_limited("a", &model_family("gemini-2.5-flash")));
+        assert!(tracker.is_limited_any("a"));
+        assert_eq!(tracker.get_limits("a")[0].family, "claude");
+
+        tracker.mark_limited("a", "gemini-flash", 60, "HTTP 429");
+        assert!(tracker.clear("a", Some("claude")));
+        assert!(!tracker.is_rate_limited("a", "claude"));
+        assert!(tracker.is_rate_limited("a", "gemini-flash"));
+        assert!(tracker.clear("a", None));
+        assert!(!tracker.is_limited_any("a"));
+    }
+
+    #[test]
+    fn test_model_family() {
+        assert_eq!(model_family("gemini-3-pro-image"), "gemini-image");
+        assert_eq!(model_family("gemini-3-pro-high"), "gemini-pro");
+        assert_eq!(model_family("gemini-2.5-flash-lite"), "gemini-flash");
+        assert_eq!(model_family("claude-sonnet-4-5"), "claude");
+    }
+}
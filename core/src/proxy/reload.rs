@@ -0,0 +1,281 @@
+//! Config hot reload
+//! Watches the config file (mtime polling) and SIGHUP, then swaps the reloadable
+//! sections — model mappings, `[auth]`, `[scheduling]`, `[timeouts]` and `[generation]` — into the running server
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::Mutex;
+
+use crate::config::{read_config_file, Config};
+use crate::proxy::server::{AppState, SecurityConfig};
+use crate::proxy::sticky_config::StickySessionConfig;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct ConfigReloader {
+    path: PathBuf,
+    state: AppState,
+    /// Last successfully applied file contents
+    current: Mutex<Config>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: PathBuf, state: AppState, loaded: Config) -> Self {
+        Self {
+            path,
+            state,
+            current: Mutex::new(loaded),
+        }
+    }
+
+    /// Re-read and validate the config file, then swap every section that changed.
+    ///
+    /// Only sections that differ from the previously loaded file are swapped, so
+    /// runtime edits made through the admin API survive unrelated reloads.
+    /// On error nothing is applied. Returns whether anything changed.
+    pub async fn reload(&self) -> anyhow::Result<bool> {
+        let new = read_config_file(&self.path)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {:?}: {}", self.path, e))?;
+        let security = SecurityConfig::from_auth_config(&new.auth)?;
+
+        let mut current = self.current.lock().await;
+        if *current == new {
+            return Ok(false);
+        }
+
+        let tables = [
+            ("custom", &current.model_mapping.custom, &new.model_mapping.custom, &self.state.custom_mapping),
+            ("openai", &current.model_mapping.openai, &new.model_mapping.openai, &self.state.openai_mapping),
+            ("anthropic", &current.model_mapping.anthropic, &new.model_mapping.anthropic, &self.state.anthropic_mapping),
+        ];
+        for (name, old_table, new_table, lock) in tables {
+            if old_table == new_table {
+                continue;
+            }
+            for line in diff_mapping(old_table, new_table) {
+                tracing::info!("[Reload] {} mapping: {}", name, line);
+            }
+            *lock.write().await = new_table.clone();
+        }
+
+        if current.auth != new.auth {
+            let old_names: Vec<&str> = current.auth.keys.iter().map(|k| k.name.as_str()).collect();
+            let new_names: Vec<&str> = new.auth.keys.iter().map(|k| k.name.as_str()).collect();
+            if current.auth.mode != new.auth.mode {
+                tracing::info!("[Reload] auth mode: {:?} -> {:?}", current.auth.mode, new.auth.mode);
+            }
+            for name in new_names.iter().filter(|n| !old_names.contains(n)) {
+                tracing::info!("[Reload] API key added: {}", name);
+            }
+            for name in old_names.iter().filter(|n| !new_names.contains(n)) {
+                tracing::info!("[Reload] API key removed: {}", name);
+            }
+            tracing::info!("[Reload] auth: {} API key(s) active", security.keys.len());
+            *self.state.security_config.write().await = security;
+        }
+
+        if current.scheduling != new.scheduling {
+            let sticky = StickySessionConfig::from(&new.scheduling);
+            tracing::info!(
+                "[Reload] scheduling: {:?} (max wait {}s) -> {:?} (max wait {}s)",
+                current.scheduling.mode,
+                current.scheduling.max_wait_seconds,
+                new.scheduling.mode,
+                new.scheduling.max_wait_seconds
+            );
+            if current.scheduling.mode != new.scheduling.mode {
+                self.state.token_manager.clear_all_sessions();
+            }
+            self.state.token_manager.update_sticky_config(sticky).await;
+        }
+
+        if current.timeouts != new.timeouts {
+            tracing::info!(
+                "[Reload] timeouts: request {}s, stream first byte {}s, stream idle {}s, {} model override(s)",
+                new.timeouts.request_timeout,
+                new.timeouts.stream_first_byte_timeout,
+                new.timeouts.stream_idle_timeout,
+                new.timeouts.models.len()
+            );
+            *self.state.timeouts.write().await = new.timeouts.clone();
+        }
+
+        if current.generation != new.generation {
+            tracing::info!(
+                "[Reload] generation: default stop sequences {:?}",
+                new.generation.default_stop_sequences
+            );
+            *self.state.generation.write().await = new.generation.clone();
+        }
+
+        let restart_sections: Vec<&str> = [
+            ("server", current.server != new.server),
+            ("accounts", current.accounts != new.accounts),
+            ("timeouts.connect_timeout", current.timeouts.connect_timeout != new.timeouts.connect_timeout),
+            ("logging", current.logging != new.logging),
+            ("metrics", current.metrics != new.metrics),
+        ]
+        .into_iter()
+        .filter_map(|(name, changed)| changed.then_some(name))
+        .collect();
+        if !restart_sections.is_empty() {
+            tracing::warn!(
+                "[Reload] Changes to [{}] require a restart and were not applied",
+                restart_sections.join("], [")
+            );
+        }
+
+        *current = new;
+        Ok(true)
+    }
+
+    /// Start the background task reloading on file change and SIGHUP
+    pub fn spawn(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut last_seen = file_signature(&self.path);
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            let mut hangup = HangupSignal::new();
+
+            tracing::info!("Watching {:?} for changes (send SIGHUP to force a reload)", self.path);
+
+            loop {
+                let trigger = tokio::select! {
+                    _ = interval.tick() => {
+                        let signature = file_signature(&self.path);
+                        if signature == last_seen || signature.is_none() {
+                            continue;
+                        }
+                        last_seen = signature;
+                        "file change"
+                    }
+                    _ = hangup.recv() => "SIGHUP",
+                };
+
+                match self.reload().await {
+                    Ok(true) => tracing::info!("Config reloaded from {:?} ({})", self.path, trigger),
+                    Ok(false) => tracing::debug!("Config unchanged after {}", trigger),
+                    Err(e) => tracing::error!("Config reload failed, keeping previous config: {}", e),
+                }
+            }
+        })
+    }
+}
+
+/// Human-readable diff of a mapping table, one line per changed entry
+fn diff_mapping(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (from, to) in new {
+        match old.get(from) {
+            None => lines.push(format!("+ {} -> {}", from, to)),
+            Some(prev) if prev != to => lines.push(format!("~ {} -> {} (was {})", from, to, prev)),
+            _ => {}
+        }
+    }
+    for (from, to) in old {
+        if !new.contains_key(from) {
+            lines.push(format!("- {} -> {}", from, to));
+        }
+    }
+    lines.sort_by(|a, b| a[2..].cmp(&b[2..]));
+    lines
+}
+
+/// (mtime, size) of the config file; `None` while it is missing (e.g. mid-rename)
+fn file_signature(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(unix)]
+struct HangupSignal(Option<tokio::signal::unix::Signal>);
+
+#[cfg(unix)]
+impl HangupSignal {
+    fn new() -> Self {
+        let signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup());
+        if let Err(e) = &signal {
+            tracing::warn!("Failed to install SIGHUP handler: {}", e);
+        }
+        Self(signal.ok())
+    }
+
+    async fn recv(&mut self) {
+        match &mut self.0 {
+            Some(signal) => {
+                signal.recv().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct HangupSignal;
+
+#[cfg(not(unix))]
+impl HangupSignal {
+    fn new() -> Self {
+        Self
+    }
+
+    async fn recv(&mut self) {
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_mapping() {
+        let old = HashMap::from([
+            ("gpt-4".to_string(), "gemini-2.5-pro".to_string()),
+            ("gpt-4o".to_string(), "gemini-2.5-flash".to_string()),
+        ]);
+        let new = HashMap::from([
+            ("gpt-4".to_string(), "gemini-3-pro-high".to_string()),
+            ("o1".to_string(), "claude-opus-4-5-thinking".to_string()),
+        ]);
+
+        assert_eq!(
+            diff_mapping(&old, &new),
+            vec![
+                "~ gpt-4 -> gemini-3-pro-high (was gemini-2.5-pro)",
+                "- gpt-4o -> gemini-2.5-flash",
+                "+ o1 -> claude-opus-4-5-thinking",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_mappings_and_keeps_old_on_error() {
+        let path = std::env::temp_dir().join(format!("antigravity-reload-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "[model_mapping.custom]\n\"my-model\" = \"gemini-2.5-flash\"\n").unwrap();
+        let loaded = read_config_file(&path).unwrap();
+        let state = AppState::for_tests(&loaded);
+        let reloader = ConfigReloader::new(path.clone(), state.clone(), loaded);
+
+        std::fs::write(&path, "[model_mapping.custom]\n\"my-model\" = \"gemini-3-pro-high\"\n").unwrap();
+        assert!(reloader.reload().await.unwrap());
+        assert_eq!(
+            state.custom_mapping.read().await.get("my-model").map(String::as_str),
+            Some("gemini-3-pro-high")
+        );
+
+        // Invalid TOML and invalid auth are both rejected without touching state
+        std::fs::write(&path, "[model_mapping.custom\n").unwrap();
+        assert!(reloader.reload().await.is_err());
+        std::fs::write(&path, "[[auth.keys]]\nname = \"ci\"\nkey = \"\"\n").unwrap();
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(
+            state.custom_mapping.read().await.get("my-model").map(String::as_str),
+            Some("gemini-3-pro-high")
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,174 @@
+//! Conversation store for the OpenAI Responses API
+//! Keeps each response's turn (new input and assistant output) linked to its
+//! `previous_response_id`, so a conversation is rebuilt by walking the chain.
+//! Bounded by entry count, total bytes and age: the oldest responses are evicted first.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// Responses kept before the oldest are evicted
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Serialized size of all stored turns before the oldest are evicted
+pub const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// How long a response can be continued
+pub const DEFAULT_TTL: Duration = Duration::from_secs(6 * 3600);
+
+struct StoredResponse {
+    /// Name of the API key that created the response (`None` when auth is off)
+    owner: Option<String>,
+    /// Response this one continued, if any
+    previous: Option<String>,
+    /// Chat-completions style messages of this turn only: new input and the assistant output
+    messages: Vec<Value>,
+    bytes: usize,
+    stored_at: Instant,
+}
+
+pub struct ResponseStore {
+    capacity: usize,
+    max_bytes: usize,
+    ttl: Duration,
+    inner: Mutex<StoreInner>,
+}
+
+#[derive(Default)]
+struct StoreInner {
+    entries: HashMap<String, StoredResponse>,
+    /// Insertion order, oldest first
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl StoreInner {
+    fn evict_oldest(&mut self) {
+        if let Some(oldest) = self.order.pop_front() {
+            if let Some(stored) = self.entries.remove(&oldest) {
+                self.bytes -= stored.bytes;
+            }
+        }
+    }
+}
+
+impl ResponseStore {
+    pub fn new(capacity: usize, max_bytes: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            max_bytes,
+            ttl,
+            inner: Mutex::new(StoreInner::default()),
+        }
+    }
+
+    /// Store the turn of response `id`, continuing `previous` when set
+    pub fn insert(&self, id: &str, owner: Option<&str>, previous: Option<&str>, messages: Vec<Value>) {
+        let bytes = messages.iter().map(|m| m.to_string().len()).sum();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let stored = StoredResponse {
+            owner: owner.map(str::to_string),
+            previous: previous.map(str::to_string),
+            messages,
+            bytes,
+            stored_at: Instant::now(),
+        };
+        inner.bytes += bytes;
+        match inner.entries.insert(id.to_string(), stored) {
+            Some(replaced) => inner.bytes -= replaced.bytes,
+            None => inner.order.push_back(id.to_string()),
+        }
+
+        while let Some(oldest) = inner.order.front() {
+            let expired = inner.entries.get(oldest).is_none_or(|s| s.stored_at.elapsed() >= self.ttl);
+            if !expired && inner.order.len() <= self.capacity && inner.bytes <= self.max_bytes {
+                break;
+            }
+            inner.evict_oldest();
+        }
+    }
+
+    /// Conversation ending with response `id`, visible only to the key that created it.
+    /// `None` if any response in the chain has expired or been evicted
+    pub fn get(&self, id: &str, owner: Option<&str>) -> Option<Vec<Value>> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut turns = Vec::new();
+        let mut next = Some(id);
+        while let Some(id) = next {
+            let stored = inner
+                .entries
+                .get(id)
+                .filter(|s| s.owner.as_deref() == owner && s.stored_at.elapsed() < self.ttl)?;
+            turns.push(&stored.messages);
+            next = stored.previous.as_deref();
+        }
+        Some(turns.into_iter().rev().flatten().cloned().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ResponseStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_MAX_BYTES, DEFAULT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_store_is_bounded_and_scoped_to_owner() {
+        let store = ResponseStore::new(2, DEFAULT_MAX_BYTES, DEFAULT_TTL);
+        store.insert("resp_1", Some("ci"), None, vec![json!({"role": "user", "content": "1"})]);
+        store.insert("resp_2", Some("ci"), None, vec![]);
+        store.insert("resp_3", None, None, vec![]);
+
+        assert_eq!(store.len(), 2);
+        assert!(store.get("resp_1", Some("ci")).is_none());
+        assert!(store.get("resp_2", Some("ci")).is_some());
+        assert!(store.get("resp_2", Some("alice")).is_none());
+        assert!(store.get("resp_3", None).is_some());
+    }
+
+    #[test]
+    fn test_store_chains_turns() {
+        let store = ResponseStore::default();
+        store.insert("resp_1", None, None, vec![json!({"role": "user", "content": "1"}), json!({"role": "assistant", "content": "a"})]);
+        store.insert("resp_2", None, Some("resp_1"), vec![json!({"role": "user", "content": "2"}), json!({"role": "assistant", "content": "b"})]);
+
+        let conversation = store.get("resp_2", None).unwrap();
+        let contents: Vec<&str> = conversation.iter().map(|m| m["content"].as_str().unwrap()).collect();
+        assert_eq!(contents, ["1", "a", "2", "b"]);
+
+        // A broken chain cannot be continued
+        store.insert("resp_3", None, Some("resp_gone"), vec![]);
+        assert!(store.get("resp_3", None).is_none());
+    }
+
+    #[test]
+    fn test_store_is_bounded_by_bytes_and_age() {
+        let turn = || vec![json!({"role": "user", "content": "x".repeat(100)})];
+        let store = ResponseStore::new(100, 300, DEFAULT_TTL);
+        store.insert("resp_1", None, None, turn());
+        store.insert("resp_2", None, None, turn());
+        store.insert("resp_3", None, None, turn());
+        assert_eq!(store.len(), 2);
+        assert!(store.get("resp_1", None).is_none());
+
+        let store = ResponseStore::new(100, DEFAULT_MAX_BYTES, Duration::ZERO);
+        store.insert("resp_1", None, None, turn());
+        assert!(store.get("resp_1", None).is_none());
+        assert!(store.is_empty());
+    }
+}
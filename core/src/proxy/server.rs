@@ -8,6 +8,7 @@ use tokio::sync::RwLock;
 use axum::{
     extract::DefaultBodyLimit,
     http::StatusCode,
+    middleware,
     response::{IntoResponse, Json, Response},
     routing::{any, get, post},
     Router,
@@ -16,7 +17,12 @@ use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
 use crate::proxy::TokenManager;
-use crate::config::AuthMode;
+use crate::proxy::middleware::auth::{constant_time_eq, ClientKey};
+use crate::proxy::usage::UsageTracker;
+use crate::proxy::responses_store::ResponseStore;
+use crate::proxy::media::MediaFetcher;
+use crate::proxy::model_catalog::ModelCatalog;
+use crate::config::{AuthConfig, AuthMode, GenerationConfig, TimeoutsConfig};
 
 /// Application state shared across handlers
 #[derive(Clone)]
@@ -26,14 +32,105 @@ pub struct AppState {
     pub anthropic_mapping: Arc<RwLock<HashMap<String, String>>>,
     pub openai_mapping: Arc<RwLock<HashMap<String, String>>>,
     pub custom_mapping: Arc<RwLock<HashMap<String, String>>>,
-    pub request_timeout: u64,
+    pub timeouts: Arc<RwLock<TimeoutsConfig>>,
     pub security_config: Arc<RwLock<SecurityConfig>>,
+    pub usage: Arc<UsageTracker>,
+    /// Conversations behind `/v1/responses` ids, for `previous_response_id`
+    pub responses: Arc<ResponseStore>,
+    /// Downloads remote image/document URLs for the mappers
+    pub media: Arc<MediaFetcher>,
+    /// Model ids served by `/v1/models`, with the upstream listing cached
+    pub models: Arc<ModelCatalog>,
+    pub generation: Arc<RwLock<GenerationConfig>>,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State built from `config` with an empty account pool in a fresh temp dir
+    pub(crate) fn for_tests(config: &crate::config::Config) -> Self {
+        let data_dir = std::env::temp_dir().join(format!("antigravity-test-{}", uuid::Uuid::new_v4()));
+        AppState {
+            token_manager: Arc::new(TokenManager::new(data_dir)),
+            upstream: Arc::new(crate::proxy::upstream::client::UpstreamClient::new(None)),
+            anthropic_mapping: Arc::new(RwLock::new(config.model_mapping.anthropic.clone())),
+            openai_mapping: Arc::new(RwLock::new(config.model_mapping.openai.clone())),
+            custom_mapping: Arc::new(RwLock::new(config.model_mapping.custom.clone())),
+            timeouts: Arc::new(RwLock::new(config.timeouts.clone())),
+            security_config: Arc::new(RwLock::new(SecurityConfig::from_auth_config(&config.auth).unwrap())),
+            usage: Arc::new(UsageTracker::new()),
+            responses: Arc::new(ResponseStore::default()),
+            media: Arc::new(MediaFetcher::new(config.media.clone())),
+            models: Arc::new(ModelCatalog::new()),
+            generation: Arc::new(RwLock::new(config.generation.clone())),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct SecurityConfig {
     pub auth_mode: AuthMode,
-    pub api_key: String,
+    pub keys: Vec<ClientKey>,
+}
+
+impl SecurityConfig {
+    /// Build from `[auth]`, validating expiry timestamps and key uniqueness
+    pub fn from_auth_config(auth: &AuthConfig) -> anyhow::Result<Self> {
+        let mut keys = Vec::new();
+
+        if !auth.api_key.is_empty() {
+            keys.push(ClientKey {
+                name: "default".to_string(),
+                key: auth.api_key.clone(),
+                expires_at: None,
+                allowed_models: Vec::new(),
+                allowed_accounts: None,
+                // The shared proxy key never grants the admin API; that needs an explicit `admin = true` entry
+                admin: false,
+            });
+        }
+
+        for entry in &auth.keys {
+            if entry.key.is_empty() {
+                anyhow::bail!("API key '{}' has an empty key", entry.name);
+            }
+            if keys.iter().any(|k| k.name == entry.name) {
+                anyhow::bail!("Duplicate API key name '{}'", entry.name);
+            }
+            if keys.iter().any(|k| k.key == entry.key) {
+                anyhow::bail!("API key '{}' reuses a key that is already configured", entry.name);
+            }
+
+            let expires_at = match &entry.expires_at {
+                Some(ts) => Some(
+                    chrono::DateTime::parse_from_rfc3339(ts)
+                        .map_err(|e| anyhow::anyhow!("Invalid expires_at for API key '{}': {}", entry.name, e))?
+                        .with_timezone(&chrono::Utc),
+                ),
+                None => None,
+            };
+
+            keys.push(ClientKey {
+                name: entry.name.clone(),
+                key: entry.key.clone(),
+                expires_at,
+                allowed_models: entry.allowed_models.clone(),
+                allowed_accounts: entry.accounts.clone(),
+                admin: entry.admin,
+            });
+        }
+
+        Ok(Self {
+            auth_mode: auth.mode.clone(),
+            keys,
+        })
+    }
+
+    /// Find the configured key matching the provided secret
+    pub fn find_key(&self, provided: &str) -> Option<&ClientKey> {
+        self.keys
+            .iter()
+            .find(|k| constant_time_eq(provided.as_bytes(), k.key.as_bytes()))
+    }
 }
 
 /// Proxy server instance
@@ -41,9 +138,11 @@ pub struct ProxyServer {
     host: String,
     port: u16,
     state: AppState,
+    metrics_enabled: bool,
 }
 
 impl ProxyServer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         host: String,
         port: u16,
@@ -51,11 +150,23 @@ impl ProxyServer {
         anthropic_mapping: HashMap<String, String>,
         openai_mapping: HashMap<String, String>,
         custom_mapping: HashMap<String, String>,
-        request_timeout: u64,
-        auth_mode: AuthMode,
-        api_key: String,
+        timeouts: TimeoutsConfig,
+        security_config: SecurityConfig,
+        metrics_config: crate::config::MetricsConfig,
+        media_config: crate::config::MediaConfig,
+        generation: GenerationConfig,
     ) -> Self {
-        let upstream = Arc::new(crate::proxy::upstream::client::UpstreamClient::new(None));
+        let upstream = Arc::new(crate::proxy::upstream::client::UpstreamClient::with_connect_timeout(
+            None,
+            std::time::Duration::from_secs(timeouts.connect_timeout),
+        ));
+        
+        if security_config.auth_mode != AuthMode::Off && security_config.keys.is_empty() {
+            tracing::warn!(
+                "Auth mode is {:?} but no API keys are configured; all authenticated requests will be rejected",
+                security_config.auth_mode
+            );
+        }
         
         let state = AppState {
             token_manager,
@@ -63,14 +174,23 @@ impl ProxyServer {
             anthropic_mapping: Arc::new(RwLock::new(anthropic_mapping)),
             openai_mapping: Arc::new(RwLock::new(openai_mapping)),
             custom_mapping: Arc::new(RwLock::new(custom_mapping)),
-            request_timeout,
-            security_config: Arc::new(RwLock::new(SecurityConfig {
-                auth_mode,
-                api_key,
-            })),
+            timeouts: Arc::new(RwLock::new(timeouts)),
+            security_config: Arc::new(RwLock::new(security_config)),
+            usage: Arc::new(UsageTracker::new()),
+            responses: Arc::new(ResponseStore::default()),
+            media: Arc::new(MediaFetcher::new(media_config)),
+            models: Arc::new(ModelCatalog::new()),
+            generation: Arc::new(RwLock::new(generation)),
         };
         
-        Self { host, port, state }
+        crate::proxy::metrics::metrics().set_hash_accounts(metrics_config.hash_account_emails);
+        
+        Self { host, port, state, metrics_enabled: metrics_config.enabled }
+    }
+    
+    /// Hot-reload `path` on change or SIGHUP; `loaded` is the config the server was built from
+    pub fn watch_config(&self, path: std::path::PathBuf, loaded: crate::config::Config) {
+        Arc::new(crate::proxy::reload::ConfigReloader::new(path, self.state.clone(), loaded)).spawn();
     }
     
     /// Run the proxy server (blocking)
@@ -80,7 +200,7 @@ impl ProxyServer {
             .allow_methods(Any)
             .allow_headers(Any);
         
-        let app = Router::new()
+        let mut app = Router::new()
             // Health check
             .route("/healthz", get(health_check_handler))
             .route("/health", get(health_check_handler))
@@ -88,15 +208,32 @@ impl ProxyServer {
             // OpenAI-compatible endpoints
             .route("/v1/chat/completions", post(crate::proxy::handlers::openai::handle_chat_completions))
             .route("/v1/completions", post(crate::proxy::handlers::openai::handle_completions))
-            .route("/v1/models", get(crate::proxy::handlers::openai::handle_list_models))
+            .route("/v1/models", get(crate::proxy::handlers::models::handle_list_models))
+            .route("/v1/models/:model_id", get(crate::proxy::handlers::models::handle_get_model))
             .route("/v1/images/generations", post(crate::proxy::handlers::openai::handle_images_generations))
+            .route("/v1/embeddings", post(crate::proxy::handlers::openai::handle_embeddings))
+            .route("/v1/responses", post(crate::proxy::handlers::responses::handle_responses))
             
             // Claude/Anthropic-compatible endpoints
             .route("/v1/messages", post(crate::proxy::handlers::claude::handle_messages))
+            .route("/v1/messages/count_tokens", post(crate::proxy::handlers::claude::handle_count_tokens))
             
             // Gemini endpoints
             .route("/v1beta/models/:model_action", any(crate::proxy::handlers::gemini::handle_gemini_request))
             
+            // Admin API (requires an admin key regardless of auth mode)
+            .nest("/admin", crate::proxy::handlers::admin::router());
+        
+        if self.metrics_enabled {
+            app = app.route("/metrics", get(metrics_handler));
+        }
+        
+        let app = app
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                crate::proxy::middleware::auth::auth_middleware,
+            ))
+            .layer(middleware::from_fn(crate::proxy::middleware::metrics::metrics_middleware))
             .layer(DefaultBodyLimit::max(100 * 1024 * 1024)) // 100MB
             .layer(cors)
             .layer(TraceLayer::new_for_http())
@@ -122,6 +259,20 @@ async fn health_check_handler() -> Response {
     (StatusCode::OK, Json(serde_json::json!({"status": "ok"}))).into_response()
 }
 
+/// Prometheus scrape handler
+async fn metrics_handler(axum::extract::State(state): axum::extract::State<AppState>) -> Response {
+    let pool = crate::proxy::metrics::PoolGauges {
+        accounts_total: state.token_manager.len(),
+        accounts_rate_limited: state.token_manager.rate_limited_count(),
+    };
+    (
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::proxy::metrics::metrics().render(&pool),
+    )
+        .into_response()
+}
+
 /// Shutdown signal handler
 async fn shutdown_signal() {
     let ctrl_c = async {
@@ -1,47 +1,149 @@
 //! Session Manager for extracting session fingerprints
 //! Used for sticky session routing
 
+use axum::http::HeaderMap;
 use sha2::{Sha256, Digest};
 use crate::proxy::mappers::claude::models::ClaudeRequest;
+use crate::proxy::mappers::openai::models::{OpenAIChatRequest, OpenAIRole};
+
+/// Header a client can set to pin a conversation explicitly
+pub const SESSION_HEADER: &str = "x-session-id";
+
+/// Longer explicit ids are hashed so the session table stays small
+const MAX_SESSION_ID_LEN: usize = 128;
 
 pub struct SessionManager;
 
 impl SessionManager {
+    /// Session for a Claude request: `x-session-id`, then `metadata.user_id`, then a fingerprint
+    pub fn claude_session_id(headers: &HeaderMap, request: &ClaudeRequest) -> String {
+        Self::header_session_id(headers).unwrap_or_else(|| Self::extract_session_id(request))
+    }
+
+    /// Session for an OpenAI request: `x-session-id`, then `user`, then a fingerprint
+    pub fn openai_session_id(headers: &HeaderMap, request: &OpenAIChatRequest) -> String {
+        Self::header_session_id(headers).unwrap_or_else(|| Self::extract_openai_session_id(request))
+    }
+
+    /// Explicit session id from the `x-session-id` header
+    pub fn header_session_id(headers: &HeaderMap) -> Option<String> {
+        let value = headers.get(SESSION_HEADER)?.to_str().ok()?.trim();
+        match value.len() {
+            0 => None,
+            n if n > MAX_SESSION_ID_LEN => Some(fingerprint(&[value])),
+            _ => Some(value.to_string()),
+        }
+    }
+
     /// Extract a session ID from Claude request for sticky routing
     pub fn extract_session_id(request: &ClaudeRequest) -> String {
         // Use metadata.user_id if available
         if let Some(metadata) = &request.metadata {
-            if let Some(user_id) = &metadata.user_id {
-                return user_id.clone();
+            if let Some(user_id) = metadata.user_id.as_deref().filter(|u| !u.is_empty()) {
+                return user_id.to_string();
             }
         }
-        
-        // Otherwise, generate a fingerprint from the request content
-        let mut hasher = Sha256::new();
-        
-        // Hash the model
-        hasher.update(request.model.as_bytes());
-        
-        // Hash first message content (if any)
-        if let Some(first_msg) = request.messages.first() {
-            let content_str = match &first_msg.content {
-                crate::proxy::mappers::claude::models::MessageContent::String(s) => s.clone(),
-                crate::proxy::mappers::claude::models::MessageContent::Array(arr) => {
-                    arr.iter()
-                        .filter_map(|b| {
-                            match b {
-                                crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
-                                _ => None,
-                            }
-                        })
-                        .collect::<Vec<_>>()
-                        .join("")
-                }
-            };
-            hasher.update(content_str.as_bytes());
+
+        // Otherwise, generate a fingerprint from the model and the first message
+        let first_message = request.messages.first().map(|first_msg| match &first_msg.content {
+            crate::proxy::mappers::claude::models::MessageContent::String(s) => s.clone(),
+            crate::proxy::mappers::claude::models::MessageContent::Array(arr) => {
+                arr.iter()
+                    .filter_map(|b| {
+                        match b {
+                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
+                            _ => None,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("")
+            }
+        });
+        fingerprint(&[&request.model, first_message.as_deref().unwrap_or_default()])
+    }
+
+    /// Extract a session ID from an OpenAI chat request for sticky routing
+    pub fn extract_openai_session_id(request: &OpenAIChatRequest) -> String {
+        if let Some(user) = request.user.as_deref().filter(|u| !u.is_empty()) {
+            return user.to_string();
         }
-        
-        let result = hasher.finalize();
-        format!("{:x}", result)[..16].to_string()
+
+        // System prompts are shared across conversations; the first user turn is not
+        let first_message = request
+            .messages
+            .iter()
+            .find(|m| !matches!(m.role, OpenAIRole::System | OpenAIRole::Developer))
+            .and_then(|m| m.content.as_ref())
+            .map(|c| c.text());
+        fingerprint(&[&request.model, first_message.as_deref().unwrap_or_default()])
+    }
+}
+
+/// Short stable hash of the given fields
+fn fingerprint(fields: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for field in fields {
+        hasher.update(field.as_bytes());
+    }
+    let result = hasher.finalize();
+    format!("{:x}", result)[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claude(body: serde_json::Value) -> ClaudeRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn openai(body: serde_json::Value) -> OpenAIChatRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_claude_session_sources() {
+        let mut headers = HeaderMap::new();
+        let with_user = claude(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "metadata": {"user_id": "user_abc_session_1"}
+        }));
+        assert_eq!(SessionManager::claude_session_id(&headers, &with_user), "user_abc_session_1");
+
+        headers.insert(SESSION_HEADER, " conv-42 ".parse().unwrap());
+        assert_eq!(SessionManager::claude_session_id(&headers, &with_user), "conv-42");
+
+        // Same first turn, same session; later turns do not change it
+        let first = claude(json!({"model": "claude-sonnet-4-5", "messages": [{"role": "user", "content": "Hi"}]}));
+        let later = claude(json!({"model": "claude-sonnet-4-5", "messages": [
+            {"role": "user", "content": "Hi"},
+            {"role": "assistant", "content": "Hello"},
+            {"role": "user", "content": "More"}
+        ]}));
+        assert_eq!(SessionManager::extract_session_id(&first), SessionManager::extract_session_id(&later));
+        assert_eq!(SessionManager::extract_session_id(&first).len(), 16);
+    }
+
+    #[test]
+    fn test_openai_session_sources() {
+        let headers = HeaderMap::new();
+        let with_user = openai(json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}], "user": "u-1"}));
+        assert_eq!(SessionManager::openai_session_id(&headers, &with_user), "u-1");
+
+        let a = openai(json!({"model": "gpt-4o", "messages": [
+            {"role": "system", "content": "Be brief"},
+            {"role": "user", "content": "Plan a trip"}
+        ]}));
+        let b = openai(json!({"model": "gpt-4o", "messages": [
+            {"role": "system", "content": "Be brief"},
+            {"role": "user", "content": "Write a poem"}
+        ]}));
+        assert_ne!(SessionManager::extract_openai_session_id(&a), SessionManager::extract_openai_session_id(&b));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(SESSION_HEADER, "x".repeat(200).parse().unwrap());
+        assert_eq!(SessionManager::header_session_id(&headers).unwrap().len(), 16);
     }
 }
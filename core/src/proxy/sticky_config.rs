@@ -39,3 +39,17 @@ impl Default for StickySessionConfig {
 fn default_max_wait_seconds() -> u64 {
     30
 }
+
+impl From<&crate::config::SchedulingConfig> for StickySessionConfig {
+    fn from(config: &crate::config::SchedulingConfig) -> Self {
+        use crate::config::SchedulingMode as ConfigMode;
+        Self {
+            mode: match config.mode {
+                ConfigMode::PerformanceFirst => SchedulingMode::PerformanceFirst,
+                ConfigMode::Balance => SchedulingMode::Balance,
+                ConfigMode::CacheFirst => SchedulingMode::CacheFirst,
+            },
+            max_wait_seconds: config.max_wait_seconds,
+        }
+    }
+}
@@ -6,10 +6,42 @@ use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::metrics::metrics;
+use crate::proxy::rate_limit::{model_family, FamilyLimit, RateLimitTracker};
 use crate::proxy::sticky_config::{StickySessionConfig, SchedulingMode};
 
+/// Sticky-session bindings idle for longer than this are forgotten
+const SESSION_TTL: Duration = Duration::from_secs(3600);
+
+/// Upper bound on remembered sticky sessions; the least recently used go first
+const MAX_SESSION_BINDINGS: usize = 10_000;
+
+/// Errors from account pool changes made through the admin API
+#[derive(Debug, thiserror::Error)]
+pub enum AccountError {
+    #[error("Invalid account id: {0}")]
+    InvalidId(String),
+    #[error("Account not found: {0}")]
+    NotFound(String),
+    #[error("Account {0} is disabled in its account file")]
+    DisabledInFile(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Account ids are file stems under `accounts/`: no separators, no leading dot
+fn is_account_file_stem(account_id: &str) -> bool {
+    !account_id.is_empty()
+        && !account_id.starts_with('.')
+        && account_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 #[derive(Debug, Clone)]
 pub struct ProxyToken {
     pub account_id: String,
@@ -23,6 +55,19 @@ pub struct ProxyToken {
     pub subscription_tier: Option<String>,
 }
 
+/// Snapshot of a pool account for the admin API
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AccountStatus {
+    pub account_id: String,
+    pub email: String,
+    pub subscription_tier: Option<String>,
+    pub project_id: Option<String>,
+    pub disabled: bool,
+    /// Limited for at least one model family
+    pub rate_limited: bool,
+    pub rate_limits: Vec<FamilyLimit>,
+}
+
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,
     current_index: Arc<AtomicUsize>,
@@ -30,7 +75,9 @@ pub struct TokenManager {
     data_dir: PathBuf,
     rate_limit_tracker: Arc<RateLimitTracker>,
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>,
-    session_accounts: Arc<DashMap<String, String>>,
+    /// Session id -> (account id, last used)
+    session_accounts: Arc<DashMap<String, (String, Instant)>>,
+    session_ttl: Duration,
 }
 
 impl TokenManager {
@@ -43,6 +90,7 @@ impl TokenManager {
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
+            session_ttl: SESSION_TTL,
         }
     }
     
@@ -147,20 +195,34 @@ impl TokenManager {
         }))
     }
     
-    /// Get a token for use (with load balancing and sticky sessions)
+    /// Get a token for use (with load balancing and sticky sessions).
+    /// Returns `(access_token, project_id, email, account_id)`.
+    ///
+    /// Accounts are skipped only while rate limited for the family of `model`.
+    /// `allowed_accounts` restricts the pool to the given account ids or emails
+    /// (used by API keys bound to a subset of accounts).
     pub async fn get_token(
         &self,
         quota_group: &str,
+        model: &str,
         force_rotate: bool,
         session_id: Option<&str>,
-    ) -> anyhow::Result<(String, String, String)> {
+        allowed_accounts: Option<&[String]>,
+    ) -> anyhow::Result<(String, String, String, String)> {
         let mut tokens_snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
-        let total = tokens_snapshot.len();
         
-        if total == 0 {
+        if tokens_snapshot.is_empty() {
             anyhow::bail!("Token pool is empty");
         }
         
+        if let Some(allowed) = allowed_accounts {
+            tokens_snapshot.retain(|t| allowed.iter().any(|a| *a == t.account_id || *a == t.email));
+            if tokens_snapshot.is_empty() {
+                anyhow::bail!("No accounts in the pool are available to this API key");
+            }
+        }
+        let total = tokens_snapshot.len();
+        
         // Sort by subscription tier priority
         tokens_snapshot.sort_by(|a, b| {
             let tier_priority = |tier: &Option<String>| match tier.as_deref() {
@@ -172,6 +234,7 @@ impl TokenManager {
             tier_priority(&a.subscription_tier).cmp(&tier_priority(&b.subscription_tier))
         });
         
+        let family = model_family(model);
         let scheduling = self.sticky_config.read().await.clone();
         let mut attempted: HashSet<String> = HashSet::new();
         let mut last_error: Option<String> = None;
@@ -184,8 +247,8 @@ impl TokenManager {
             if !rotate && session_id.is_some() && scheduling.mode != SchedulingMode::PerformanceFirst {
                 let sid = session_id.unwrap();
                 
-                if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
-                    let reset_sec = self.rate_limit_tracker.get_remaining_wait(&bound_id);
+                if let Some(bound_id) = self.bound_account(sid) {
+                    let reset_sec = self.rate_limit_tracker.get_remaining_wait(&bound_id, &family);
                     if reset_sec > 0 {
                         if scheduling.mode == SchedulingMode::CacheFirst && reset_sec <= scheduling.max_wait_seconds {
                             tokio::time::sleep(std::time::Duration::from_secs(reset_sec)).await;
@@ -208,7 +271,10 @@ impl TokenManager {
                 let mut last_used = self.last_used_account.lock().await;
                 
                 if let Some((account_id, last_time)) = &*last_used {
-                    if last_time.elapsed().as_secs() < 60 && !attempted.contains(account_id) {
+                    if last_time.elapsed().as_secs() < 60
+                        && !attempted.contains(account_id)
+                        && !self.rate_limit_tracker.is_rate_limited(account_id, &family)
+                    {
                         if let Some(found) = tokens_snapshot.iter().find(|t| &t.account_id == account_id) {
                             target_token = Some(found.clone());
                         }
@@ -223,17 +289,11 @@ impl TokenManager {
                         if attempted.contains(&candidate.account_id) {
                             continue;
                         }
-                        if self.rate_limit_tracker.is_rate_limited(&candidate.account_id) {
+                        if self.rate_limit_tracker.is_rate_limited(&candidate.account_id, &family) {
                             continue;
                         }
                         target_token = Some(candidate.clone());
                         *last_used = Some((candidate.account_id.clone(), std::time::Instant::now()));
-                        
-                        if let Some(sid) = session_id {
-                            if scheduling.mode != SchedulingMode::PerformanceFirst {
-                                self.session_accounts.insert(sid.to_string(), candidate.account_id.clone());
-                            }
-                        }
                         break;
                     }
                 }
@@ -245,7 +305,7 @@ impl TokenManager {
                     if attempted.contains(&candidate.account_id) {
                         continue;
                     }
-                    if self.rate_limit_tracker.is_rate_limited(&candidate.account_id) {
+                    if self.rate_limit_tracker.is_rate_limited(&candidate.account_id, &family) {
                         continue;
                     }
                     target_token = Some(candidate.clone());
@@ -257,19 +317,21 @@ impl TokenManager {
                 Some(t) => t,
                 None => {
                     let min_wait = tokens_snapshot.iter()
-                        .filter_map(|t| self.rate_limit_tracker.get_reset_seconds(&t.account_id))
+                        .filter_map(|t| self.rate_limit_tracker.get_reset_seconds(&t.account_id, &family))
                         .min()
                         .unwrap_or(60);
-                    anyhow::bail!("All accounts are currently limited. Please wait {}s.", min_wait);
+                    anyhow::bail!("All accounts are currently limited for {}. Please wait {}s.", family, min_wait);
                 }
             };
-            
+
             // Check token expiry (refresh if < 5 minutes remaining)
             let now = chrono::Utc::now().timestamp();
             if now >= token.timestamp - 300 {
                 tracing::debug!("Token for {} expiring soon, refreshing...", token.email);
                 
-                match crate::oauth::refresh_access_token(&token.refresh_token).await {
+                let refreshed = crate::oauth::refresh_access_token(&token.refresh_token).await;
+                metrics().record_token_refresh(&token.email, refreshed.is_ok());
+                match refreshed {
                     Ok(response) => {
                         token.access_token = response.access_token.clone();
                         token.expires_in = response.expires_in;
@@ -319,12 +381,52 @@ impl TokenManager {
                 }
             };
             
-            return Ok((token.access_token, project_id, token.email));
+            // Bind the session only once the account has a usable token and project
+            // (whichever account serves it, including the 60s lock and retries)
+            if let Some(sid) = session_id {
+                if scheduling.mode != SchedulingMode::PerformanceFirst {
+                    self.bind_session(sid, &token.account_id);
+                }
+            }
+            
+            return Ok((token.access_token, project_id, token.email, token.account_id));
         }
         
         Err(anyhow::anyhow!(last_error.unwrap_or_else(|| "All accounts failed".to_string())))
     }
     
+    /// Account a session is bound to, unless the binding has gone idle
+    fn bound_account(&self, session_id: &str) -> Option<String> {
+        let (account_id, last_used) = self.session_accounts.get(session_id).map(|v| v.clone())?;
+        if last_used.elapsed() >= self.session_ttl {
+            self.session_accounts.remove(session_id);
+            return None;
+        }
+        Some(account_id)
+    }
+    
+    fn bind_session(&self, session_id: &str, account_id: &str) {
+        self.session_accounts.insert(session_id.to_string(), (account_id.to_string(), Instant::now()));
+        if self.session_accounts.len() > MAX_SESSION_BINDINGS {
+            self.prune_sessions(MAX_SESSION_BINDINGS);
+        }
+    }
+    
+    /// Drop idle bindings, then the least recently used until at most `max` remain
+    fn prune_sessions(&self, max: usize) {
+        self.session_accounts.retain(|_, (_, last_used)| last_used.elapsed() < self.session_ttl);
+        let excess = self.session_accounts.len().saturating_sub(max);
+        if excess == 0 {
+            return;
+        }
+        let mut by_age: Vec<(String, Instant)> =
+            self.session_accounts.iter().map(|e| (e.key().clone(), e.value().1)).collect();
+        by_age.sort_by_key(|(_, last_used)| *last_used);
+        for (session_id, _) in by_age.into_iter().take(excess) {
+            self.session_accounts.remove(&session_id);
+        }
+    }
+    
     async fn save_refreshed_token(&self, token: &ProxyToken) -> anyhow::Result<()> {
         let mut content: serde_json::Value = serde_json::from_str(
             &std::fs::read_to_string(&token.account_path)?
@@ -360,19 +462,32 @@ impl TokenManager {
         self.tokens.is_empty()
     }
     
-    /// Mark account as rate limited
+    /// Mark account as rate limited for the family of `model`
     pub fn mark_rate_limited(
         &self,
         account_id: &str,
+        model: &str,
         status: u16,
         retry_after_header: Option<&str>,
         error_body: &str,
     ) {
-        self.rate_limit_tracker.parse_from_error(account_id, status, retry_after_header, error_body);
+        let family = model_family(model);
+        if self.rate_limit_tracker.parse_from_error(account_id, &family, status, retry_after_header, error_body).is_some() {
+            let email = self.tokens.get(account_id).map(|t| t.email.clone()).unwrap_or_default();
+            metrics().record_rate_limit(&email, status);
+        }
+    }
+    
+    /// Number of loaded accounts in rate-limit cooldown for at least one model family
+    pub fn rate_limited_count(&self) -> usize {
+        self.tokens
+            .iter()
+            .filter(|e| self.rate_limit_tracker.is_limited_any(e.key()))
+            .count()
     }
     
-    pub fn is_rate_limited(&self, account_id: &str) -> bool {
-        self.rate_limit_tracker.is_rate_limited(account_id)
+    pub fn is_rate_limited(&self, account_id: &str, model: &str) -> bool {
+        self.rate_limit_tracker.is_rate_limited(account_id, &model_family(model))
     }
     
     pub async fn get_sticky_config(&self) -> StickySessionConfig {
@@ -387,4 +502,192 @@ impl TokenManager {
     pub fn clear_all_sessions(&self) {
         self.session_accounts.clear();
     }
+    
+    /// List loaded accounts with their rate-limit state, followed by accounts
+    /// excluded from the pool via `proxy_disabled`
+    pub fn list_accounts(&self) -> Vec<AccountStatus> {
+        let mut accounts: Vec<AccountStatus> = self.tokens.iter().map(|e| {
+            let token = e.value();
+            let limits = self.rate_limit_tracker.get_limits(&token.account_id);
+            AccountStatus {
+                account_id: token.account_id.clone(),
+                email: token.email.clone(),
+                subscription_tier: token.subscription_tier.clone(),
+                project_id: token.project_id.clone(),
+                disabled: false,
+                rate_limited: !limits.is_empty(),
+                rate_limits: limits,
+            }
+        }).collect();
+        accounts.sort_by(|a, b| a.email.cmp(&b.email));
+        
+        let entries = match std::fs::read_dir(self.data_dir.join("accounts")) {
+            Ok(entries) => entries,
+            Err(_) => return accounts,
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let Ok(account) = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).map_err(Into::into))
+            else {
+                continue;
+            };
+            if !account.get("proxy_disabled").and_then(|v| v.as_bool()).unwrap_or(false) {
+                continue;
+            }
+            accounts.push(AccountStatus {
+                account_id: account["id"].as_str().unwrap_or_default().to_string(),
+                email: account["email"].as_str().unwrap_or_default().to_string(),
+                subscription_tier: account.get("quota")
+                    .and_then(|q| q.get("subscription_tier"))
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                project_id: account["token"].get("project_id")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                disabled: true,
+                rate_limited: false,
+                rate_limits: Vec::new(),
+            });
+        }
+        
+        accounts
+    }
+    
+    /// Enable or disable an account for the proxy pool.
+    /// The `proxy_disabled` flag is persisted to the account file so it survives reloads.
+    pub async fn set_account_enabled(&self, account_id: &str, enabled: bool) -> Result<(), AccountError> {
+        // The id comes from a URL path; it must name a file directly inside the accounts dir
+        if !is_account_file_stem(account_id) {
+            return Err(AccountError::InvalidId(account_id.to_string()));
+        }
+        let path = match self.tokens.get(account_id) {
+            Some(token) => token.account_path.clone(),
+            None => self.data_dir.join("accounts").join(format!("{}.json", account_id)),
+        };
+        if !path.is_file() {
+            return Err(AccountError::NotFound(account_id.to_string()));
+        }
+        
+        let mut content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        if content["id"].as_str() != Some(account_id) {
+            return Err(AccountError::NotFound(account_id.to_string()));
+        }
+        content["proxy_disabled"] = serde_json::Value::Bool(!enabled);
+        std::fs::write(&path, serde_json::to_string_pretty(&content)?)?;
+        
+        if enabled {
+            match self.load_single_account(&path).await? {
+                Some(token) => {
+                    self.tokens.insert(token.account_id.clone(), token);
+                }
+                None => return Err(AccountError::DisabledInFile(account_id.to_string())),
+            }
+        } else {
+            self.tokens.remove(account_id);
+            self.session_accounts.retain(|_, (bound, _)| bound != account_id);
+            let mut last_used = self.last_used_account.lock().await;
+            if last_used.as_ref().is_some_and(|(id, _)| id == account_id) {
+                *last_used = None;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Clear the rate-limit cooldown for an account, for one model family or all.
+    /// Returns false if it had none.
+    pub fn clear_rate_limit(&self, account_id: &str, family: Option<&str>) -> bool {
+        self.rate_limit_tracker.clear(account_id, family)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("antigravity-tokens-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("accounts")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_set_account_enabled_rejects_paths() {
+        let dir = data_dir();
+        let secret = dir.join("secret.json");
+        std::fs::write(&secret, r#"{"id": "secret"}"#).unwrap();
+        std::fs::write(dir.join("accounts").join("acc-1.json"), r#"{"id": "acc-1"}"#).unwrap();
+        let manager = TokenManager::new(dir.clone());
+
+        for id in ["../secret", "..", "a/b", "a\\b", ".hidden", ""] {
+            let err = manager.set_account_enabled(id, false).await.unwrap_err();
+            assert!(matches!(err, AccountError::InvalidId(_)), "{}: {}", id, err);
+        }
+        assert_eq!(std::fs::read_to_string(&secret).unwrap(), r#"{"id": "secret"}"#);
+
+        let err = manager.set_account_enabled("acc-2", false).await.unwrap_err();
+        assert!(matches!(err, AccountError::NotFound(_)));
+
+        manager.set_account_enabled("acc-1", false).await.unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("accounts").join("acc-1.json")).unwrap()).unwrap();
+        assert_eq!(saved["proxy_disabled"], true);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn valid_token(account_id: &str) -> ProxyToken {
+        ProxyToken {
+            account_id: account_id.to_string(),
+            access_token: format!("at-{}", account_id),
+            refresh_token: String::new(),
+            expires_in: 3600,
+            timestamp: chrono::Utc::now().timestamp() + 3600,
+            email: format!("{}@example.com", account_id),
+            account_path: PathBuf::new(),
+            project_id: Some("project".to_string()),
+            subscription_tier: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_binding_follows_served_account() {
+        let manager = TokenManager::new(data_dir());
+        manager.tokens.insert("acc-1".to_string(), valid_token("acc-1"));
+        manager.tokens.insert("acc-2".to_string(), valid_token("acc-2"));
+
+        let (_, _, _, first) = manager.get_token("text", "gemini-2.5-flash", false, Some("s1"), None).await.unwrap();
+        assert_eq!(manager.bound_account("s1").as_deref(), Some(first.as_str()));
+
+        // A rotated retry rebinds the session to the account that actually served it
+        let (_, _, _, second) = manager.get_token("text", "gemini-2.5-flash", true, Some("s1"), None).await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(manager.bound_account("s1").as_deref(), Some(second.as_str()));
+    }
+
+    #[test]
+    fn test_session_bindings_expire_and_are_capped() {
+        let mut manager = TokenManager::new(data_dir());
+        manager.session_ttl = Duration::from_secs(60);
+        let now = Instant::now();
+        let idle = now - Duration::from_secs(60);
+        manager.session_accounts.insert("stale".to_string(), ("acc-1".to_string(), idle));
+        assert_eq!(manager.bound_account("stale"), None);
+        assert!(manager.session_accounts.is_empty());
+
+        for i in 0..5u64 {
+            let last_used = now - Duration::from_secs(10 - i);
+            manager.session_accounts.insert(format!("s{}", i), ("acc-1".to_string(), last_used));
+        }
+        manager.session_accounts.insert("idle".to_string(), ("acc-1".to_string(), idle));
+        manager.prune_sessions(3);
+        assert_eq!(manager.session_accounts.len(), 3);
+        assert!(!manager.session_accounts.contains_key("idle"));
+        assert!(!manager.session_accounts.contains_key("s0"));
+        assert!(manager.session_accounts.contains_key("s4"));
+    }
 }
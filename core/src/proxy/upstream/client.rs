@@ -19,12 +19,17 @@ pub struct UpstreamClient {
 
 impl UpstreamClient {
     pub fn new(proxy_url: Option<String>) -> Self {
+        Self::with_connect_timeout(proxy_url, Duration::from_secs(20))
+    }
+    
+    /// No total timeout is set on the client: handlers enforce `[timeouts]`
+    /// per call so long streams are not cut off
+    pub fn with_connect_timeout(proxy_url: Option<String>, connect_timeout: Duration) -> Self {
         let mut builder = Client::builder()
-            .connect_timeout(Duration::from_secs(20))
+            .connect_timeout(connect_timeout)
             .pool_max_idle_per_host(16)
             .pool_idle_timeout(Duration::from_secs(90))
             .tcp_keepalive(Duration::from_secs(60))
-            .timeout(Duration::from_secs(600))
             .user_agent("antigravity/1.11.9 cli");
         
         if let Some(proxy) = proxy_url {
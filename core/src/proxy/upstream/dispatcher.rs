@@ -0,0 +1,288 @@
+//! Upstream dispatcher
+//! Shared by every protocol handler: token acquisition, account rotation on
+//! 401/403/429/5xx, network errors and per-attempt timeouts, backoff and
+//! rate-limit bookkeeping around `call_v1_internal`;
+//! `dispatch_once` serves auxiliary single-account calls without the bookkeeping
+
+use std::time::Duration;
+
+use rand::Rng;
+use serde_json::Value;
+use tokio::time::{sleep, Instant};
+
+use crate::proxy::metrics::metrics;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::retry::parse_retry_delay;
+
+/// Upper bound on attempts per client request (further capped by the pool size)
+pub const MAX_RETRY_ATTEMPTS: usize = 3;
+/// Upstream retry delays above this are not slept on; the account is benched and rotated instead
+const MAX_BACKOFF_MS: u64 = 5_000;
+const JITTER_FACTOR: f64 = 0.2;
+
+/// What to send upstream and which accounts may serve it
+pub struct DispatchRequest<'a> {
+    /// Quota group passed to `TokenManager::get_token` ("agent", "image_gen", ...)
+    pub quota_group: &'a str,
+    pub session_id: Option<&'a str>,
+    pub allowed_accounts: Option<&'a [String]>,
+    /// v1internal method, e.g. `generateContent` or `streamGenerateContent`
+    pub method: &'a str,
+    pub query: Option<&'a str>,
+    /// Mapped upstream model, selects the rate-limit family and labels metrics
+    pub model: &'a str,
+    /// Limit for receiving response headers on each attempt
+    pub timeout: Duration,
+    pub trace_id: &'a str,
+}
+
+/// Account that served a request
+#[derive(Debug, Clone)]
+pub struct UpstreamAccount {
+    pub account_id: String,
+    pub email: String,
+    pub project_id: String,
+}
+
+pub struct Dispatched {
+    pub response: reqwest::Response,
+    pub account: UpstreamAccount,
+    /// Deadline of the successful attempt, so callers can bound body reads / first chunk
+    pub deadline: Instant,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DispatchError {
+    /// No account could be acquired from the pool
+    #[error("No available accounts: {0}")]
+    NoAccounts(String),
+    /// The request body could not be built for upstream
+    #[error("{0}")]
+    InvalidRequest(String),
+    /// Last upstream error after all attempts (or a non-retryable status)
+    #[error("HTTP {status}: {body}")]
+    Upstream {
+        status: u16,
+        body: String,
+        retry_after: Option<String>,
+    },
+    #[error("{0}")]
+    Network(String),
+    #[error("Upstream did not respond within {}s", .0.as_secs())]
+    Timeout(Duration),
+}
+
+impl DispatchError {
+    /// HTTP status to report to the client
+    pub fn status(&self) -> axum::http::StatusCode {
+        use axum::http::StatusCode;
+        match self {
+            DispatchError::NoAccounts(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DispatchError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            DispatchError::Upstream { status, .. } => {
+                StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY)
+            }
+            DispatchError::Network(_) => StatusCode::BAD_GATEWAY,
+            DispatchError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+/// Statuses that rotate to another account
+fn should_rotate(status: u16) -> bool {
+    matches!(status, 401 | 403 | 429) || (500..600).contains(&status)
+}
+
+/// Apply jitter to delay
+fn apply_jitter(delay_ms: u64) -> u64 {
+    let jitter_range = (delay_ms as f64 * JITTER_FACTOR) as i64;
+    let jitter: i64 = rand::rng().random_range(-jitter_range..=jitter_range);
+    ((delay_ms as i64) + jitter).max(1) as u64
+}
+
+/// Delay before the next attempt: the upstream RetryInfo when it is short,
+/// otherwise a linear backoff by status
+fn backoff_ms(status: u16, error_body: &str, attempt: usize) -> u64 {
+    if let Some(ms) = parse_retry_delay(error_body).filter(|ms| *ms <= MAX_BACKOFF_MS) {
+        return ms;
+    }
+    match status {
+        429 => apply_jitter(1000 * (attempt as u64 + 1)),
+        500..=599 => apply_jitter(500 * (attempt as u64 + 1)),
+        _ => 0,
+    }
+}
+
+/// Send a request upstream, rotating accounts until one succeeds.
+///
+/// `build_body` receives the project id of the account chosen for each attempt.
+pub async fn dispatch<F>(
+    state: &AppState,
+    req: DispatchRequest<'_>,
+    mut build_body: F,
+) -> Result<Dispatched, DispatchError>
+where
+    F: FnMut(&str) -> Result<Value, String>,
+{
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(state.token_manager.len()).max(1);
+    let mut last_error: Option<DispatchError> = None;
+
+    for attempt in 0..max_attempts {
+        let (access_token, project_id, email, account_id) = match state
+            .token_manager
+            .get_token(req.quota_group, req.model, attempt > 0, req.session_id, req.allowed_accounts)
+            .await
+        {
+            Ok(t) => t,
+            // Keep the upstream error from the previous attempt if rotation ran dry
+            Err(e) => return Err(last_error.unwrap_or(DispatchError::NoAccounts(e.to_string()))),
+        };
+
+        tracing::info!(
+            "[{}] Upstream attempt {}/{} | Account: {} | Model: {}",
+            req.trace_id,
+            attempt + 1,
+            max_attempts,
+            email,
+            req.model
+        );
+
+        let body = build_body(&project_id).map_err(DispatchError::InvalidRequest)?;
+
+        let deadline = Instant::now() + req.timeout;
+        let call = state.upstream.call_v1_internal(req.method, &access_token, body, req.query);
+        let response = match tokio::time::timeout_at(deadline, call).await {
+            Ok(Ok(r)) => r,
+            Ok(Err(e)) => {
+                tracing::debug!("[{}] Request failed: {}", req.trace_id, e);
+                last_error = Some(DispatchError::Network(e));
+                continue;
+            }
+            // A hung account is rotated like a network error; the next one gets a fresh deadline
+            Err(_) => {
+                tracing::warn!("[{}] No response from {} within {}s", req.trace_id, email, req.timeout.as_secs());
+                last_error = Some(DispatchError::Timeout(req.timeout));
+                continue;
+            }
+        };
+
+        let status = response.status().as_u16();
+        metrics().record_upstream(req.model, &email, status);
+
+        if response.status().is_success() {
+            return Ok(Dispatched {
+                response,
+                account: UpstreamAccount { account_id, email, project_id },
+                deadline,
+            });
+        }
+
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+        let error_body = match tokio::time::timeout_at(deadline, response.text()).await {
+            Ok(Ok(text)) => text,
+            _ => format!("HTTP {}", status),
+        };
+        tracing::debug!("[{}] Upstream error {} from {}: {}", req.trace_id, status, email, error_body);
+
+        if status == 429 || status >= 500 {
+            state
+                .token_manager
+                .mark_rate_limited(&account_id, req.model, status, retry_after.as_deref(), &error_body);
+        }
+
+        let rotate = should_rotate(status);
+        let delay = if rotate { backoff_ms(status, &error_body, attempt) } else { 0 };
+        last_error = Some(DispatchError::Upstream { status, body: error_body, retry_after });
+
+        if !rotate {
+            break;
+        }
+        if attempt + 1 < max_attempts && delay > 0 {
+            sleep(Duration::from_millis(delay)).await;
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| DispatchError::Network("All upstream attempts failed".to_string())))
+}
+
+/// Send an auxiliary request (e.g. `countTokens`) on a single account.
+///
+/// No rotation and no rate-limit bookkeeping: a failing side call must not bench an
+/// account for generation traffic, and the caller falls back locally instead.
+pub async fn dispatch_once<F>(
+    state: &AppState,
+    req: DispatchRequest<'_>,
+    build_body: F,
+) -> Result<Dispatched, DispatchError>
+where
+    F: FnOnce(&str) -> Result<Value, String>,
+{
+    let (access_token, project_id, email, account_id) = state
+        .token_manager
+        .get_token(req.quota_group, req.model, false, req.session_id, req.allowed_accounts)
+        .await
+        .map_err(|e| DispatchError::NoAccounts(e.to_string()))?;
+    let body = build_body(&project_id).map_err(DispatchError::InvalidRequest)?;
+
+    let deadline = Instant::now() + req.timeout;
+    let call = state.upstream.call_v1_internal(req.method, &access_token, body, req.query);
+    let response = match tokio::time::timeout_at(deadline, call).await {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => return Err(DispatchError::Network(e)),
+        Err(_) => return Err(DispatchError::Timeout(req.timeout)),
+    };
+
+    let status = response.status().as_u16();
+    metrics().record_upstream(req.model, &email, status);
+    if !response.status().is_success() {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+        let body = match tokio::time::timeout_at(deadline, response.text()).await {
+            Ok(Ok(text)) => text,
+            _ => format!("HTTP {}", status),
+        };
+        tracing::debug!("[{}] {} failed with {} on {}: {}", req.trace_id, req.method, status, email, body);
+        return Err(DispatchError::Upstream { status, body, retry_after });
+    }
+
+    Ok(Dispatched {
+        response,
+        account: UpstreamAccount { account_id, email, project_id },
+        deadline,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_rotate() {
+        for status in [401, 403, 429, 500, 503, 529] {
+            assert!(should_rotate(status), "{}", status);
+        }
+        for status in [400, 404, 413] {
+            assert!(!should_rotate(status), "{}", status);
+        }
+    }
+
+    #[test]
+    fn test_backoff_uses_short_retry_info() {
+        let body = r#"{"error":{"details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"1.5s"}]}}"#;
+        assert_eq!(backoff_ms(429, body, 0), 1500);
+
+        // Long quota resets are not slept on; fall back to the linear backoff
+        let body = r#"{"error":{"details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"3600s"}]}}"#;
+        let delay = backoff_ms(429, body, 0);
+        assert!((800..=1200).contains(&delay));
+
+        assert_eq!(backoff_ms(403, "", 0), 0);
+    }
+}
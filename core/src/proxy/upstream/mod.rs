@@ -1,4 +1,6 @@
 //! Upstream client modules
 
 pub mod client;
+pub mod dispatcher;
 pub mod retry;
+pub mod timeouts;
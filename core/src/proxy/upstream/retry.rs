@@ -34,19 +34,34 @@ pub fn parse_duration_ms(duration_str: &str) -> Option<u64> {
     Some(total_ms.round() as u64)
 }
 
-/// 从 429 错误中提取 retry delay
+/// 解析 protobuf Duration 的 JSON 对象形式 ({"seconds": 60, "nanos": 0})
+fn parse_duration_object(value: &serde_json::Value) -> Option<u64> {
+    let seconds = value.get("seconds").and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok()))?;
+    let nanos = value.get("nanos").and_then(|v| v.as_u64()).unwrap_or(0);
+    Some(seconds * 1000 + nanos / 1_000_000)
+}
+
+/// 从 429/5xx 错误中提取 retry delay (毫秒)
+/// 唯一实现: 账号冷却 (RateLimitTracker) 与重试退避 (dispatcher) 共用
 pub fn parse_retry_delay(error_text: &str) -> Option<u64> {
     use serde_json::Value;
 
     let json: Value = serde_json::from_str(error_text).ok()?;
+    // 上游有时返回 [{"error": {...}}]
+    let json = match json {
+        Value::Array(mut items) if !items.is_empty() => items.swap_remove(0),
+        other => other,
+    };
     let details = json.get("error")?.get("details")?.as_array()?;
 
     // 方式1: RetryInfo.retryDelay
     for detail in details {
         if let Some(type_str) = detail.get("@type").and_then(|v| v.as_str()) {
             if type_str.contains("RetryInfo") {
-                if let Some(retry_delay) = detail.get("retryDelay").and_then(|v| v.as_str()) {
-                    return parse_duration_ms(retry_delay);
+                match detail.get("retryDelay") {
+                    Some(Value::String(retry_delay)) => return parse_duration_ms(retry_delay),
+                    Some(obj @ Value::Object(_)) => return parse_duration_object(obj),
+                    _ => {}
                 }
             }
         }
@@ -91,4 +106,31 @@ mod tests {
 
         assert_eq!(parse_retry_delay(error_json), Some(1204));
     }
+
+    #[test]
+    fn test_parse_quota_reset_delay_hours() {
+        let error_json = r#"[{
+            "error": {
+                "code": 429,
+                "details": [{
+                    "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                    "reason": "RATE_LIMIT_EXCEEDED",
+                    "metadata": {"quotaResetDelay": "3h5m10s"}
+                }]
+            }
+        }]"#;
+
+        assert_eq!(parse_retry_delay(error_json), Some(11_110_000));
+    }
+
+    #[test]
+    fn test_parse_retry_delay_object_form() {
+        let error_json = r#"{"error":{"details":[{
+            "@type":"type.googleapis.com/google.rpc.RetryInfo",
+            "retryDelay":{"seconds":"42","nanos":500000000}
+        }]}}"#;
+
+        assert_eq!(parse_retry_delay(error_json), Some(42_500));
+        assert_eq!(parse_retry_delay("not json"), None);
+    }
 }
@@ -0,0 +1,180 @@
+//! Upstream timeouts
+//! Resolves `[timeouts]` (with per-model overrides) and enforces the stream
+//! first-byte and idle-gap limits
+
+use std::pin::Pin;
+use std::time::Duration;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde_json::json;
+
+use crate::config::TimeoutsConfig;
+use crate::proxy::common::utils::glob_match;
+use crate::proxy::middleware::auth::ClientProtocol;
+
+/// Effective timeouts for one upstream model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelTimeouts {
+    pub request: Duration,
+    pub first_byte: Duration,
+    pub idle: Duration,
+}
+
+impl ModelTimeouts {
+    /// Apply the override whose glob matches `model`. An exact key wins,
+    /// otherwise the longest matching pattern.
+    pub fn resolve(config: &TimeoutsConfig, model: &str) -> Self {
+        let matched = config.models.get(model).or_else(|| {
+            config
+                .models
+                .iter()
+                .filter(|(pattern, _)| glob_match(pattern, model))
+                .max_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| b.0.cmp(a.0)))
+                .map(|(_, o)| o)
+        });
+
+        let pick = |over: Option<u64>, global: u64| Duration::from_secs(over.unwrap_or(global));
+        Self {
+            request: pick(matched.and_then(|o| o.request_timeout), config.request_timeout),
+            first_byte: pick(
+                matched.and_then(|o| o.stream_first_byte_timeout),
+                config.stream_first_byte_timeout,
+            ),
+            idle: pick(matched.and_then(|o| o.stream_idle_timeout), config.stream_idle_timeout),
+        }
+    }
+}
+
+/// Error item of an upstream byte stream
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error("upstream stream error: {0}")]
+    Upstream(#[from] reqwest::Error),
+    #[error("no data from upstream within {}s", .0.as_secs())]
+    FirstByteTimeout(Duration),
+    #[error("upstream stream stalled for more than {}s", .0.as_secs())]
+    IdleTimeout(Duration),
+}
+
+impl StreamError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, StreamError::FirstByteTimeout(_) | StreamError::IdleTimeout(_))
+    }
+}
+
+pub type UpstreamByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, StreamError>> + Send>>;
+
+/// Wrap an upstream body stream so it ends with a timeout error when the first
+/// chunk takes longer than `first_byte`, or later chunks are more than `idle` apart
+pub fn with_stream_timeouts<S>(stream: S, first_byte: Duration, idle: Duration) -> UpstreamByteStream
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let mut stream = Box::pin(stream);
+        let mut received_any = false;
+
+        loop {
+            let limit = if received_any { idle } else { first_byte };
+            match tokio::time::timeout(limit, stream.next()).await {
+                Ok(Some(Ok(chunk))) => {
+                    received_any = true;
+                    yield Ok(chunk);
+                }
+                Ok(Some(Err(e))) => {
+                    yield Err(StreamError::Upstream(e));
+                    break;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    yield Err(if received_any {
+                        StreamError::IdleTimeout(limit)
+                    } else {
+                        StreamError::FirstByteTimeout(limit)
+                    });
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Protocol-appropriate 504 for an upstream call that exceeded its timeout
+pub fn timeout_response(protocol: ClientProtocol, message: &str) -> Response {
+    tracing::warn!("Upstream timeout: {}", message);
+    let body = match protocol {
+        ClientProtocol::OpenAI => json!({
+            "error": {
+                "message": message,
+                "type": "timeout_error",
+                "param": null,
+                "code": "timeout"
+            }
+        }),
+        ClientProtocol::Anthropic => json!({
+            "type": "error",
+            "error": {
+                "type": "timeout_error",
+                "message": message
+            }
+        }),
+        ClientProtocol::Gemini => json!({
+            "error": {
+                "code": 504,
+                "message": message,
+                "status": "DEADLINE_EXCEEDED"
+            }
+        }),
+    };
+    (StatusCode::GATEWAY_TIMEOUT, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelTimeoutsConfig;
+
+    #[test]
+    fn test_resolve_model_overrides() {
+        let mut config = TimeoutsConfig::default();
+        config.models.insert(
+            "*-thinking".to_string(),
+            ModelTimeoutsConfig { request_timeout: Some(600), ..Default::default() },
+        );
+        config.models.insert(
+            "claude-opus-*-thinking".to_string(),
+            ModelTimeoutsConfig { stream_first_byte_timeout: Some(300), ..Default::default() },
+        );
+
+        let flash = ModelTimeouts::resolve(&config, "gemini-2.5-flash");
+        assert_eq!(flash.request, Duration::from_secs(config.request_timeout));
+
+        let sonnet = ModelTimeouts::resolve(&config, "claude-sonnet-4-5-thinking");
+        assert_eq!(sonnet.request, Duration::from_secs(600));
+
+        // Most specific pattern wins; its unset fields fall back to the global value
+        let opus = ModelTimeouts::resolve(&config, "claude-opus-4-5-thinking");
+        assert_eq!(opus.first_byte, Duration::from_secs(300));
+        assert_eq!(opus.request, Duration::from_secs(config.request_timeout));
+    }
+
+    #[tokio::test]
+    async fn test_stream_idle_timeout() {
+        let upstream = async_stream::stream! {
+            yield Ok::<_, reqwest::Error>(Bytes::from_static(b"data: {}\n\n"));
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            yield Ok(Bytes::from_static(b"data: {}\n\n"));
+        };
+        let mut stream = with_stream_timeouts(upstream, Duration::from_secs(1), Duration::from_millis(50));
+
+        assert!(stream.next().await.unwrap().is_ok());
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, StreamError::IdleTimeout(_)));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,78 @@
+//! Per-API-key usage statistics
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+/// Aggregated usage for a single client API key
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KeyUsage {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub last_used: i64,
+}
+
+pub struct UsageTracker {
+    /// key name -> usage
+    by_key: DashMap<String, KeyUsage>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self {
+            by_key: DashMap::new(),
+        }
+    }
+
+    /// Record an authenticated request for a key
+    pub fn record_request(&self, key_name: &str) {
+        let mut entry = self.by_key.entry(key_name.to_string()).or_default();
+        entry.requests += 1;
+        entry.last_used = chrono::Utc::now().timestamp();
+    }
+
+    /// Record token usage reported by upstream for a key
+    pub fn record_tokens(&self, key_name: &str, input_tokens: u32, output_tokens: u32) {
+        let mut entry = self.by_key.entry(key_name.to_string()).or_default();
+        entry.input_tokens += input_tokens as u64;
+        entry.output_tokens += output_tokens as u64;
+    }
+
+    /// Snapshot of all keys, sorted by name
+    pub fn snapshot(&self) -> Vec<(String, KeyUsage)> {
+        let mut all: Vec<_> = self
+            .by_key
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_accumulates_per_key() {
+        let tracker = UsageTracker::new();
+        tracker.record_request("ci");
+        tracker.record_request("ci");
+        tracker.record_tokens("ci", 100, 20);
+        tracker.record_request("alice");
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, "alice");
+        assert_eq!(snapshot[1].1.requests, 2);
+        assert_eq!(snapshot[1].1.input_tokens, 100);
+        assert_eq!(snapshot[1].1.output_tokens, 20);
+    }
+}